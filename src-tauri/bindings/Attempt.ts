@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface Attempt { job_id: string, attempt: number, max_attempts: number, next_attempt_at: bigint | null, }
\ No newline at end of file
@@ -1,5 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Destination } from "./Destination";
+import type { Hooks } from "./Hooks";
 import type { Location } from "./Location";
 import type { Options } from "./Options";
 
-export interface Backup { client_location: Location, server_location: Location, latest_run: bigint | null, options: Options | null, }
\ No newline at end of file
+export interface Backup { client_location: Location, server_location: Location, latest_run: bigint | null, options: Options | null, mirrors?: Array<Destination>, hooks?: Hooks, databases?: Array<string>, }
\ No newline at end of file
@@ -1,3 +1,5 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Notifications } from "./Notifications";
+import type { RetryPolicy } from "./RetryPolicy";
 
-export interface Config { client_name: string, username: string, server_address: string, server_port: number, allow_background_backup: boolean, }
\ No newline at end of file
+export interface Config { client_name: string, username: string, server_address: string, server_port: number, allow_background_backup: boolean, identity_file?: string, proxy_jump?: string, ssh_options?: Array<string>, known_hosts_file?: string, notifications?: Notifications, metrics_port?: number, retry?: RetryPolicy, max_transfers?: number, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type DaemonRequest = "Status" | "Stop" | "Reload" | { RunBackup: string } | "Pause" | "Resume" | "Metrics" | { CancelJob: string } | { Watch: string } | { Unwatch: string } | "UnwatchAll";
\ No newline at end of file
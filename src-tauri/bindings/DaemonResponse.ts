@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DaemonStatus } from "./DaemonStatus";
+
+export type DaemonResponse = { Ack: string } | { Status: DaemonStatus } | { Metrics: string } | { Error: string };
\ No newline at end of file
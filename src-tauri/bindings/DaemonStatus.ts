@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Attempt } from "./Attempt";
+
+export interface DaemonStatus { pid: number, paused: boolean, jobs: Array<string>, failed_jobs: Array<string>, retrying: Array<Attempt>, cancelled: Array<string>, queued: Array<string>, }
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface Email { server_url: string, from: string, to: Array<string>, username?: string, password?: string, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { HookStage } from "./HookStage";
+
+export interface HookRun { stage: HookStage, command: string, exit_code: number | null, timed_out: boolean, output: string, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Hook } from "./Hook";
+
+export interface Hooks { pre?: Hook, post?: Hook, abort_on_pre_failure: boolean, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { HostKey } from "./HostKey";
+
+export interface HostKeyScan { known_hosts_file: string, keys: Array<HostKey>, }
\ No newline at end of file
@@ -1,3 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Attempt } from "./Attempt";
+import type { FailureKind } from "./FailureKind";
+import type { JobState } from "./JobState";
+import type { Transfer } from "./Transfer";
 
-export type JobStatus = "Running" | "Failed" | "Completed";
\ No newline at end of file
+export interface JobStatus { job_id: string, state: JobState, started_at: bigint | null, ended_at: bigint | null, error: string | null, error_kind: FailureKind | null, attempt: Attempt | null, progress: Transfer | null, }
\ No newline at end of file
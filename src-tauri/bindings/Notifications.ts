@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Email } from "./Email";
+
+export interface Notifications { desktop: boolean, completions: boolean, webhook_url?: string, email?: Email, failing_threshold_minutes?: number, }
\ No newline at end of file
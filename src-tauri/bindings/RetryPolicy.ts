@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface RetryPolicy { max_attempts: number, initial_delay_seconds: number, max_delay_seconds: number, }
\ No newline at end of file
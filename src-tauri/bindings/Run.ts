@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { HookRun } from "./HookRun";
+import type { RunStatus } from "./RunStatus";
+import type { Transfer } from "./Transfer";
+import type { Trigger } from "./Trigger";
+
+export interface Run { job_id: string, client_path: string, server_path: string, trigger: Trigger, started_at: bigint, ended_at: bigint, transfer: Transfer, status: RunStatus, error: string | null, pid?: number, hooks?: Array<HookRun>, }
\ No newline at end of file
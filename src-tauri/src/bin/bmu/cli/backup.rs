@@ -0,0 +1,398 @@
+use super::{print, Args};
+use crate::{daemon, storage::Storage, Error};
+use back_me_up::control::{self, Request, Response};
+use back_me_up::jobs::{self, history::History};
+use back_me_up::models::app::MutexState;
+use back_me_up::models::backup::{Backup, Destination, Hook, Hooks, Location, Options};
+use back_me_up::ssh::connect::Connection;
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Serialize)]
+struct Entry {
+    /// Position in `bmu backup list`, starting at 1
+    id: usize,
+    #[serde(flatten)]
+    backup: Backup,
+}
+
+#[derive(Serialize)]
+struct RunResult {
+    id: String,
+    client_path: String,
+    status: jobs::Status,
+}
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let subcommand = args.first().map_or("", String::as_str);
+    let args = args.get(1..).unwrap_or_default();
+
+    match subcommand {
+        "list" => list(&Args::parse(args, &[], &["json"])?),
+        "add" => add(&Args::parse(
+            args,
+            &[
+                "source",
+                "dest",
+                "mirror",
+                "database",
+                "pre-hook",
+                "post-hook",
+                "hook-timeout",
+            ],
+            &["use-client-directory", "continue-on-hook-failure", "json"],
+        )?),
+        "run" => run_backups(&Args::parse(args, &[], &["all", "detach", "json"])?),
+        "remove" => remove(&Args::parse(args, &[], &["json"])?),
+        _ => Err(Error::Usage(String::from(
+            "Expected 'bmu backup [list|add|run|remove]'",
+        ))),
+    }
+}
+
+/// Finds a backup by its position in `bmu backup list` or by its client path.
+fn find(backups: &[Backup], id: &str) -> Result<Backup, Error> {
+    let by_position = id
+        .parse::<usize>()
+        .ok()
+        .and_then(|position| position.checked_sub(1))
+        .and_then(|index| backups.get(index));
+    let by_path = || {
+        backups
+            .iter()
+            .find(|backup| backup.client_location.path == id.trim_end_matches('/'))
+    };
+
+    by_position
+        .or_else(by_path)
+        .cloned()
+        .ok_or_else(|| Error::Usage(format!("No backup with id '{id}'")))
+}
+
+fn location(path: &str) -> Location {
+    let path = path.trim_end_matches('/').to_string();
+    let entity_name = path.split('/').last().unwrap_or_default().to_string();
+
+    Location { path, entity_name }
+}
+
+fn list(args: &Args) -> Result<(), Error> {
+    let backups = Storage::load()?.backups()?;
+    let entries: Vec<Entry> = backups
+        .into_iter()
+        .enumerate()
+        .map(|(index, backup)| Entry {
+            id: index + 1,
+            backup,
+        })
+        .collect();
+    let text = if entries.is_empty() {
+        String::from("No backups, add one with 'bmu backup add'")
+    } else {
+        entries
+            .iter()
+            .map(|entry| format!("{:>3}  {}", entry.id, entry.backup))
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+
+    print(args.flag("json"), &entries, &text);
+    Ok(())
+}
+
+/// Hooks given by `--pre-hook` and `--post-hook`, which share the timeout of `--hook-timeout`.
+fn hooks(args: &Args) -> Result<Option<Hooks>, Error> {
+    let timeout_seconds = match args.option("hook-timeout") {
+        Some(seconds) => Some(seconds.parse().map_err(|_| {
+            Error::Usage(format!(
+                "--hook-timeout must be a number of seconds, got '{seconds}'"
+            ))
+        })?),
+        None => None,
+    };
+    let hook = |name: &str| {
+        args.option(name).map(|command| Hook {
+            command: command.to_string(),
+            timeout_seconds,
+        })
+    };
+    let (pre, post) = (hook("pre-hook"), hook("post-hook"));
+
+    if pre.is_none() && post.is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(Hooks {
+        pre,
+        post,
+        abort_on_pre_failure: !args.flag("continue-on-hook-failure"),
+    }))
+}
+
+fn add(args: &Args) -> Result<(), Error> {
+    let storage = Storage::load()?;
+    let config = storage
+        .config()
+        .ok_or_else(|| Error::State(String::from("No config, run 'bmu' to setup")))?;
+    let source = args.require("source")?;
+    let dest = args.require("dest")?;
+
+    if !Path::new(source).is_absolute() || !Path::new(source).is_dir() {
+        return Err(Error::Usage(format!(
+            "--source must be an absolute path to a directory, got '{source}'"
+        )));
+    }
+
+    // folders are picked from the home directory on the server in the interactive menu as well
+    let dest = if dest.starts_with('/') {
+        dest.to_string()
+    } else {
+        format!("/home/{}/{dest}", config.username)
+    };
+
+    let mirrors: Vec<Destination> = args
+        .options("mirror")
+        .iter()
+        .map(|path| Destination::Disk(location(path)))
+        .collect();
+
+    let databases: Vec<String> = args
+        .options("database")
+        .iter()
+        .map(|path| path.trim_start_matches('/').to_string())
+        .collect();
+
+    if !databases.is_empty() && !jobs::snapshot::sqlite3_is_installed() {
+        return Err(Error::State(String::from(
+            "sqlite3 is required to back up databases, install it first",
+        )));
+    }
+
+    let backup = Backup {
+        client_location: location(source),
+        server_location: location(&dest),
+        latest_run: None,
+        options: Some(Options {
+            use_client_directory: args.flag("use-client-directory"),
+        }),
+        mirrors: if mirrors.is_empty() {
+            None
+        } else {
+            Some(mirrors)
+        },
+        hooks: hooks(args)?,
+        databases: if databases.is_empty() {
+            None
+        } else {
+            Some(databases)
+        },
+    };
+
+    storage.add_backup(backup.clone())?;
+
+    let id = storage.backups()?.len();
+    print(
+        args.flag("json"),
+        &Entry {
+            id,
+            backup: backup.clone(),
+        },
+        &format!("✅ Added backup {id}: {backup}"),
+    );
+    Ok(())
+}
+
+fn remove(args: &Args) -> Result<(), Error> {
+    let storage = Storage::load()?;
+    let id = args
+        .positional(0)
+        .ok_or_else(|| Error::Usage(String::from("Missing backup id")))?;
+    let backup = find(&storage.backups()?, id)?;
+
+    storage.delete_backup(&backup)?;
+
+    print(
+        args.flag("json"),
+        &backup,
+        &format!("✅ Removed backup: {backup}"),
+    );
+    Ok(())
+}
+
+fn run_backups(args: &Args) -> Result<(), Error> {
+    let storage = Storage::load()?;
+    let backups = storage.backups()?;
+    let backups = match (args.flag("all"), args.positional(0)) {
+        (true, None) => backups,
+        (false, Some(id)) => vec![find(&backups, id)?],
+        _ => {
+            return Err(Error::Usage(String::from(
+                "Expected either a backup id or --all",
+            )))
+        }
+    };
+
+    if args.flag("detach") {
+        return detach(&storage, &backups, args.flag("json"));
+    }
+
+    let results = execute(&storage, backups)?;
+    let count = |status: fn(&jobs::Status) -> bool| {
+        results
+            .iter()
+            .filter(|result| status(&result.status))
+            .count()
+    };
+    let failed = count(|status| matches!(status, jobs::Status::Failed));
+    let cancelled = count(|status| matches!(status, jobs::Status::Cancelled));
+    let text = results
+        .iter()
+        .map(|result| {
+            let icon = match result.status {
+                jobs::Status::Failed => "⛔️",
+                jobs::Status::Cancelled => "✋",
+                _ => "✅",
+            };
+            format!("{icon} {}", result.client_path)
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    print(args.flag("json"), &results, &text);
+
+    if failed > 0 {
+        Err(Error::Job(jobs::Error::Failed(format!(
+            "{failed} of {} backups failed",
+            results.len()
+        ))))
+    } else if cancelled > 0 {
+        Err(Error::Job(jobs::Error::Terminate(format!(
+            "{cancelled} of {} backups were cancelled",
+            results.len()
+        ))))
+    } else {
+        Ok(())
+    }
+}
+
+/// Hands `backups` to the running daemon, which runs them without the command waiting for them.
+fn detach(storage: &Storage, backups: &[Backup], json: bool) -> Result<(), Error> {
+    let socket = daemon::socket_path(storage);
+    let mut started = vec![];
+
+    for backup in backups {
+        let client_path = backup.client_location.path.clone();
+        match control::send(&socket, &Request::RunBackup(client_path.clone())) {
+            Ok(Response::Ack(_)) => started.push(client_path),
+            Ok(Response::Error(message)) => return Err(Error::State(message)),
+            Ok(Response::Status(_) | Response::Metrics(_)) => {
+                return Err(Error::State(String::from(
+                    "The daemon did not answer with an acknowledgement",
+                )))
+            }
+            Err(why) => return Err(Error::State(format!("Could not reach the daemon: {why:?}"))),
+        }
+    }
+
+    let text = started
+        .iter()
+        .map(|client_path| format!("⏳ {client_path}"))
+        .collect::<Vec<String>>()
+        .join("\n");
+    print(json, &started, &text);
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn execute(storage: &Storage, backups: Vec<Backup>) -> Result<Vec<RunResult>, Error> {
+    let config = storage
+        .config()
+        .ok_or_else(|| Error::State(String::from("No config, run 'bmu' to setup")))?;
+    let state = MutexState {
+        config: Mutex::new(Some(config.clone())),
+        connection: tokio::sync::Mutex::default(),
+        jobs: Arc::new(Mutex::default()),
+        failed_jobs: Arc::new(Mutex::default()),
+        pool: Mutex::new(jobs::Pool::new(None)),
+        app_cache_dir: Arc::new(Mutex::new(storage.cache_dir.clone())),
+        app_log_dir: Arc::new(Mutex::new(storage.log_dir.clone())),
+        history: Arc::new(Mutex::new(History::new(storage.data_dir.clone()))),
+        metrics: Arc::new(Mutex::default()),
+    };
+    let connection = Connection::new(config, storage.cache_dir.clone()).await?;
+    state.connection.lock().await.get_or_insert(connection);
+
+    let mut started = vec![];
+    for backup in backups {
+        let client_path = backup.client_location.path.clone();
+        let id = jobs::backup::entity_to_server(backup, Arc::new(&state)).await?;
+        started.push((id, client_path));
+    }
+
+    // a line on stdin cancels the backups, stdin that is not a terminal usually just ends
+    let cancel_requested = Arc::new(AtomicBool::new(false));
+    {
+        let cancel_requested = Arc::clone(&cancel_requested);
+        thread::spawn(move || {
+            let mut line = String::new();
+            if matches!(io::stdin().read_line(&mut line), Ok(read) if read > 0) {
+                cancel_requested.store(true, Ordering::SeqCst);
+            }
+        });
+    }
+    eprintln!("Press Enter to cancel");
+
+    let (retrying, cancellations, scheduler) = {
+        let pool = state.pool.lock()?;
+        (pool.retrying(), pool.cancellations(), pool.scheduler())
+    };
+    let status = |id: &String| {
+        jobs::check_status(
+            id,
+            &state.jobs,
+            &state.failed_jobs,
+            &retrying,
+            &cancellations,
+            &scheduler,
+        )
+    };
+    let is_pending = |id: &String| -> Result<bool, Error> {
+        Ok(state.pool.lock()?.has_task(id)
+            || matches!(
+                status(id)?,
+                jobs::Status::Queued | jobs::Status::Running | jobs::Status::Retrying(_)
+            ))
+    };
+    let mut results = vec![];
+    for (id, client_path) in &started {
+        while is_pending(id)? {
+            if cancel_requested.swap(false, Ordering::SeqCst) {
+                let pool = state.pool.lock()?;
+                // jobs that already finished have nothing left to cancel
+                for (id, _) in &started {
+                    _ = pool.cancel(id);
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+
+        results.push(RunResult {
+            status: status(id)?,
+            id: id.clone(),
+            client_path: client_path.clone(),
+        });
+    }
+
+    if let Some(connection) = state.connection.lock().await.take() {
+        connection.sftp_client.close().await?;
+        connection.ssh_session.close().await?;
+    }
+
+    Ok(results)
+}
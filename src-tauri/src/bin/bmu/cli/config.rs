@@ -0,0 +1,238 @@
+use super::{print, Args};
+use crate::{storage::Storage, Error};
+use back_me_up::models::app::Config;
+use back_me_up::models::notification::Notifications;
+use back_me_up::models::retry::RetryPolicy;
+use back_me_up::ssh::address;
+
+const KEYS: [&str; 21] = [
+    "client_name",
+    "username",
+    "server_address",
+    "server_port",
+    "allow_background_backup",
+    "identity_file",
+    "proxy_jump",
+    "ssh_options",
+    "known_hosts_file",
+    "notify_desktop",
+    "notify_completions",
+    "notify_webhook_url",
+    "notify_failing_minutes",
+    "notify_email_server",
+    "notify_email_from",
+    "notify_email_to",
+    "notify_email_username",
+    "notify_email_password",
+    "metrics_port",
+    "retry_max_attempts",
+    "retry_initial_delay_seconds",
+    "retry_max_delay_seconds",
+];
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let subcommand = args.first().map_or("", String::as_str);
+    let args = Args::parse(args.get(1..).unwrap_or_default(), &[], &["json"])?;
+
+    match subcommand {
+        "show" => show(&args),
+        "set" => set(&args),
+        _ => Err(Error::Usage(String::from(
+            "Expected 'bmu config [show|set]'",
+        ))),
+    }
+}
+
+fn load(storage: &Storage) -> Result<Config, Error> {
+    storage
+        .config()
+        .ok_or_else(|| Error::State(String::from("No config, run 'bmu' to setup")))
+}
+
+fn to_text(config: &Config) -> String {
+    let optional = |value: &Option<String>| value.clone().unwrap_or_default();
+    let notifications = config.notifications.clone().unwrap_or_default();
+    let email = notifications.email.clone().unwrap_or_default();
+    let retry = config.retry.clone().unwrap_or_default();
+
+    [
+        ("client_name", config.client_name.clone()),
+        ("username", config.username.clone()),
+        ("server_address", config.server_address.clone()),
+        ("server_port", config.server_port.to_string()),
+        (
+            "allow_background_backup",
+            config.allow_background_backup.to_string(),
+        ),
+        ("identity_file", optional(&config.identity_file)),
+        ("proxy_jump", optional(&config.proxy_jump)),
+        (
+            "ssh_options",
+            config.ssh_options.clone().unwrap_or_default().join(","),
+        ),
+        ("known_hosts_file", optional(&config.known_hosts_file)),
+        ("notify_desktop", notifications.desktop.to_string()),
+        ("notify_completions", notifications.completions.to_string()),
+        ("notify_webhook_url", optional(&notifications.webhook_url)),
+        (
+            "notify_failing_minutes",
+            notifications
+                .failing_threshold_minutes
+                .map(|minutes| minutes.to_string())
+                .unwrap_or_default(),
+        ),
+        ("notify_email_server", email.server_url),
+        ("notify_email_from", email.from),
+        ("notify_email_to", email.to.join(",")),
+        ("notify_email_username", optional(&email.username)),
+        (
+            "notify_email_password",
+            email
+                .password
+                .map(|_| String::from("********"))
+                .unwrap_or_default(),
+        ),
+        (
+            "metrics_port",
+            config
+                .metrics_port
+                .map(|port| port.to_string())
+                .unwrap_or_default(),
+        ),
+        ("retry_max_attempts", retry.max_attempts.to_string()),
+        (
+            "retry_initial_delay_seconds",
+            retry.initial_delay_seconds.to_string(),
+        ),
+        (
+            "retry_max_delay_seconds",
+            retry.max_delay_seconds.to_string(),
+        ),
+    ]
+    .iter()
+    .map(|(key, value)| format!("{key:24} {value}"))
+    .collect::<Vec<String>>()
+    .join("\n")
+}
+
+fn show(args: &Args) -> Result<(), Error> {
+    let config = load(&Storage::load()?)?;
+    print(args.flag("json"), &config, &to_text(&config));
+    Ok(())
+}
+
+/// Empty values unset optional keys.
+fn optional(value: &str) -> Option<String> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+/// Sets one of the `notify_` keys, returns `None` if the value is invalid.
+fn set_notification(notifications: &mut Notifications, key: &str, value: &str) -> Option<()> {
+    match key {
+        "notify_desktop" => notifications.desktop = value.parse().ok()?,
+        "notify_completions" => notifications.completions = value.parse().ok()?,
+        "notify_webhook_url" => notifications.webhook_url = optional(value),
+        "notify_failing_minutes" => {
+            notifications.failing_threshold_minutes = match optional(value) {
+                Some(minutes) => Some(minutes.parse().ok()?),
+                None => None,
+            };
+        }
+        _ => {
+            let email = notifications.email.get_or_insert_with(Default::default);
+            match key {
+                "notify_email_server" => email.server_url = value.to_string(),
+                "notify_email_from" => email.from = value.to_string(),
+                "notify_email_to" => {
+                    email.to = value
+                        .split(',')
+                        .map(|recipient| recipient.trim().to_string())
+                        .filter(|recipient| !recipient.is_empty())
+                        .collect();
+                }
+                "notify_email_username" => email.username = optional(value),
+                "notify_email_password" => email.password = optional(value),
+                _ => return None,
+            }
+        }
+    }
+
+    Some(())
+}
+
+fn set(args: &Args) -> Result<(), Error> {
+    let storage = Storage::load()?;
+    let mut config = load(&storage)?;
+    let (key, value) = match (args.positional(0), args.positional(1)) {
+        (Some(key), Some(value)) => (key, value.trim()),
+        _ => {
+            return Err(Error::Usage(format!(
+                "Expected 'bmu config set <key> <value>', where key is one of: {}",
+                KEYS.join(", ")
+            )))
+        }
+    };
+    let invalid = || Error::Usage(format!("Invalid value '{value}' for {key}"));
+
+    match key {
+        "client_name" if !value.is_empty() => config.client_name = value.to_string(),
+        "username" if !value.is_empty() => config.username = value.to_string(),
+        "server_address" if address::is_valid(value) => {
+            config.server_address = value.to_string();
+        }
+        "server_port" => config.server_port = value.parse().map_err(|_| invalid())?,
+        "allow_background_backup" => {
+            config.allow_background_backup = value.parse().map_err(|_| invalid())?;
+        }
+        "identity_file" => config.identity_file = optional(value),
+        "proxy_jump" => config.proxy_jump = optional(value),
+        "ssh_options" => {
+            config.ssh_options = optional(value).map(|options| {
+                options
+                    .split(',')
+                    .map(|option| option.trim().to_string())
+                    .filter(|option| !option.is_empty())
+                    .collect()
+            });
+        }
+        "known_hosts_file" => config.known_hosts_file = optional(value),
+        "metrics_port" => {
+            config.metrics_port = match optional(value) {
+                Some(port) => Some(port.parse().map_err(|_| invalid())?),
+                None => None,
+            };
+        }
+        _ if key.starts_with("retry_") && KEYS.contains(&key) => {
+            let retry = config.retry.get_or_insert_with(RetryPolicy::default);
+            let value = value.parse().map_err(|_| invalid())?;
+            match key {
+                "retry_max_attempts" if value > 0 => retry.max_attempts = value,
+                "retry_initial_delay_seconds" => retry.initial_delay_seconds = value,
+                "retry_max_delay_seconds" => retry.max_delay_seconds = value,
+                _ => return Err(invalid()),
+            }
+        }
+        _ if key.starts_with("notify_") && KEYS.contains(&key) => {
+            let notifications = config
+                .notifications
+                .get_or_insert_with(Notifications::default);
+            set_notification(notifications, key, value).ok_or_else(invalid)?;
+        }
+        _ if KEYS.contains(&key) => return Err(invalid()),
+        _ => {
+            return Err(Error::Usage(format!(
+                "Unknown key '{key}', expected one of: {}",
+                KEYS.join(", ")
+            )))
+        }
+    }
+
+    storage.write_conig(&config);
+
+    print(args.flag("json"), &config, &to_text(&config));
+    Ok(())
+}
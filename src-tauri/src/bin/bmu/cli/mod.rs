@@ -0,0 +1,122 @@
+use crate::Error;
+use std::collections::HashMap;
+use std::process;
+
+mod backup;
+mod config;
+
+/// Exit code for failures while running a command.
+const EXIT_FAILURE: i32 = 1;
+/// Exit code for invalid arguments, following the convention of most shells.
+const EXIT_USAGE: i32 = 2;
+
+/// Arguments of a subcommand split into positionals, flags and options taking a value.
+pub struct Args {
+    positionals: Vec<String>,
+    flags: Vec<String>,
+    options: HashMap<String, Vec<String>>,
+}
+
+impl Args {
+    /// Parses `args`, accepting `--option value`, `--option=value` and `--flag`. Anything not
+    /// listed in `options` or `flags` is an error.
+    pub fn parse(args: &[String], options: &[&str], flags: &[&str]) -> Result<Self, Error> {
+        let mut parsed = Self {
+            positionals: vec![],
+            flags: vec![],
+            options: HashMap::new(),
+        };
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let name = match arg.strip_prefix("--") {
+                Some(name) => name,
+                None => {
+                    parsed.positionals.push(arg.clone());
+                    continue;
+                }
+            };
+
+            let (name, inline_value) =
+                name.split_once('=').map_or((name, None), |(name, value)| {
+                    (name, Some(value.to_string()))
+                });
+
+            if flags.contains(&name) && inline_value.is_none() {
+                parsed.flags.push(name.to_string());
+            } else if options.contains(&name) {
+                let value = inline_value
+                    .or_else(|| args.next().cloned())
+                    .ok_or_else(|| Error::Usage(format!("Missing value for --{name}")))?;
+                parsed
+                    .options
+                    .entry(name.to_string())
+                    .or_default()
+                    .push(value);
+            } else {
+                return Err(Error::Usage(format!("Unknown argument '{arg}'")));
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    pub fn positional(&self, index: usize) -> Option<&str> {
+        self.positionals.get(index).map(String::as_str)
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+
+    /// The last value given for an option.
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .get(name)
+            .and_then(|values| values.last())
+            .map(String::as_str)
+    }
+
+    /// Every value given for an option that may be repeated.
+    pub fn options(&self, name: &str) -> Vec<String> {
+        self.options.get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn require(&self, name: &str) -> Result<&str, Error> {
+        self.option(name)
+            .ok_or_else(|| Error::Usage(format!("Missing required argument --{name}")))
+    }
+}
+
+/// Prints `value` as json, or `text` for humans.
+pub fn print<T: serde::Serialize>(json: bool, value: &T, text: &str) {
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(value).expect("Could not serialize output")
+        );
+    } else {
+        println!("{text}");
+    }
+}
+
+/// Runs a non-interactive subcommand and exits the process with a code matching the result.
+pub fn run(command: &str, args: &[String]) -> ! {
+    let result = match command {
+        "backup" => backup::run(args),
+        "config" => config::run(args),
+        _ => Err(Error::Usage(format!("Invalid argument '{command}'"))),
+    };
+
+    match result {
+        Ok(()) => process::exit(0),
+        Err(Error::Usage(message)) => {
+            eprintln!("⛔️ {message}\nRun 'bmu help' for usage");
+            process::exit(EXIT_USAGE);
+        }
+        Err(e) => {
+            eprintln!("⛔️ {e:?}");
+            process::exit(EXIT_FAILURE);
+        }
+    }
+}
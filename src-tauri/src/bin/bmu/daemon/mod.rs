@@ -1,20 +1,48 @@
 use crate::storage::Storage;
 use crate::{set_state_and_test_connection, storage};
+use back_me_up::control::{self, DaemonStatus, Request, Response};
 use back_me_up::graceful_exit;
-use back_me_up::models::app::MutexState;
-use back_me_up::{commands, jobs};
+use back_me_up::jobs::history::History;
+use back_me_up::models::app::{Config, MutexState};
+use back_me_up::models::backup::Backup;
+use back_me_up::models::history::Trigger;
+use back_me_up::{commands, jobs, metrics};
 use daemonize::Daemonize;
+use log::{error, info};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use std::fs::File;
-use std::io::Write;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use std::{fs, io, process};
-use tokio::time::{sleep, Duration};
+use std::time::Duration;
+use std::{fs, process};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::time::sleep;
+
+mod service;
+
+const PID_FILE_NAME: &str = "bmu_cli.pid";
+const STOP_TIMEOUT: Duration = Duration::from_secs(60);
+const RELOAD_DELAY: Duration = Duration::from_millis(500);
+
+/// Runtime state of the daemon besides the shared `MutexState`.
+struct Daemon {
+    paused: bool,
+    /// Backups that watchers were started for
+    watched: Vec<Backup>,
+}
 
 #[tokio::main]
 async fn main() {
     let storage = storage::Storage::load().expect("⛔️ Could not load storage: {why:?}");
-    fs::write(storage.daemon_dir.join("state"), "running")
-        .expect("Could not write daemon state file");
+    let socket = socket_path(&storage);
+    // daemonize writes the pid file as well, but not when running in the foreground
+    fs::write(
+        storage.daemon_dir.join(PID_FILE_NAME),
+        process::id().to_string(),
+    )
+    .expect("Could not write pid file");
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(16);
+    let (reload_sender, mut reload_receiver) = tokio::sync::mpsc::channel(16);
     let pool = jobs::Pool::new(None);
     let state = MutexState {
         config: Mutex::default(),
@@ -24,6 +52,8 @@ async fn main() {
         pool: Mutex::new(pool),
         app_cache_dir: Arc::new(Mutex::new(storage.cache_dir.clone())),
         app_log_dir: Arc::new(Mutex::new(storage.log_dir.clone())),
+        history: Arc::new(Mutex::new(History::new(storage.data_dir.clone()))),
+        metrics: Arc::new(Mutex::default()),
     };
     let config = storage
         .config()
@@ -36,33 +66,388 @@ async fn main() {
         }
     };
 
-    commands::app::start_background_backups(
-        &state,
-        &storage.backups().expect("could not load backups"),
-    )
-    .expect("could not start background backups");
+    let mut daemon = Daemon {
+        paused: false,
+        watched: storage.backups().expect("could not load backups"),
+    };
+
+    if daemon
+        .watched
+        .iter()
+        .any(|backup| backup.databases.is_some())
+        && !jobs::snapshot::sqlite3_is_installed()
+    {
+        println!("⚠️  sqlite3 is not installed, backups of databases will fail until it is\n");
+    }
+
+    commands::app::start_background_backups(&state, &daemon.watched)
+        .expect("could not start background backups");
+
+    if let Some(port) = config.metrics_port {
+        match metrics::serve(port, sender.clone()) {
+            Ok(address) => info!("Serving metrics on http://{address}/metrics"),
+            Err(e) => error!("Could not serve metrics on port {port}: {e:?}"),
+        }
+    }
+
+    if let Err(why) = control::listen(&socket, sender) {
+        panic!("⛔️ Could not listen on {}: {why:?}", socket.display());
+    }
+
+    // the watcher must live as long as the daemon
+    let _storage_watcher = watch_storage(&storage, reload_sender);
+    let mut terminate = signal(SignalKind::terminate()).expect("could not listen for SIGTERM");
+    let mut interrupt = signal(SignalKind::interrupt()).expect("could not listen for SIGINT");
+    // handed to the pool one per turn of the loop, so the daemon keeps answering meanwhile
+    let mut interrupted = interrupted_backups(&storage, &state);
 
     loop {
-        let action = fs::read_to_string(format!("{}/state", storage.daemon_dir.display()))
-            .unwrap_or_default();
+        tokio::select! {
+            () = std::future::ready(()), if !interrupted.is_empty() => {
+                let backup = interrupted.remove(0);
+                info!(
+                    "Running interrupted backup of {} again",
+                    backup.client_location.path
+                );
+                if let Err(e) = jobs::backup::entity_to_server(backup, Arc::new(&state)).await {
+                    error!("Could not run interrupted backup again: {e:?}");
+                }
+            }
+            // sent by systemd and launchd on stop, and by ctrl-c when running in the foreground
+            _ = terminate.recv() => {
+                info!("Received SIGTERM, stopping");
+                break;
+            }
+            _ = interrupt.recv() => {
+                info!("Received SIGINT, stopping");
+                break;
+            }
+            Some((request, respond)) = receiver.recv() => {
+                let is_stop = request == Request::Stop;
+                let response = handle_request(request, &storage, &state, &mut daemon).await;
 
-        if action.trim() == "terminate" {
-            break;
-        };
+                if let Err(e) = respond.send(response) {
+                    error!("Could not respond to control request: {e:?}");
+                }
 
-        sleep(Duration::from_secs(5)).await;
+                if is_stop {
+                    break;
+                }
+            }
+            Some(()) = reload_receiver.recv() => {
+                // editors and the GUI write in several steps, so wait for the burst to settle
+                sleep(RELOAD_DELAY).await;
+                while reload_receiver.try_recv().is_ok() {}
+
+                match reload(&storage, &state, &mut daemon).await {
+                    Ok(message) => info!("{message}"),
+                    Err(why) => error!("Could not reload storage: {why:?}"),
+                }
+            }
+            else => break,
+        }
     }
 
-    fs::write(storage.daemon_dir.join("state"), "stopped").unwrap_or_default();
+    _ = fs::remove_file(&socket);
+    _ = fs::remove_file(storage.daemon_dir.join(PID_FILE_NAME));
     graceful_exit(&state).await;
 }
 
+/// The backups to run again, since their last one-off run was interrupted, because the previous
+/// daemon or another process recording runs exited before it finished. A run of a watcher only
+/// uploaded a single change, it is not worth a full backup.
+fn interrupted_backups(storage: &Storage, state: &MutexState) -> Vec<Backup> {
+    let interrupted = match state.history.lock() {
+        Ok(history) => history.interrupted(commands::os::process_is_running_since),
+        Err(e) => {
+            error!("Could not lock history: {e:?}");
+            return vec![];
+        }
+    };
+    let mut client_paths: Vec<String> = match interrupted {
+        Ok(runs) => runs
+            .into_iter()
+            .filter(|run| run.trigger != Trigger::Watcher)
+            .map(|run| run.client_path)
+            .collect(),
+        Err(e) => {
+            error!("Could not read interrupted runs: {e:?}");
+            return vec![];
+        }
+    };
+    client_paths.sort();
+    client_paths.dedup();
+
+    storage
+        .backups()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|backup| client_paths.contains(&backup.client_location.path))
+        .collect()
+}
+
+/// Watches the config and backups files, as written by `bmu` and the GUI, and sends a message
+/// on `sender` whenever one of them changes.
+fn watch_storage(
+    storage: &Storage,
+    sender: tokio::sync::mpsc::Sender<()>,
+) -> Option<RecommendedWatcher> {
+    let files = [
+        storage.config_dir.join(storage::CONFIG_FILE_NAME),
+        storage.data_dir.join(storage::BACKUPS_FILE_NAME),
+    ];
+    let handler = move |result: notify::Result<Event>| match result {
+        Ok(event) => {
+            let is_storage_file = event.paths.iter().any(|path| files.contains(path));
+            if is_storage_file && !matches!(event.kind, EventKind::Access(_)) {
+                // a full channel already holds a pending reload
+                _ = sender.try_send(());
+            }
+        }
+        Err(e) => error!("Storage watcher failed: {e:?}"),
+    };
+
+    let mut watcher = match RecommendedWatcher::new(handler, notify::Config::default()) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            error!("Could not create storage watcher, changes need a reload: {e:?}");
+            return None;
+        }
+    };
+
+    // the directories are watched, since the files are replaced rather than modified by some
+    // writers
+    for directory in [&storage.config_dir, &storage.data_dir] {
+        if let Err(e) = watcher.watch(directory, RecursiveMode::NonRecursive) {
+            error!("Could not watch {}: {e:?}", directory.display());
+        }
+    }
+
+    Some(watcher)
+}
+
+async fn handle_request(
+    request: Request,
+    storage: &Storage,
+    state: &MutexState,
+    daemon: &mut Daemon,
+) -> Response {
+    let result = match request {
+        Request::Status => return Response::Status(DaemonStatus::current(state, daemon.paused)),
+        Request::Stop => Ok(String::from("Stopping")),
+        Request::Reload => reload(storage, state, daemon).await,
+        Request::RunBackup(client_path) => run_backup(storage, state, &client_path).await,
+        Request::Pause => pause(state, daemon),
+        Request::Resume => resume(storage, state, daemon),
+        Request::Metrics => return Response::Metrics(render_metrics(state, daemon).await),
+        Request::CancelJob(id) => cancel_job(state, &id),
+        Request::Watch(client_path) => watch(storage, state, daemon, &client_path),
+        Request::Unwatch(client_path) => unwatch(state, daemon, Some(&client_path)),
+        Request::UnwatchAll => unwatch(state, daemon, None),
+    };
+
+    match result {
+        Ok(message) => Response::Ack(message),
+        Err(why) => {
+            error!("Control request failed: {why:?}");
+            Response::Error(format!("{why:?}"))
+        }
+    }
+}
+
+fn cancel_job(state: &MutexState, id: &str) -> Result<String, crate::Error> {
+    state.pool.lock()?.cancel(id)?;
+
+    Ok(format!("Cancelled {id}"))
+}
+
+async fn render_metrics(state: &MutexState, daemon: &Daemon) -> String {
+    let count =
+        |jobs: &Arc<Mutex<jobs::Active>>| jobs.lock().map(|jobs| jobs.len()).unwrap_or_default();
+    let last_success = match state.history.lock() {
+        Ok(history) => daemon
+            .watched
+            .iter()
+            .map(|backup| {
+                let path = &backup.client_location.path;
+                let latest = history.latest_success(path).ok().flatten();
+                (path.clone(), latest.map(|run| run.ended_at))
+            })
+            .collect(),
+        Err(e) => {
+            error!("Could not lock history: {e:?}");
+            vec![]
+        }
+    };
+    let gauges = metrics::Gauges {
+        running_jobs: count(&state.jobs),
+        queued_jobs: state
+            .pool
+            .lock()
+            .map(|pool| pool.queued())
+            .unwrap_or_default(),
+        failed_jobs: count(&state.failed_jobs),
+        connected: state.connection.lock().await.is_some(),
+        paused: daemon.paused,
+        last_success,
+    };
+
+    match state.metrics.lock() {
+        Ok(metrics) => metrics::render(&metrics, &gauges),
+        Err(e) => {
+            error!("Could not lock metrics: {e:?}");
+            metrics::render(&metrics::Metrics::default(), &gauges)
+        }
+    }
+}
+
+/// Options that require a new connection when they change.
+fn connection_changed(previous: &Config, config: &Config) -> bool {
+    previous.username != config.username
+        || previous.server_address != config.server_address
+        || previous.server_port != config.server_port
+        || previous.identity_file != config.identity_file
+        || previous.proxy_jump != config.proxy_jump
+        || previous.ssh_options != config.ssh_options
+        || previous.known_hosts_file != config.known_hosts_file
+}
+
+/// Applies changes of the config and backups in storage. Only the watchers of changed backups
+/// are restarted, unless the config changed, since every watcher holds a copy of it. The
+/// connection is only replaced if an option it was made with changed.
+async fn reload(
+    storage: &Storage,
+    state: &MutexState,
+    daemon: &mut Daemon,
+) -> Result<String, crate::Error> {
+    let config = storage
+        .try_config()?
+        .ok_or_else(|| crate::Error::State(String::from("No config detected")))?;
+    let previous = state.config.lock()?.clone();
+    let config_changed = previous.as_ref() != Some(&config);
+
+    if previous.as_ref().map_or(false, |previous| {
+        previous.metrics_port != config.metrics_port
+    }) {
+        info!("The metrics port changed, restart the daemon to serve metrics on the new one");
+    }
+
+    if previous.map_or(true, |previous| connection_changed(&previous, &config)) {
+        set_state_and_test_connection(state, config.clone()).await?;
+    } else {
+        _ = state.config.lock()?.insert(config.clone());
+    }
+
+    let next = if daemon.paused || !config.allow_background_backup {
+        vec![]
+    } else {
+        storage.backups()?
+    };
+
+    if config_changed {
+        commands::app::apply_backup_changes(state, &daemon.watched, &[])?;
+        daemon.watched.clear();
+    }
+
+    commands::app::apply_backup_changes(state, &daemon.watched, &next)?;
+    daemon.watched = next;
+
+    Ok(format!(
+        "Reloaded, watching {} backups",
+        daemon.watched.len()
+    ))
+}
+
+async fn run_backup(
+    storage: &Storage,
+    state: &MutexState,
+    client_path: &str,
+) -> Result<String, crate::Error> {
+    let backup = storage
+        .backups()?
+        .into_iter()
+        .find(|backup| backup.client_location.path == client_path)
+        .ok_or_else(|| crate::Error::Path(format!("No backup of {client_path}")))?;
+
+    Ok(jobs::backup::entity_to_server(backup, Arc::new(state)).await?)
+}
+
+/// Starts the watcher of the backup of `client_path`, unless it is watched already.
+fn watch(
+    storage: &Storage,
+    state: &MutexState,
+    daemon: &mut Daemon,
+    client_path: &str,
+) -> Result<String, crate::Error> {
+    let backup = storage
+        .backups()?
+        .into_iter()
+        .find(|backup| backup.client_location.path == client_path)
+        .ok_or_else(|| crate::Error::Path(format!("No backup of {client_path}")))?;
+
+    if daemon
+        .watched
+        .iter()
+        .any(|watched| watched.same_config(&backup))
+    {
+        return Ok(format!("Already watching {client_path}"));
+    }
+
+    commands::app::start_background_backups(state, &[backup.clone()])?;
+    daemon.watched.push(backup);
+
+    Ok(format!("Watching {client_path}"))
+}
+
+/// Stops the watcher of the backup of `client_path`, or every watcher if none is given.
+fn unwatch(
+    state: &MutexState,
+    daemon: &mut Daemon,
+    client_path: Option<&str>,
+) -> Result<String, crate::Error> {
+    let (to_stop, to_keep): (Vec<Backup>, Vec<Backup>) =
+        daemon.watched.iter().cloned().partition(|backup| {
+            client_path.map_or(true, |path| backup.client_location.path == path)
+        });
+
+    commands::app::apply_backup_changes(state, &to_stop, &[])?;
+    daemon.watched = to_keep;
+
+    Ok(format!("Stopped {} watchers", to_stop.len()))
+}
+
+/// Terminates the watchers, but keeps the connection and any running one-off backups.
+fn pause(state: &MutexState, daemon: &mut Daemon) -> Result<String, crate::Error> {
+    commands::app::apply_backup_changes(state, &daemon.watched, &[])?;
+    daemon.watched.clear();
+    daemon.paused = true;
+
+    Ok(String::from("Paused background backups"))
+}
+
+fn resume(
+    storage: &Storage,
+    state: &MutexState,
+    daemon: &mut Daemon,
+) -> Result<String, crate::Error> {
+    let backups = storage.backups()?;
+    commands::app::apply_backup_changes(state, &daemon.watched, &backups)?;
+    daemon.watched = backups;
+    daemon.paused = false;
+
+    Ok(String::from("Resumed background backups"))
+}
+
 pub fn start() {
     let storage = storage::Storage::load().expect("⛔️ Could not load storage: {why:?}\nYou might need to initialize setup by running 'bmu' first");
     if is_running(&storage) {
         println!("Daemon is already running");
         process::exit(0);
     }
+    if service::is_installed() {
+        println!("The daemon is installed as a service, it is started by the init system");
+        process::exit(0);
+    }
     let daemon_dir = storage.daemon_dir.display().to_string();
     let stdout =
         File::create(format!("{daemon_dir}/bmu_cli.out")).expect("could not create daemon.out");
@@ -70,7 +455,7 @@ pub fn start() {
         File::create(format!("{daemon_dir}/bmu_cli.err")).expect("could not create daemon.err");
 
     let daemonize = Daemonize::new()
-        .pid_file(format!("{daemon_dir}/bmu_cli.pid"))
+        .pid_file(format!("{daemon_dir}/{PID_FILE_NAME}"))
         .chown_pid_file(true)
         .working_directory(&daemon_dir)
         .stdout(stdout)
@@ -103,62 +488,140 @@ pub fn start() {
     }
 }
 
+/// Runs the daemon in the foreground, for init systems that supervise it.
+pub fn run() {
+    let storage = storage::Storage::load().expect("⛔️ Could not load storage: {why:?}\nYou might need to initialize setup by running 'bmu' first");
+
+    if is_running(&storage) {
+        println!("Daemon is already running");
+        process::exit(0);
+    }
+
+    main();
+}
+
+pub fn install() {
+    let storage = storage::Storage::load().expect("could not load storage");
+
+    if is_running(&storage) && !service::is_installed() {
+        stop();
+    }
+
+    match service::install(&storage) {
+        Ok(path) => {
+            let mut messages = vec![
+                format!("✅ Installed the daemon as a service: {}", path.display()),
+                String::from("⚙️  It starts on login and is restarted if it fails"),
+                String::from("💻 Run 'bmu daemon uninstall' to remove it"),
+            ];
+            if cfg!(target_os = "linux") {
+                messages.push(String::from(
+                    "👉 Run 'loginctl enable-linger' to keep it running after logging out",
+                ));
+            }
+            crate::menu::ui::print_frame("Back me up 🚀", messages, false);
+        }
+        Err(why) => panic!("⛔️ Could not install the daemon: {why:?}"),
+    }
+}
+
+pub fn uninstall() {
+    match service::uninstall() {
+        Ok(path) => println!("✅ Removed the daemon service {}", path.display()),
+        Err(why) => panic!("⛔️ Could not uninstall the daemon: {why:?}"),
+    }
+}
+
 pub fn stop() {
     let storage = Storage::load().expect("could not load storage");
-    let daemon_dir = storage.daemon_dir.display();
 
     if !is_running(&storage) {
         println!("Daemon is already stopped");
         process::exit(0);
     }
 
-    fs::write(storage.daemon_dir.join("state"), "terminate")
-        .expect("Could not write daemon state file");
-
-    crate::menu::ui::print_frame(
-        "Back Me Up",
-        vec![
-            "Terminate message was sent to the daemon".to_string(),
-            format!("Check '{daemon_dir}' to verify that it closed as expected"),
-        ],
-        true,
-    );
+    match control::stop_and_wait(&socket_path(&storage), STOP_TIMEOUT) {
+        Ok(_) => println!("✅ Daemon stopped"),
+        Err(why) => panic!("⛔️ Could not stop the daemon: {why:?}"),
+    }
 }
 
 pub fn restart() {
-    let storage = storage::Storage::load().expect("could not load storage");
-    stop();
-    let mut try_count = 0;
-    while is_terminating(&storage) && try_count < 10 {
-        try_count += 1;
-        print!("\r🔄 Checking if daemon has stopped. Try count: {try_count}");
-        io::stdout().flush().expect("could not flush stdout");
-        std::thread::sleep(std::time::Duration::from_secs(5));
-        println!("\r\x1B[1A\x1B[2K");
-        io::stdout().flush().expect("failed to flush stdout");
+    if service::is_installed() {
+        match service::restart() {
+            Ok(()) => println!("✅ Daemon restarted"),
+            Err(why) => panic!("⛔️ Could not restart the daemon service: {why:?}"),
+        }
+        return;
     }
 
-    println!("{}", " ".repeat(50));
+    stop();
+    start();
+}
 
-    assert!(
-        is_stopped(&storage),
-        "⛔️ Daemon is not running, could not restart"
-    );
+/// Sends a request to the running daemon and prints its response.
+pub fn send(request: &Request) {
+    let storage = Storage::load().expect("could not load storage");
 
-    start();
+    match control::send(&socket_path(&storage), request) {
+        Ok(Response::Ack(message)) => println!("✅ {message}"),
+        Ok(Response::Status(status)) => println!("{status:?}"),
+        Ok(Response::Metrics(text)) => print!("{text}"),
+        Ok(Response::Error(message)) => {
+            eprintln!("⛔️ {message}");
+            process::exit(1);
+        }
+        Err(why) => {
+            eprintln!("⛔️ Could not reach the daemon: {why:?}");
+            process::exit(1);
+        }
+    }
+}
+
+#[must_use]
+pub fn socket_path(storage: &Storage) -> PathBuf {
+    control::socket_path(&storage.cache_dir)
 }
 
+fn pid(storage: &Storage) -> Option<u32> {
+    fs::read_to_string(storage.daemon_dir.join(PID_FILE_NAME))
+        .ok()
+        .and_then(|pid| pid.trim().parse().ok())
+}
+
+/// Returns true if a daemon answers on the control socket, or if its process is still alive
+/// while it is busy starting. Files left behind by a daemon that crashed are removed, so that
+/// a new one can start.
 pub fn is_running(storage: &Storage) -> bool {
-    let state = fs::read_to_string(storage.daemon_dir.join("state")).unwrap_or_default();
-    state.trim() == "running"
+    if control::is_running(&socket_path(storage)) {
+        return true;
+    }
+
+    if pid(storage).map_or(false, |pid| {
+        pid != process::id() && commands::os::process_is_running(pid, Some("bmu"))
+    }) {
+        return true;
+    }
+
+    clean_stale(storage);
+    false
 }
 
-pub fn is_terminating(storage: &Storage) -> bool {
-    let state = fs::read_to_string(storage.daemon_dir.join("state")).unwrap_or_default();
-    state.trim() == "terminate"
+fn clean_stale(storage: &Storage) {
+    for file in [storage.daemon_dir.join(PID_FILE_NAME), socket_path(storage)] {
+        if file.exists() {
+            info!("Removing stale {}", file.display());
+            if let Err(e) = fs::remove_file(&file) {
+                error!("Could not remove {}: {e:?}", file.display());
+            }
+        }
+    }
 }
 
-pub fn is_stopped(storage: &Storage) -> bool {
-    let state = fs::read_to_string(storage.daemon_dir.join("state")).unwrap_or_default();
-    state.trim() == "stopped"
+/// Status reported by the running daemon.
+pub fn status(storage: &Storage) -> Option<DaemonStatus> {
+    match control::send(&socket_path(storage), &Request::Status) {
+        Ok(Response::Status(status)) => Some(status),
+        _ => None,
+    }
 }
@@ -0,0 +1,181 @@
+use crate::storage::Storage;
+use std::path::PathBuf;
+use std::process::Command;
+use std::{env, fs, io};
+
+const SYSTEMD_UNIT_NAME: &str = "bmu.service";
+const LAUNCHD_LABEL: &str = "com.backmeup.bmu";
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Command(String),
+    UnsupportedOs(String),
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+fn home_dir() -> Result<PathBuf, Error> {
+    env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| Error::Command(String::from("HOME not set")))
+}
+
+fn service_file() -> Result<PathBuf, Error> {
+    match env::consts::OS {
+        "linux" => Ok(home_dir()?
+            .join(".config/systemd/user")
+            .join(SYSTEMD_UNIT_NAME)),
+        "macos" => Ok(home_dir()?
+            .join("Library/LaunchAgents")
+            .join(format!("{LAUNCHD_LABEL}.plist"))),
+        os => Err(Error::UnsupportedOs(os.to_string())),
+    }
+}
+
+/// Returns true if the daemon is installed as a service of the init system.
+pub fn is_installed() -> bool {
+    service_file().map_or(false, |path| path.exists())
+}
+
+fn systemd_unit(executable: &str) -> String {
+    format!(
+        "[Unit]
+Description=Back me up daemon
+After=network-online.target
+Wants=network-online.target
+
+[Service]
+Type=simple
+ExecStart=\"{executable}\" daemon run
+Environment=USER=%u
+Restart=on-failure
+RestartSec=10
+
+[Install]
+WantedBy=default.target
+"
+    )
+}
+
+fn launchd_plist(executable: &str, storage: &Storage) -> String {
+    let daemon_dir = storage.daemon_dir.display();
+    let user = env::var("USER").unwrap_or_default();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{LAUNCHD_LABEL}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{executable}</string>
+        <string>daemon</string>
+        <string>run</string>
+    </array>
+    <key>EnvironmentVariables</key>
+    <dict>
+        <key>USER</key>
+        <string>{user}</string>
+    </dict>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+    <key>StandardOutPath</key>
+    <string>{daemon_dir}/bmu_cli.out</string>
+    <key>StandardErrorPath</key>
+    <string>{daemon_dir}/bmu_cli.err</string>
+</dict>
+</plist>
+"#
+    )
+}
+
+fn run(program: &str, args: &[&str]) -> Result<(), Error> {
+    let output = Command::new(program).args(args).output()?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(Error::Command(format!(
+            "'{program} {}' failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Writes a systemd user unit, or a launchd agent on macOS, that runs `bmu daemon run` and
+/// restarts it on failure, then enables and starts it.
+pub fn install(storage: &Storage) -> Result<PathBuf, Error> {
+    let executable = env::current_exe()?.display().to_string();
+    let path = service_file()?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if env::consts::OS == "macos" {
+        fs::write(&path, launchd_plist(&executable, storage))?;
+        run("launchctl", &["load", "-w", &path.display().to_string()])?;
+    } else {
+        fs::write(&path, systemd_unit(&executable))?;
+        run("systemctl", &["--user", "daemon-reload"])?;
+        run(
+            "systemctl",
+            &["--user", "enable", "--now", SYSTEMD_UNIT_NAME],
+        )?;
+    }
+
+    Ok(path)
+}
+
+/// Stops and removes the service written by `install`.
+pub fn uninstall() -> Result<PathBuf, Error> {
+    let path = service_file()?;
+
+    if !path.exists() {
+        return Err(Error::Command(format!(
+            "The daemon is not installed, {} does not exist",
+            path.display()
+        )));
+    }
+
+    if env::consts::OS == "macos" {
+        run("launchctl", &["unload", "-w", &path.display().to_string()])?;
+        fs::remove_file(&path)?;
+    } else {
+        run(
+            "systemctl",
+            &["--user", "disable", "--now", SYSTEMD_UNIT_NAME],
+        )?;
+        fs::remove_file(&path)?;
+        run("systemctl", &["--user", "daemon-reload"])?;
+    }
+
+    Ok(path)
+}
+
+/// Restarts the service through the init system, which also starts it if it was stopped.
+pub fn restart() -> Result<(), Error> {
+    let path = service_file()?;
+
+    if env::consts::OS == "macos" {
+        let path = path.display().to_string();
+        // a stopped agent can't be unloaded
+        _ = run("launchctl", &["unload", &path]);
+        run("launchctl", &["load", "-w", &path])
+    } else {
+        run("systemctl", &["--user", "restart", SYSTEMD_UNIT_NAME])
+    }
+}
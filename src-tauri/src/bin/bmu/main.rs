@@ -1,10 +1,14 @@
 use self::menu::ui;
 use back_me_up::commands::os::{create_directory, directory_exists};
+use back_me_up::control::Request;
+use back_me_up::jobs::history::History;
 use back_me_up::jobs::Pool;
 use back_me_up::models::app::Config as AppConfig;
 use back_me_up::models::app::MutexState;
+use back_me_up::models::history::RunStatus;
 use back_me_up::ssh::connect::Connection;
 use back_me_up::{commands, jobs, ssh};
+use chrono::{Local, TimeZone};
 use inquire::InquireError;
 use log::LevelFilter;
 use log4rs::append::file::FileAppender;
@@ -12,11 +16,13 @@ use log4rs::config::{Appender, Config, Root};
 use log4rs::encode::pattern::PatternEncoder;
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::{MutexGuard, PoisonError};
+use std::sync::{mpsc, MutexGuard, PoisonError};
 use std::{env, io};
 
+mod cli;
 mod daemon;
 mod menu;
+mod status;
 mod storage;
 
 static USER_PANIC: &str = "USER not set";
@@ -33,6 +39,7 @@ pub enum Error {
     Job(jobs::Error),
     Storage(storage::Error),
     Command(commands::Error),
+    Usage(String),
 }
 
 impl From<openssh_sftp_client::Error> for Error {
@@ -164,6 +171,9 @@ fn main() {
         },
         |arg| match arg.as_str() {
             "daemon" => handle_daemon(&args),
+            "history" => handle_history(&args),
+            "backup" | "config" => cli::run(arg, &args[2..]),
+            "status" => status::show(args.iter().any(|arg| arg == "--json")),
             "clean" => {
                 let storage = storage::Storage::load().expect("Could not load storage");
                 let directories = jobs::maintenance::Directories {
@@ -175,7 +185,18 @@ fn main() {
                     daemon: true,
                     logs: true,
                 };
-                match jobs::maintenance::clean(&directories, Some(options)) {
+                let mut pool = Pool::new(None);
+                let (sender, receiver) = mpsc::channel();
+                let result = jobs::maintenance::spawn(&mut pool, directories, options, sender)
+                    .and_then(|_| {
+                        receiver.recv().unwrap_or_else(|_| {
+                            Err(jobs::Error::Failed(String::from(
+                                "The cleaning job ended without a result",
+                            )))
+                        })
+                    });
+
+                match result {
                     Err(why) => {
                         panic!("⛔️ Could not perform cleaning job {why:?}");
                     }
@@ -244,8 +265,46 @@ fn set_envs() {
 fn help() {
     let messages = vec![
         format!("To start the interactive menu: bmu\n"),
-        format!("Other usage: bmu [daemon|clean|help]"),
-        format!("{:10} {:22}", "  daemon", "[start|restart|stop]",),
+        format!("Other usage: bmu [backup|config|daemon|status|history|clean|help]"),
+        format!("{:10} {:22}", "  backup", "[list|add|run|remove]"),
+        format!(
+            "{:10} {:22} -- {}",
+            "   list", "[--json]", "Lists backups with their id"
+        ),
+        format!(
+            "{:10} {:22} -- {}",
+            "   add", "--source <dir>", "Adds a backup of a local directory"
+        ),
+        format!("{:10} {:22}", "", "--dest <folder>"),
+        format!("{:10} {:22}", "", "[--mirror <dir>]..."),
+        format!("{:10} {:22}", "", "[--use-client-directory]"),
+        format!("{:10} {:22}", "", "[--database <file>]..."),
+        format!("{:10} {:22}", "", "[--pre-hook <command>]"),
+        format!("{:10} {:22}", "", "[--post-hook <command>]"),
+        format!("{:10} {:22}", "", "[--hook-timeout <seconds>]"),
+        format!("{:10} {:22}", "", "[--continue-on-hook-failure]"),
+        format!(
+            "{:10} {:22} -- {}",
+            "   run", "<id|--all>", "Runs backups and waits for them to finish, Enter cancels them"
+        ),
+        format!(
+            "{:10} {:22} -- {}",
+            "", "[--detach]", "Leaves the backups to the running daemon instead of waiting"
+        ),
+        format!("{:10} {:22} -- {}", "   remove", "<id>", "Removes a backup"),
+        format!("{:10} {:22}", "  config", "[show|set]"),
+        format!(
+            "{:10} {:22} -- {}",
+            "   show", "[--json]", "Shows the config"
+        ),
+        format!(
+            "{:10} {:22} -- {}",
+            "   set", "<key> <value>", "Sets a config value, empty unsets it"
+        ),
+        format!(
+            "{:10} {:22}",
+            "  daemon", "[start|restart|stop|reload|pause|resume|metrics|run|install|uninstall]",
+        ),
         format!(
             "{:10} {:22} -- {}",
             "   start", "", "Starts background backups in a daemon"
@@ -258,6 +317,42 @@ fn help() {
             "{:10} {:22} -- {}",
             "   restart", "", "Restarts the deamon if its running"
         ),
+        format!(
+            "{:10} {:22} -- {}",
+            "   reload", "", "Reads config and backups again"
+        ),
+        format!(
+            "{:10} {:22} -- {}",
+            "   pause", "", "Stops watching for changes"
+        ),
+        format!(
+            "{:10} {:22} -- {}",
+            "   resume", "", "Starts watching for changes again"
+        ),
+        format!(
+            "{:10} {:22} -- {}",
+            "   metrics", "", "Prints the Prometheus metrics of the daemon"
+        ),
+        format!(
+            "{:10} {:22} -- {}",
+            "   run", "", "Runs the daemon in the foreground"
+        ),
+        format!(
+            "{:10} {:22} -- {}",
+            "   install", "", "Installs the daemon as a systemd or launchd service"
+        ),
+        format!(
+            "{:10} {:22} -- {}",
+            "   uninstall", "", "Removes the daemon service"
+        ),
+        format!(
+            "{:10} {:22} -- {}",
+            "  status", "[--json]", "Show the daemon, connection and backup status"
+        ),
+        format!(
+            "{:10} {:22} -- {}",
+            "  history", "[limit]", "Show the latest backup runs, 20 by default"
+        ),
         format!(
             "{:10} {:22} -- {}",
             "  clean", "", "Clean the cache and logs"
@@ -272,12 +367,81 @@ fn handle_daemon(args: &[String]) {
     let arg = args.get(2).expect("⛔️ Missing daemon argument");
     match arg.as_str() {
         "start" => daemon::start(),
+        "install" => daemon::install(),
+        "uninstall" => daemon::uninstall(),
         "restart" => daemon::restart(),
         "stop" => daemon::stop(),
+        "reload" => daemon::send(&Request::Reload),
+        "pause" => daemon::send(&Request::Pause),
+        "resume" => daemon::send(&Request::Resume),
+        "metrics" => daemon::send(&Request::Metrics),
+        "run" => daemon::run(),
         _ => panic!("⛔️ Invalid argument '{arg}'"),
     }
 }
 
+fn handle_history(args: &[String]) {
+    let limit = args.get(2).map_or(20, |limit| {
+        limit
+            .parse()
+            .unwrap_or_else(|_| panic!("⛔️ Invalid limit '{limit}'"))
+    });
+    let storage = storage::Storage::load().expect("Could not load storage");
+    let runs = History::new(storage.data_dir)
+        .query(None, Some(limit))
+        .expect("⛔️ Could not read history");
+
+    let messages = if runs.is_empty() {
+        vec![String::from("No backups have run yet")]
+    } else {
+        runs.iter()
+            .map(|run| {
+                let started_at = i64::try_from(run.started_at)
+                    .ok()
+                    .and_then(|timestamp| Local.timestamp_opt(timestamp, 0).single())
+                    .map_or_else(String::new, |date| {
+                        date.format("%Y-%m-%d %H:%M:%S").to_string()
+                    });
+                let status = match run.status {
+                    RunStatus::Success => "✅",
+                    RunStatus::Partial => "🟡",
+                    RunStatus::Failure => "⛔️",
+                    RunStatus::Running => "⏳",
+                    RunStatus::Interrupted => "⚠️ ",
+                    RunStatus::Cancelled => "✋",
+                };
+                let mut message = format!(
+                    "{status} {started_at} {:?} {} ({} files, {} bytes, {}s)",
+                    run.trigger,
+                    run.client_path,
+                    run.transfer.files,
+                    run.transfer.bytes,
+                    run.ended_at.saturating_sub(run.started_at)
+                );
+                if let Some(error) = &run.error {
+                    message.push_str(&format!("\n     {error}"));
+                }
+                for hook_run in run.hooks.iter().flatten() {
+                    let exit = if hook_run.timed_out {
+                        String::from("timed out")
+                    } else {
+                        hook_run
+                            .exit_code
+                            .map_or_else(|| String::from("not run"), |code| code.to_string())
+                    };
+                    message.push_str(&format!(
+                        "\n     {:?} hook '{}': {exit}",
+                        hook_run.stage, hook_run.command
+                    ));
+                }
+                message
+            })
+            .collect()
+    };
+
+    menu::ui::print_frame("History", messages, false);
+}
+
 pub async fn set_state_and_test_connection(
     state: &MutexState,
     config: AppConfig,
@@ -1,8 +1,8 @@
 use super::Error;
 use crate::menu::Action;
-use crate::{daemon, storage};
+use crate::storage;
 use back_me_up::models::app::MutexState;
-use back_me_up::models::backup::{Backup, Location, Options};
+use back_me_up::models::backup::{Backup, Destination, Location, Options};
 use back_me_up::models::storage::Folder;
 use back_me_up::ssh::commands::list_home_folders;
 use back_me_up::{commands, jobs};
@@ -70,22 +70,34 @@ pub async fn handle(state: &MutexState) -> Result<Action, Error> {
 
 async fn run(state: &MutexState, backup: Backup) -> Result<(), Error> {
     let id = jobs::backup::entity_to_server(backup.clone(), Arc::new(state)).await?;
+    let (retrying, cancellations, scheduler) = {
+        let pool = state.pool.lock()?;
+        (pool.retrying(), pool.cancellations(), pool.scheduler())
+    };
+    let status = |id: &String| {
+        jobs::check_status(
+            id,
+            &state.jobs,
+            &state.failed_jobs,
+            &retrying,
+            &cancellations,
+            &scheduler,
+        )
+    };
     print!("\r⏳ Backing up: {id}");
     io::stdout().flush().expect("failed to flush stdout");
-    thread::sleep(std::time::Duration::from_secs(1));
-    while matches!(
-        jobs::check_status(&id, &state.jobs, &state.failed_jobs)?,
-        jobs::Status::Running
-    ) {
+    while state.pool.lock()?.has_task(&id)
+        || matches!(
+            status(&id)?,
+            jobs::Status::Queued | jobs::Status::Running | jobs::Status::Retrying(_)
+        )
+    {
         thread::sleep(std::time::Duration::from_millis(500));
     }
     println!("\x1B[1A\x1B[2K");
     io::stdout().flush().expect("failed to flush stdout");
 
-    if matches!(
-        jobs::check_status(&id, &state.jobs, &state.failed_jobs)?,
-        jobs::Status::Failed
-    ) {
+    if matches!(status(&id)?, jobs::Status::Failed) {
         print!("\r{}", " ".repeat(100)); // removes the loading indicator
         return Err(Error::Job(jobs::Error::Failed(format!(
             "Something went wrong when backing up {}",
@@ -107,18 +119,9 @@ fn select() -> Result<HandleOrGoBack, Error> {
 
     options.push(HandleOrGoBack::Back);
 
-    let storage = storage::Storage::load()?;
-
-    let option: HandleOrGoBack = if daemon::is_running(&storage) {
-        Select::new("Select a backup", options)
-            .with_vim_mode(true)
-            .with_help_message("Daemon is running, remember to run 'bmu daemon restart' if you make changes in your backups")
-            .prompt()?
-    } else {
-        Select::new("Select a backup", options)
-            .with_vim_mode(true)
-            .prompt()?
-    };
+    let option: HandleOrGoBack = Select::new("Select a backup", options)
+        .with_vim_mode(true)
+        .prompt()?;
 
     Ok(option)
 }
@@ -129,6 +132,9 @@ pub async fn add(state: &MutexState) -> Result<Action, Error> {
         server_location: get_server_location(state).await?,
         latest_run: None,
         options: Some(get_options()?),
+        mirrors: get_mirrors()?,
+        hooks: None,
+        databases: None,
     };
 
     println!(
@@ -255,3 +261,22 @@ fn get_options() -> Result<Options, Error> {
         use_client_directory,
     })
 }
+
+fn get_mirrors() -> Result<Option<Vec<Destination>>, Error> {
+    let mut mirrors = Vec::new();
+
+    while Confirm::new("Mirror the backup to a local disk as well?")
+        .with_default(false)
+        .prompt()?
+    {
+        let location = get_client_location()?;
+        println!("Mirror location: {}", location.path);
+        mirrors.push(Destination::Disk(location));
+    }
+
+    Ok(if mirrors.is_empty() {
+        None
+    } else {
+        Some(mirrors)
+    })
+}
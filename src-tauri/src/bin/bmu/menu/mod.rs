@@ -1,5 +1,6 @@
 use super::storage;
 use crate::{daemon, set_state_and_test_connection, Error};
+use back_me_up::jobs::history::History;
 use back_me_up::models::app::MutexState;
 use back_me_up::{commands, graceful_exit, jobs};
 use inquire::InquireError;
@@ -44,6 +45,8 @@ pub async fn show() {
         pool: Mutex::new(pool),
         app_cache_dir: Arc::new(Mutex::new(storage.cache_dir.clone())),
         app_log_dir: Arc::new(Mutex::new(storage.log_dir.clone())),
+        history: Arc::new(Mutex::new(History::new(storage.data_dir.clone()))),
+        metrics: Arc::new(Mutex::default()),
     };
 
     let config = if let Some(c) = storage.config() {
@@ -1,13 +1,14 @@
 use super::{Action, Error};
-use crate::{daemon, storage};
-use back_me_up::models::app::MutexState;
+use crate::{daemon, set_state_and_test_connection, storage};
+use back_me_up::models::app::{Config, MutexState};
 use back_me_up::{commands, jobs};
-use inquire::{Confirm, Select};
+use inquire::{Confirm, Select, Text};
 use std::fmt::Display;
 
 enum SettingsMenuItem {
     EnableBackgroundBackups(String),
     DisableBackgroundBackups(String),
+    ConnectionOptions(String),
     Disconnect(String),
     Back(String),
 }
@@ -17,6 +18,7 @@ impl Display for SettingsMenuItem {
         let text = match self {
             Self::EnableBackgroundBackups(value)
             | Self::DisableBackgroundBackups(value)
+            | Self::ConnectionOptions(value)
             | Self::Disconnect(value)
             | Self::Back(value) => value,
         };
@@ -45,14 +47,77 @@ fn set_background_backups(enabled: bool, state: &MutexState) -> Result<Action, E
         commands::app::start_background_backups(state, &storage.backups()?)?;
     } else {
         let mut jobs = state.jobs.lock()?;
-        let mut pool = state.pool.lock()?;
+        let pool = state.pool.lock()?;
 
-        jobs::backup::terminate_all(&mut jobs, &mut pool);
+        jobs::backup::terminate_all(&mut jobs, &pool);
     }
 
     Ok(Action::Show)
 }
 
+fn optional(input: String) -> Option<String> {
+    let input = input.trim().to_string();
+    if input.is_empty() {
+        None
+    } else {
+        Some(input)
+    }
+}
+
+async fn edit_connection_options(state: &MutexState) -> Result<Action, Error> {
+    let storage = storage::Storage::load()?;
+    let config_mutex = state.config.lock()?.clone();
+    let previous_config = match config_mutex {
+        Some(config) => config,
+        None => return Err(Error::State(String::from("No config found"))),
+    };
+
+    let identity_file = Text::new("Identity file:")
+        .with_default(previous_config.identity_file.as_deref().unwrap_or_default())
+        .with_help_message("Path to a private key, leave empty to use your default keys")
+        .prompt()?;
+    let proxy_jump = Text::new("Jump hosts:")
+        .with_default(previous_config.proxy_jump.as_deref().unwrap_or_default())
+        .with_help_message("Comma separated, e.g. user@bastion:22,other-jump-host")
+        .prompt()?;
+    let ssh_options = Text::new("Extra ssh options:")
+        .with_default(
+            &previous_config
+                .ssh_options
+                .clone()
+                .unwrap_or_default()
+                .join(", "),
+        )
+        .with_help_message("Comma separated, e.g. ServerAliveInterval=30, Compression=yes")
+        .prompt()?;
+
+    let ssh_options: Vec<String> = ssh_options
+        .split(',')
+        .filter_map(|option| optional(option.to_string()))
+        .collect();
+    let config = Config {
+        identity_file: optional(identity_file),
+        proxy_jump: optional(proxy_jump),
+        ssh_options: if ssh_options.is_empty() {
+            None
+        } else {
+            Some(ssh_options)
+        },
+        ..previous_config.clone()
+    };
+
+    if let Err(why) = set_state_and_test_connection(state, config.clone()).await {
+        println!("⛔️ Could not connect with the new options, keeping the previous ones: {why:?}\n");
+        set_state_and_test_connection(state, previous_config).await?;
+        return Ok(Action::Show);
+    }
+
+    storage.write_conig(&config);
+    println!("✅ Connection options saved\n");
+
+    Ok(Action::Show)
+}
+
 async fn disconnect(state: &MutexState) -> Result<Action, Error> {
     let confirmed = Confirm::new("Are you sure you want to erase your credentials and disconnect?\nYour backups will remain on the server").with_default(true).prompt()?;
 
@@ -66,8 +131,8 @@ async fn disconnect(state: &MutexState) -> Result<Action, Error> {
     }
 
     let mut jobs = state.jobs.lock()?;
-    let mut pool = state.pool.lock()?;
-    jobs::backup::terminate_all(&mut jobs, &mut pool);
+    let pool = state.pool.lock()?;
+    jobs::backup::terminate_all(&mut jobs, &pool);
 
     state.config.lock()?.take();
     drop(pool);
@@ -92,26 +157,19 @@ pub async fn show(state: &MutexState) -> Result<Action, Error> {
         } else {
             SettingsMenuItem::EnableBackgroundBackups(String::from("Enable background backups"))
         },
+        SettingsMenuItem::ConnectionOptions(String::from("Connection options")),
         SettingsMenuItem::Disconnect(String::from("Disconnnect")),
         SettingsMenuItem::Back(String::from("Back")),
     ];
 
-    let storage = storage::Storage::load()?;
-
-    let option: SettingsMenuItem = if daemon::is_running(&storage) && !allow_background_backups {
-        Select::new("Select option", options)
-            .with_vim_mode(true)
-            .with_help_message("Daemon is running, remember to run 'bmu daemon stop' to fully disable background backups")
-            .prompt()?
-    } else {
-        Select::new("Select option", options)
-            .with_vim_mode(true)
-            .prompt()?
-    };
+    let option: SettingsMenuItem = Select::new("Select option", options)
+        .with_vim_mode(true)
+        .prompt()?;
 
     match option {
         SettingsMenuItem::EnableBackgroundBackups(_) => set_background_backups(true, state),
         SettingsMenuItem::DisableBackgroundBackups(_) => set_background_backups(false, state),
+        SettingsMenuItem::ConnectionOptions(_) => edit_connection_options(state).await,
         SettingsMenuItem::Disconnect(_) => disconnect(state).await,
         SettingsMenuItem::Back(_) => Ok(Action::Exit),
     }
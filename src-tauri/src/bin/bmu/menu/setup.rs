@@ -2,10 +2,11 @@ use super::Error;
 use crate::{set_state_and_test_connection, storage};
 use back_me_up::commands::os::get_hostname;
 use back_me_up::models::app::{Config, MutexState};
+use back_me_up::ssh::address::{self, Address};
+use back_me_up::ssh::{host_key, key};
 use inquire::validator::Validation;
-use inquire::{CustomType, Text};
-use std::net::IpAddr;
-use std::str::FromStr;
+use inquire::{Confirm, CustomType, Text};
+use std::path::Path;
 
 fn setup_config() -> Result<Config, Error> {
     let client_name =
@@ -24,15 +25,19 @@ fn setup_config() -> Result<Config, Error> {
         .with_validator(validator)
         .prompt()?;
 
-    let server_addr_validator = |input: &str| match IpAddr::from_str(input) {
-        Ok(_) => Ok(Validation::Valid),
-        Err(_) => Ok(Validation::Invalid(
-            "Please enter a valid IP address.".into(),
-        )),
+    let server_addr_validator = |input: &str| {
+        if address::is_valid(input) {
+            Ok(Validation::Valid)
+        } else {
+            Ok(Validation::Invalid(
+                "Please enter a valid IP address, hostname or ~/.ssh/config host alias.".into(),
+            ))
+        }
     };
 
-    let server_address = Text::new("Backup server ip-address:")
+    let server_address = Text::new("Backup server address:")
         .with_validator(server_addr_validator)
+        .with_help_message("IP address, hostname or a Host alias from ~/.ssh/config")
         .prompt()?;
 
     let server_port = CustomType::<u16>::new("Backup server port:")
@@ -42,19 +47,116 @@ fn setup_config() -> Result<Config, Error> {
     Ok(Config {
         username,
         client_name,
-        server_address,
+        server_address: address::normalize_host(&server_address),
         server_port,
         allow_background_backup: false,
+        identity_file: None,
+        proxy_jump: None,
+        ssh_options: None,
+        known_hosts_file: None,
+        notifications: None,
+        metrics_port: None,
+        retry: None,
+        max_transfers: None,
     })
 }
 
+/// Shows the fingerprint of an untrusted server and stores its host key if the user confirms it.
+fn confirm_host_key(config: &Config, known_hosts: &Path) -> Result<bool, Error> {
+    let address = Address::resolve(config);
+    let scan = host_key::check(&address, known_hosts)?;
+
+    let key = match scan.keys.first() {
+        Some(key) => key,
+        None => return Ok(true),
+    };
+
+    println!("\n🔑 The authenticity of {address} can't be established.");
+    for key in &scan.keys {
+        println!("   {} key fingerprint is {}", key.key_type, key.fingerprint);
+    }
+
+    let confirmed = Confirm::new("Do you trust this server?")
+        .with_default(false)
+        .with_help_message("Compare the fingerprint with the one shown by 'ssh-keygen -lf /etc/ssh/ssh_host_ed25519_key.pub' on the server")
+        .prompt()?;
+
+    if confirmed {
+        host_key::trust(&address, known_hosts, &key.fingerprint)?;
+    }
+
+    Ok(confirmed)
+}
+
+/// Offers to generate a key for the app and install it on the server, for users that don't have
+/// passwordless ssh set up yet. Returns the config using the new key.
+fn install_ssh_key(config: Config) -> Result<Option<Config>, Error> {
+    let confirmed = Confirm::new(
+        "Could not log in without a password. Generate an ssh key for backups and install it on the server?",
+    )
+    .with_default(true)
+    .with_help_message("You will be asked for your server password once")
+    .prompt()?;
+
+    if !confirmed {
+        return Ok(None);
+    }
+
+    let storage = storage::Storage::load()?;
+    let private_key = key::generate(
+        &storage.config_dir,
+        &format!("back-me-up@{}", config.client_name),
+    )?;
+    let config = Config {
+        identity_file: Some(private_key.display().to_string()),
+        ..config
+    };
+
+    key::install(
+        &Address::resolve(&config),
+        &private_key,
+        &key::PasswordPrompt::Terminal,
+    )?;
+    println!("🔑 Installed {} on the server\n", private_key.display());
+
+    Ok(Some(config))
+}
+
 pub async fn begin(state: &MutexState) -> Result<Config, Error> {
+    let known_hosts = host_key::app_known_hosts(&state.app_cache_dir.lock()?);
+
     loop {
-        if let Ok(config) = set_state_and_test_connection(state, setup_config()?).await {
+        let mut config = setup_config()?;
+        config.known_hosts_file = Some(known_hosts.display().to_string());
+
+        match confirm_host_key(&config, &known_hosts) {
+            Ok(true) => (),
+            Ok(false) => {
+                println!("⛔️ The server was not trusted, try again with another server.\n");
+                continue;
+            }
+            Err(why) => {
+                println!("⛔️ Could not verify the server: {why:?}\n");
+                continue;
+            }
+        }
+
+        if let Ok(config) = set_state_and_test_connection(state, config.clone()).await {
             storage::Storage::load()?.write_conig(&config);
             return Ok(config);
         }
 
+        match install_ssh_key(config) {
+            Ok(Some(config)) => {
+                if let Ok(config) = set_state_and_test_connection(state, config).await {
+                    storage::Storage::load()?.write_conig(&config);
+                    return Ok(config);
+                }
+            }
+            Ok(None) => (),
+            Err(why) => println!("⛔️ Could not set up an ssh key: {why:?}\n"),
+        }
+
         println!("⛔️ Could not connect with the provided credentials, try again and make sure your credentials are correct.\n");
     }
 }
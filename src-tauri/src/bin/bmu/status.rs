@@ -0,0 +1,208 @@
+use crate::storage::Storage;
+use crate::{daemon, menu::ui, TITLE};
+use back_me_up::jobs::{self, history::History};
+use back_me_up::models::backup::Backup;
+use back_me_up::ssh;
+use chrono::{Local, TimeZone};
+use serde::Serialize;
+use tokio::time::{timeout, Duration};
+
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Serialize)]
+pub struct Status {
+    daemon: Daemon,
+    connection: Connection,
+    backups: Vec<BackupStatus>,
+    jobs: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Daemon {
+    running: bool,
+    pid: Option<u32>,
+    paused: bool,
+    /// The socket exists but no daemon answers on it
+    stale_socket: bool,
+}
+
+#[derive(Serialize)]
+struct Connection {
+    connected: bool,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+enum Mode {
+    OnChange,
+    Manual,
+}
+
+#[derive(Serialize)]
+struct BackupStatus {
+    client_path: String,
+    server_path: String,
+    mode: Mode,
+    watching: bool,
+    last_success: Option<u64>,
+    last_failure: Option<u64>,
+    last_error: Option<String>,
+}
+
+async fn check_connection(storage: &Storage) -> Connection {
+    let config = match storage.config() {
+        Some(config) => config,
+        None => {
+            return Connection {
+                connected: false,
+                error: Some(String::from("No config, run 'bmu' to setup")),
+            }
+        }
+    };
+
+    let error = match timeout(
+        CONNECTION_TIMEOUT,
+        ssh::connect::to_server(config, storage.cache_dir.clone()),
+    )
+    .await
+    {
+        Ok(Ok(session)) => {
+            _ = session.close().await;
+            None
+        }
+        Ok(Err(e)) => Some(format!("{e:?}")),
+        Err(_) => Some(String::from("Timed out")),
+    };
+
+    Connection {
+        connected: error.is_none(),
+        error,
+    }
+}
+
+fn backup_status(
+    backup: &Backup,
+    history: &History,
+    background_backups: bool,
+    jobs: &[String],
+) -> BackupStatus {
+    let client_path = &backup.client_location.path;
+    let last_success = history.latest_success(client_path).ok().flatten();
+    let last_failure = history.latest_failure(client_path).ok().flatten();
+    let watcher_id = jobs::id_from_backup(backup, &jobs::Kind::BackupOnChange);
+
+    BackupStatus {
+        client_path: client_path.clone(),
+        server_path: backup.server_location.path.clone(),
+        mode: if background_backups {
+            Mode::OnChange
+        } else {
+            Mode::Manual
+        },
+        watching: jobs.contains(&watcher_id),
+        // backups of earlier versions only know their latest run
+        last_success: last_success.map(|run| run.ended_at).or(backup.latest_run),
+        last_failure: last_failure.as_ref().map(|run| run.ended_at),
+        last_error: last_failure.and_then(|run| run.error),
+    }
+}
+
+/// Collects the status of the daemon, the connection and every backup.
+pub async fn collect(storage: &Storage) -> Status {
+    let daemon_status = daemon::status(storage);
+    let jobs = daemon_status
+        .as_ref()
+        .map(|status| status.jobs.clone())
+        .unwrap_or_default();
+    let history = History::new(storage.data_dir.clone());
+    let background_backups = storage
+        .config()
+        .map_or(false, |config| config.allow_background_backup);
+
+    Status {
+        daemon: Daemon {
+            running: daemon_status.is_some(),
+            pid: daemon_status.as_ref().map(|status| status.pid),
+            paused: daemon_status.as_ref().map_or(false, |status| status.paused),
+            stale_socket: daemon_status.is_none() && daemon::socket_path(storage).exists(),
+        },
+        connection: check_connection(storage).await,
+        backups: storage
+            .backups()
+            .unwrap_or_default()
+            .iter()
+            .map(|backup| backup_status(backup, &history, background_backups, &jobs))
+            .collect(),
+        jobs,
+    }
+}
+
+fn format_timestamp(timestamp: Option<u64>) -> String {
+    timestamp
+        .and_then(|timestamp| i64::try_from(timestamp).ok())
+        .and_then(|timestamp| Local.timestamp_opt(timestamp, 0).single())
+        .map_or_else(
+            || String::from("never"),
+            |date| date.format("%Y-%m-%d %H:%M:%S").to_string(),
+        )
+}
+
+#[tokio::main]
+pub async fn show(json: bool) {
+    let storage = Storage::load().expect("⛔️ Could not load storage");
+    let status = collect(&storage).await;
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&status).expect("Could not serialize status")
+        );
+        return;
+    }
+
+    let mut messages = vec![
+        match status.daemon.pid {
+            Some(pid) if status.daemon.paused => format!("⏸️  Daemon is paused, pid {pid}"),
+            Some(pid) => format!("✅ Daemon is running with pid {pid}"),
+            None if status.daemon.stale_socket => {
+                String::from("⛔️ Daemon is not running, but did not stop cleanly")
+            }
+            None => String::from("💤 Daemon is not running"),
+        },
+        match &status.connection.error {
+            None => String::from("✅ Server is reachable"),
+            Some(error) => format!("⛔️ Server is not reachable: {error}"),
+        },
+    ];
+
+    for backup in &status.backups {
+        let mode = match (&backup.mode, backup.watching) {
+            (Mode::OnChange, true) => "watching",
+            (Mode::OnChange, false) => "on change, not watching",
+            (Mode::Manual, _) => "manual",
+        };
+
+        messages.push(format!("\n📁 {} ({mode})", backup.client_path));
+        messages.push(format!(
+            "   Last success: {}",
+            format_timestamp(backup.last_success)
+        ));
+
+        if backup.last_failure > backup.last_success {
+            messages.push(format!(
+                "   Last error:   {} {}",
+                format_timestamp(backup.last_failure),
+                backup.last_error.clone().unwrap_or_default()
+            ));
+        }
+    }
+
+    if !status.jobs.is_empty() {
+        messages.push(String::from("\n⚙️  Running jobs"));
+        for job in &status.jobs {
+            messages.push(format!("   {job}"));
+        }
+    }
+
+    ui::print_frame(TITLE, messages, true);
+}
@@ -1,8 +1,12 @@
+use back_me_up::jobs::fs::{write_atomic, FileLock};
 use back_me_up::models::app::Config;
 use back_me_up::models::backup::Backup;
 use std::path::PathBuf;
 use std::{env, fs};
 
+pub const CONFIG_FILE_NAME: &str = "server.conf.json";
+pub const BACKUPS_FILE_NAME: &str = "backups.json";
+
 #[derive(Debug)]
 pub enum Error {
     NotUnique(String),
@@ -47,7 +51,7 @@ impl Storage {
 
         if !cache_dir.exists() || !cache_dir.is_dir() {
             fs::create_dir_all(&cache_dir)?;
-        } 
+        }
         if !config_dir.exists() || !config_dir.is_dir() {
             fs::create_dir_all(&config_dir)?;
         }
@@ -71,19 +75,33 @@ impl Storage {
     }
 
     pub fn config(&self) -> Option<Config> {
-        if !self.config_dir.join("server.conf.json").exists() {
+        if !self.config_dir.join(CONFIG_FILE_NAME).exists() {
             return None;
         }
 
-        let config_file_path = self.config_dir.join("server.conf.json");
+        let config_file_path = self.config_dir.join(CONFIG_FILE_NAME);
         let config_file_contents =
             std::fs::read_to_string(config_file_path).expect("Failed to read config file");
 
         Some(serde_json::from_str(&config_file_contents).expect("Failed to parse config file"))
     }
 
+    /// Like `config`, but a file that can't be read or parsed is an error instead of a panic,
+    /// since another process might be in the middle of writing it.
+    pub fn try_config(&self) -> Result<Option<Config>, Error> {
+        let config_file_path = self.config_dir.join(CONFIG_FILE_NAME);
+
+        if !config_file_path.exists() {
+            return Ok(None);
+        }
+
+        Ok(Some(serde_json::from_str(&fs::read_to_string(
+            config_file_path,
+        )?)?))
+    }
+
     pub fn write_conig(&self, config: &Config) {
-        let config_file_path = self.config_dir.join("server.conf.json");
+        let config_file_path = self.config_dir.join(CONFIG_FILE_NAME);
         let config_file_contents =
             serde_json::to_string(&config).expect("Failed to serialize config");
         std::fs::write(config_file_path, config_file_contents)
@@ -91,17 +109,19 @@ impl Storage {
     }
 
     pub fn backups(&self) -> Result<Vec<Backup>, Error> {
-        if !self.data_dir.join("backups.json").exists() {
+        if !self.data_dir.join(BACKUPS_FILE_NAME).exists() {
             return Ok(vec![]);
         }
 
-        let backup_file_path = self.data_dir.join("backups.json");
+        let backup_file_path = self.data_dir.join(BACKUPS_FILE_NAME);
         let backup_file_contents = std::fs::read_to_string(backup_file_path)?;
 
         Ok(serde_json::from_str(&backup_file_contents)?)
     }
 
     pub fn add_backup(&self, backup: Backup) -> Result<(), Error> {
+        // the app and other bmu processes may change the backups at the same time
+        let _lock = FileLock::acquire(&self.data_dir.join(BACKUPS_FILE_NAME))?;
         let mut backups = self.backups()?;
         if backups.iter().any(|b| {
             b.client_location.path == backup.client_location.path
@@ -116,6 +136,7 @@ impl Storage {
     }
 
     pub fn delete_backup(&self, backup: &Backup) -> Result<(), Error> {
+        let _lock = FileLock::acquire(&self.data_dir.join(BACKUPS_FILE_NAME))?;
         let mut backups = self.backups()?;
         backups.retain(|b| {
             b.client_location.path != backup.client_location.path
@@ -141,10 +162,10 @@ impl Storage {
     }
 
     fn write_backups(&self, backups: &[Backup]) {
-        let backup_file_path = self.data_dir.join("backups.json");
+        let backup_file_path = self.data_dir.join(BACKUPS_FILE_NAME);
         let backup_file_contents =
             serde_json::to_string(&backups).expect("Failed to serialize backup file");
-        std::fs::write(backup_file_path, backup_file_contents)
+        write_atomic(&backup_file_path, &backup_file_contents)
             .expect("Failed to write backup file");
     }
 }
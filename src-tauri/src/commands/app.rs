@@ -1,5 +1,5 @@
 use super::Error;
-use crate::jobs;
+use crate::jobs::{self, scheduler::Priority};
 use crate::models::app::{self, MutexState};
 use crate::models::backup::Backup;
 use log::info;
@@ -21,9 +21,14 @@ pub fn backup_on_change(state: &MutexState, backup: Backup) -> Result<(), Error>
     }
 
     let job_id = jobs::id_from_backup(&backup, &jobs::Kind::BackupOnChange);
-    let jobs = Arc::clone(&state.jobs);
+    let failed_jobs = Arc::clone(&state.failed_jobs);
+    let history = Arc::clone(&state.history);
+    let metrics = Arc::clone(&state.metrics);
+    let app_cache_dir = state.app_cache_dir.lock()?.clone();
 
-    if jobs.lock()?.iter().any(|(id, _)| id == &job_id) {
+    let mut jobs = state.jobs.lock()?;
+    let mut pool = state.pool.lock()?;
+    if is_watched(&jobs, &pool, &job_id) {
         info!(
             "Already running background backup for {}",
             backup.client_location.path
@@ -31,48 +36,69 @@ pub fn backup_on_change(state: &MutexState, backup: Backup) -> Result<(), Error>
         return Ok(());
     };
 
-    let mut pool = state.pool.lock()?;
-    pool.execute(move |worker| {
-        jobs.lock()
-            .expect("Could not lock jobs")
-            .insert(job_id, worker.id);
-        jobs::backup::directory_on_change(&worker, &backup, config_to_move_into_thread);
+    let active = Arc::clone(&state.jobs);
+    pool.set_max_transfers(config_to_move_into_thread.max_transfers);
+    let worker_id = pool.spawn(job_id.clone(), Priority::Watcher, move |worker| {
+        jobs::backup::supervise_directory(
+            worker,
+            backup,
+            config_to_move_into_thread,
+            active,
+            failed_jobs,
+            history,
+            metrics,
+            app_cache_dir,
+        )
     })?;
+    jobs.insert(job_id, worker_id);
 
     Ok(())
 }
 
 pub fn terminate_background_backup(state: &MutexState, backup: &Backup) -> Result<(), Error> {
     let mut jobs = state.jobs.lock()?;
+    let pool = state.pool.lock()?;
     let job_id = &jobs::id_from_backup(backup, &jobs::Kind::BackupOnChange);
-    let worker_id = if let Some(id) = jobs.get(job_id) {
-        id
-    } else {
+
+    if !is_watched(&jobs, &pool, job_id) {
         let error = jobs::Error::NotFound("could not find job".to_string());
         return Err(Error::Job(error));
-    };
+    }
 
-    let mut pool = state.pool.lock()?;
-    let result = pool.terminate_job(*worker_id, || {
-        let file_path = format!("{}/.bmu_event_trigger", backup.client_location.path);
+    for (_, worker) in pool
+        .active_jobs()
+        .into_iter()
+        .filter(|(id, _)| id == job_id)
+    {
+        if let Err(e) = pool.terminate_job(worker) {
+            return Err(Error::Job(jobs::Error::Terminate(e)));
+        }
+    }
+    jobs.remove(job_id);
 
-        _ = super::os::create_file(&file_path);
-        _ = super::os::delete_file(&file_path);
-    });
+    Ok(())
+}
 
-    if let Err(e) = result {
-        let error = jobs::Error::Terminate(e);
-        return Err(Error::Job(error));
-    }
+/// Terminates every background backup, but keeps one-off backups running.
+pub fn terminate_watchers(state: &MutexState) -> Result<(), Error> {
+    let mut jobs = state.jobs.lock()?;
+    let pool = state.pool.lock()?;
+    let watchers: Vec<(String, usize)> = pool
+        .active_jobs()
+        .into_iter()
+        .filter(|(job_id, _)| job_id.ends_with("_backup_on_change"))
+        .collect();
 
-    if jobs.remove(job_id).is_some() {
-        Ok(())
-    } else {
-        let error = jobs::Error::Terminate(String::from(
-            "Worker was terminated, but couldn't remove job from active list",
-        ));
-        Err(Error::Job(error))
+    for (job_id, worker) in watchers {
+        info!("Terminating job: {job_id}");
+
+        if let Err(e) = pool.terminate_job(worker) {
+            return Err(Error::Job(jobs::Error::Terminate(e)));
+        }
+        jobs.remove(&job_id);
     }
+
+    Ok(())
 }
 
 pub fn start_background_backups(state: &MutexState, backups: &[Backup]) -> Result<(), Error> {
@@ -86,31 +112,20 @@ pub fn start_background_backups(state: &MutexState, backups: &[Backup]) -> Resul
         }
     }
 
-    let jobs = state.jobs.lock()?;
-    let available_workers = state.pool.lock()?.available_workers();
-    let backup_jobs_that_are_not_already_running: Vec<_> = backups
-        .iter()
-        .filter(|b| {
-            !jobs
-                .iter()
-                .any(|(job_id, _)| job_id == &jobs::id_from_backup(b, &jobs::Kind::BackupOnChange))
-        })
-        .collect();
-
-    let num_of_backups_to_start = backup_jobs_that_are_not_already_running.len();
-
-    if num_of_backups_to_start > available_workers {
-        state
-            .pool
-            .lock()?
-            .create_workers(num_of_backups_to_start - available_workers);
-    }
-
-    if num_of_backups_to_start == 0 {
-        return Ok(());
-    }
-
-    state.pool.lock()?.start_all_stopped_workers();
+    let mut jobs = state.jobs.lock()?;
+    let backup_jobs_that_are_not_already_running: Vec<_> = {
+        let pool = state.pool.lock()?;
+        backups
+            .iter()
+            .filter(|b| {
+                !is_watched(
+                    &jobs,
+                    &pool,
+                    &jobs::id_from_backup(b, &jobs::Kind::BackupOnChange),
+                )
+            })
+            .collect()
+    };
 
     for value in backup_jobs_that_are_not_already_running {
         let config_to_move_into_thread = if let Some(config) = state_config.as_ref() {
@@ -123,15 +138,82 @@ pub fn start_background_backups(state: &MutexState, backups: &[Backup]) -> Resul
         let backup = value.clone();
 
         let job_id = jobs::id_from_backup(&backup, &jobs::Kind::BackupOnChange);
-        let jobs = Arc::clone(&state.jobs);
+        let active = Arc::clone(&state.jobs);
+        let failed_jobs = Arc::clone(&state.failed_jobs);
+        let history = Arc::clone(&state.history);
+        let metrics = Arc::clone(&state.metrics);
+        let app_cache_dir = state.app_cache_dir.lock()?.clone();
 
         let mut pool = state.pool.lock()?;
-        pool.execute(move |worker| {
-            jobs.lock()
-                .expect("Could not lock jobs")
-                .insert(job_id, worker.id);
-            jobs::backup::directory_on_change(&worker, &backup, config_to_move_into_thread);
+        pool.set_max_transfers(config_to_move_into_thread.max_transfers);
+        let worker_id = pool.spawn(job_id.clone(), Priority::Watcher, move |worker| {
+            jobs::backup::supervise_directory(
+                worker,
+                backup,
+                config_to_move_into_thread,
+                active,
+                failed_jobs,
+                history,
+                metrics,
+                app_cache_dir,
+            )
         })?;
+        jobs.insert(job_id, worker_id);
+    }
+
+    Ok(())
+}
+
+/// Whether a watcher runs for `job_id`. A watcher that panicked is not among the active jobs
+/// while it waits to be restarted, but its task is still in the pool. A terminated watcher that
+/// is still finishing doesn't count, so it can be replaced right away.
+fn is_watched(jobs: &jobs::Active, pool: &jobs::Pool, job_id: &str) -> bool {
+    jobs.contains_key(job_id) || pool.active_jobs().iter().any(|(id, _)| id == job_id)
+}
+
+/// Splits a change of the backups from `previous` to `next` into the watchers to stop and the
+/// ones to start. A backup with changed options or mirrors is restarted, so it is in both. A
+/// new latest run alone doesn't change a backup.
+#[must_use]
+pub fn diff_backups(previous: &[Backup], next: &[Backup]) -> (Vec<Backup>, Vec<Backup>) {
+    let is_in =
+        |backups: &[Backup], backup: &Backup| backups.iter().any(|other| other.same_config(backup));
+    let to_stop = previous
+        .iter()
+        .filter(|backup| !is_in(next, backup))
+        .cloned()
+        .collect();
+    let to_start = next
+        .iter()
+        .filter(|backup| !is_in(previous, backup))
+        .cloned()
+        .collect();
+
+    (to_stop, to_start)
+}
+
+/// Stops and starts only the watchers affected by a change of the backups from `previous` to
+/// `next`, every other watcher keeps running.
+pub fn apply_backup_changes(
+    state: &MutexState,
+    previous: &[Backup],
+    next: &[Backup],
+) -> Result<(), Error> {
+    let (to_stop, to_start) = diff_backups(previous, next);
+
+    for backup in &to_stop {
+        let job_id = jobs::id_from_backup(backup, &jobs::Kind::BackupOnChange);
+        if is_watched(&state.jobs.lock()?, &state.pool.lock()?, &job_id) {
+            info!(
+                "Stopping background backup for {}",
+                backup.client_location.path
+            );
+            terminate_background_backup(state, backup)?;
+        }
+    }
+
+    if !to_start.is_empty() {
+        start_background_backups(state, &to_start)?;
     }
 
     Ok(())
@@ -2,6 +2,7 @@ use crate::jobs::{self, Pool};
 use crate::models::app::{Config, Error as AppError};
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::path::PathBuf;
 use std::sync::{MutexGuard, PoisonError};
 
 pub mod app;
@@ -32,6 +33,12 @@ impl From<PoisonError<MutexGuard<'_, Pool>>> for Error {
     }
 }
 
+impl From<PoisonError<MutexGuard<'_, PathBuf>>> for Error {
+    fn from(e: PoisonError<MutexGuard<PathBuf>>) -> Self {
+        Self::App(AppError::from(e))
+    }
+}
+
 impl From<jobs::Error> for Error {
     fn from(e: jobs::Error) -> Self {
         Self::Job(e)
@@ -1,9 +1,14 @@
-use log::error;
-
 use super::Error;
+use crate::jobs::cancel::{self, Cancel};
+use crate::jobs::history;
+use crate::models::backup::Backup;
+use crate::ssh::commands::{rsync_result, Copied};
 use std::fs;
 use std::process::Command;
 
+/// Seconds `ps` may round the elapsed time of a process down by
+const START_TOLERANCE: u64 = 2;
+
 pub fn get_hostname() -> Result<String, Error> {
     // TODO: use 'hostname' command for windows
     let uname = Command::new("uname")
@@ -23,58 +28,50 @@ pub fn get_hostname() -> Result<String, Error> {
     }
 }
 
-pub fn create_file(file_path: &str) -> Result<(), Error> {
-    match Command::new("touch").args([&file_path]).output() {
-        Ok(output) => {
-            if output.status.success() {
-                Ok(())
-            } else {
-                let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-                let why = format!("Failed to create file: {stdout}\n{stderr}");
-                error!("{why}");
-                Err(Error::IO(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    why,
-                )))
-            }
-        }
-        Err(e) => {
-            let why = format!("Failed to create file: {e:?}");
-            error!("{why}");
-            Err(Error::IO(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                why,
-            )))
-        }
-    }
+/// Returns true if a process with `pid` exists. If `name` is given, the command of the process
+/// must contain it as well, so that a pid reused by another program is not mistaken for it.
+#[must_use]
+pub fn process_is_running(pid: u32, name: Option<&str>) -> bool {
+    Command::new("ps")
+        .args(["-p", &pid.to_string(), "-o", "comm="])
+        .output()
+        .map_or(false, |output| {
+            output.status.success()
+                && name.map_or(true, |name| {
+                    String::from_utf8_lossy(&output.stdout).contains(name)
+                })
+        })
 }
 
-pub fn delete_file(file_path: &str) -> Result<(), Error> {
-    match Command::new("rm").args([&file_path]).output() {
-        Ok(output) => {
-            if output.status.success() {
-                Ok(())
-            } else {
-                let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-                let why = format!("Failed to remove file: {stdout}\n{stderr}");
-                error!("{why}");
-                Err(Error::IO(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    why,
-                )))
-            }
-        }
-        Err(e) => {
-            let why = format!("Failed to remove file: {e:?}");
-            error!("{why}");
-            Err(Error::IO(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                why,
-            )))
-        }
-    }
+/// Returns true if a process with `pid` exists that started no later than `started_at`, a unix
+/// timestamp. A process that started afterwards only reuses the pid of one that is gone.
+#[must_use]
+pub fn process_is_running_since(pid: u32, started_at: u64) -> bool {
+    let output = match Command::new("ps")
+        .args(["-p", &pid.to_string(), "-o", "etime="])
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        _ => return false,
+    };
+
+    // a start that can't be read keeps the process, like checking the pid alone would
+    parse_elapsed(String::from_utf8_lossy(&output.stdout).trim()).map_or(true, |elapsed| {
+        history::now().saturating_sub(elapsed) <= started_at + START_TOLERANCE
+    })
+}
+
+/// Seconds of an elapsed time as printed by `ps -o etime`, `[[dd-]hh:]mm:ss`.
+fn parse_elapsed(elapsed: &str) -> Option<u64> {
+    let (days, time) = match elapsed.split_once('-') {
+        Some((days, time)) => (days.parse::<u64>().ok()?, time),
+        None => (0, elapsed),
+    };
+    let seconds = time.split(':').try_fold(0, |total: u64, part| {
+        Some(total * 60 + part.parse::<u64>().ok()?)
+    })?;
+
+    Some(days * 86_400 + seconds)
 }
 
 #[must_use]
@@ -86,3 +83,49 @@ pub fn create_directory(path: &str) -> Result<(), Error> {
     fs::create_dir(path)?;
     Ok(())
 }
+
+/// Mirrors a backup to a directory on a locally mounted disk with rsync, stopping it once
+/// `cancel` is set.
+pub fn backup_to_disk(
+    backup: &Backup,
+    is_directory: bool,
+    cancel: &Cancel,
+) -> Result<Copied, Error> {
+    let target = &backup.server_location.path;
+
+    // rsync only creates the last component of the target path
+    if let Some(parent) = std::path::Path::new(target).parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let rsync = cancel::output(
+        Command::new("rsync")
+            .arg("-a")
+            .arg("--exclude=.*")
+            .arg("--stats")
+            .arg("--itemize-changes")
+            .args(backup.database_excludes(is_directory))
+            .arg(backup.client_source(is_directory))
+            .arg(target),
+        cancel,
+    )?;
+
+    rsync_result(&rsync).map_err(|why| {
+        Error::IO(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            format!("Rsync to disk failed: {why}"),
+        ))
+    })
+}
+
+pub fn delete_from_disk(path: &str) -> Result<(), Error> {
+    let target = std::path::Path::new(path);
+
+    if target.is_dir() {
+        fs::remove_dir_all(target)?;
+    } else if target.exists() {
+        fs::remove_file(target)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,318 @@
+use crate::jobs::{self, Attempt};
+use crate::models::app::MutexState;
+use serde::{Deserialize, Serialize};
+use std::io;
+#[cfg(unix)]
+use std::io::{BufRead, BufReader, Read, Write};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{mpsc, Arc, Mutex};
+#[cfg(unix)]
+use std::thread;
+#[cfg(unix)]
+use std::time::Duration;
+use ts_rs::TS;
+
+const SOCKET_FILE_NAME: &str = "bmu.sock";
+#[cfg(unix)]
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize)]
+pub enum Error {
+    /// No daemon is listening on the socket
+    NotRunning(String),
+    Io(String),
+    Protocol(String),
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Protocol(e.to_string())
+    }
+}
+
+/// A request to the daemon, sent as a single line of json.
+#[derive(TS, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[ts(
+    export,
+    export_to = "../../bindings/DaemonRequest.ts",
+    rename = "DaemonRequest"
+)]
+pub enum Request {
+    Status,
+    Stop,
+    /// Reads config and backups from storage again
+    Reload,
+    /// Runs the backup of the given client path once
+    RunBackup(String),
+    /// Stops the watchers but keeps the connection
+    Pause,
+    Resume,
+    /// Renders the metrics in the Prometheus text format
+    Metrics,
+    /// Cancels the one-off backup with the given job id
+    CancelJob(String),
+    /// Starts watching the backup of the given client path
+    Watch(String),
+    /// Stops watching the backup of the given client path, until a reload finds it again
+    Unwatch(String),
+    /// Stops every watcher, until a reload finds the backups again
+    UnwatchAll,
+}
+
+#[derive(TS, Serialize, Deserialize, Clone, Debug)]
+#[ts(export)]
+pub struct DaemonStatus {
+    pub pid: u32,
+    pub paused: bool,
+    pub jobs: Vec<String>,
+    pub failed_jobs: Vec<String>,
+    /// Jobs the pool retries, older daemons don't report them
+    #[serde(default)]
+    pub retrying: Vec<Attempt>,
+    /// Ids of cancelled jobs
+    #[serde(default)]
+    pub cancelled: Vec<String>,
+    /// Ids of jobs waiting for a transfer, in the order they start
+    #[serde(default)]
+    pub queued: Vec<String>,
+}
+
+impl DaemonStatus {
+    /// The jobs run by `state` in this process.
+    #[must_use]
+    pub fn current(state: &MutexState, paused: bool) -> Self {
+        let ids = |jobs: &Arc<Mutex<jobs::Active>>| {
+            jobs.lock()
+                .map(|jobs| jobs.keys().cloned().collect())
+                .unwrap_or_default()
+        };
+
+        let pool = state.pool.lock().ok();
+
+        Self {
+            pid: process::id(),
+            paused,
+            jobs: ids(&state.jobs),
+            failed_jobs: ids(&state.failed_jobs),
+            retrying: pool
+                .as_ref()
+                .and_then(|pool| {
+                    pool.retrying()
+                        .lock()
+                        .map(|retrying| retrying.values().cloned().collect())
+                        .ok()
+                })
+                .unwrap_or_default(),
+            cancelled: pool
+                .as_ref()
+                .and_then(|pool| {
+                    pool.cancellations()
+                        .lock()
+                        .map(|cancellations| {
+                            cancellations
+                                .iter()
+                                .filter(|(_, cancel)| cancel.is_cancelled())
+                                .map(|(id, _)| id.clone())
+                                .collect()
+                        })
+                        .ok()
+                })
+                .unwrap_or_default(),
+            queued: pool
+                .as_ref()
+                .map(|pool| pool.scheduler().queued())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Status of the job `id`, with the same meaning as `jobs::check_status`.
+    #[must_use]
+    pub fn status_of(&self, id: &str) -> jobs::Status {
+        jobs::status_from(
+            self.retrying.iter().find(|attempt| attempt.job_id == id),
+            self.jobs.iter().any(|job| job == id),
+            self.failed_jobs.iter().any(|job| job == id),
+            self.cancelled.iter().any(|job| job == id),
+            self.queued.iter().any(|job| job == id),
+        )
+    }
+}
+
+/// The answer of the daemon, sent as a single line of json.
+#[derive(TS, Serialize, Deserialize, Clone, Debug)]
+#[ts(
+    export,
+    export_to = "../../bindings/DaemonResponse.ts",
+    rename = "DaemonResponse"
+)]
+pub enum Response {
+    Ack(String),
+    Status(DaemonStatus),
+    Metrics(String),
+    Error(String),
+}
+
+/// A request received by the daemon along with the channel its response is sent on.
+pub type Incoming = (Request, mpsc::Sender<Response>);
+
+/// The socket of the daemon, kept in the daemon directory of the app cache.
+#[must_use]
+pub fn socket_path(app_cache_dir: &Path) -> PathBuf {
+    app_cache_dir.join("daemon").join(SOCKET_FILE_NAME)
+}
+
+#[cfg(unix)]
+fn connect(socket: &Path) -> Result<UnixStream, Error> {
+    let stream = UnixStream::connect(socket)
+        .map_err(|e| Error::NotRunning(format!("{}: {e}", socket.display())))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    Ok(stream)
+}
+
+#[cfg(unix)]
+fn request(socket: &Path, request: &Request) -> Result<(Response, BufReader<UnixStream>), Error> {
+    let mut stream = connect(socket)?;
+    writeln!(stream, "{}", serde_json::to_string(request)?)?;
+
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+
+    if line.is_empty() {
+        return Err(Error::Protocol(String::from(
+            "The daemon closed the connection without a response",
+        )));
+    }
+
+    Ok((serde_json::from_str(&line)?, reader))
+}
+
+/// Sends a request to the daemon and waits for its response.
+#[cfg(unix)]
+pub fn send(socket: &Path, request: &Request) -> Result<Response, Error> {
+    self::request(socket, request).map(|(response, _)| response)
+}
+
+/// Returns true if a daemon answers on the socket.
+#[cfg(unix)]
+#[must_use]
+pub fn is_running(socket: &Path) -> bool {
+    matches!(send(socket, &Request::Status), Ok(Response::Status(_)))
+}
+
+/// Asks the daemon to stop and blocks until it has exited. The daemon keeps the connection
+/// open after acknowledging, so it is closed by the operating system when the process ends.
+#[cfg(unix)]
+pub fn stop_and_wait(socket: &Path, timeout: Duration) -> Result<Response, Error> {
+    let (response, mut reader) = request(socket, &Request::Stop)?;
+    reader.get_ref().set_read_timeout(Some(timeout))?;
+
+    match reader.read_to_end(&mut vec![]) {
+        Ok(_) => Ok(response),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
+            Err(Error::Io(format!(
+                "The daemon did not exit within {} seconds",
+                timeout.as_secs()
+            )))
+        }
+        Err(e) => Err(Error::from(e)),
+    }
+}
+
+/// Binds the socket and forwards every request to `sender`. Every connection is handled on its
+/// own thread, so a request waiting for the daemon does not hold up the others. A socket file
+/// left behind by a daemon that did not exit cleanly is replaced.
+#[cfg(unix)]
+pub fn listen(socket: &Path, sender: tokio::sync::mpsc::Sender<Incoming>) -> Result<(), Error> {
+    if socket.exists() {
+        if is_running(socket) {
+            return Err(Error::Io(format!(
+                "A daemon is already listening on {}",
+                socket.display()
+            )));
+        }
+        std::fs::remove_file(socket)?;
+    }
+
+    let listener = UnixListener::bind(socket)?;
+
+    thread::spawn(move || {
+        // connections of stop requests are held until the process exits
+        let waiting = Arc::new(Mutex::new(vec![]));
+
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::error!("Could not accept control connection: {e:?}");
+                    continue;
+                }
+            };
+            let sender = sender.clone();
+            let waiting = Arc::clone(&waiting);
+
+            thread::spawn(move || match handle_connection(&stream, &sender) {
+                Ok(Some(Request::Stop)) => match waiting.lock() {
+                    Ok(mut waiting) => waiting.push(stream),
+                    Err(e) => log::error!("Could not hold stop connection: {e:?}"),
+                },
+                Ok(_) => (),
+                Err(e) => log::error!("Could not handle control request: {e:?}"),
+            });
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn handle_connection(
+    stream: &UnixStream,
+    sender: &tokio::sync::mpsc::Sender<Incoming>,
+) -> Result<Option<Request>, Error> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+
+    let (response, request) = match serde_json::from_str::<Request>(&line) {
+        Ok(request) => {
+            let (response_sender, response_receiver) = mpsc::channel();
+            sender
+                .blocking_send((request.clone(), response_sender))
+                .map_err(|e| Error::Protocol(e.to_string()))?;
+            let response = response_receiver
+                .recv_timeout(TIMEOUT)
+                .unwrap_or_else(|e| Response::Error(format!("No response from daemon: {e}")));
+            (response, Some(request))
+        }
+        Err(e) => (Response::Error(format!("Invalid request: {e}")), None),
+    };
+
+    let mut writer = stream;
+    writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+
+    Ok(request)
+}
+
+/// The daemon only runs on unix, so there is never one to talk to elsewhere.
+#[cfg(not(unix))]
+pub fn send(socket: &Path, _request: &Request) -> Result<Response, Error> {
+    Err(Error::NotRunning(socket.display().to_string()))
+}
+
+#[cfg(not(unix))]
+#[must_use]
+pub fn is_running(_socket: &Path) -> bool {
+    false
+}
@@ -0,0 +1,112 @@
+use crate::handlers::Error;
+use back_me_up::commands;
+use back_me_up::control::{self, DaemonStatus, Request, Response};
+use back_me_up::models::app::MutexState;
+use log::{error, info};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Whether a daemon answered the latest poll of `watch`
+static ATTACHED: AtomicBool = AtomicBool::new(false);
+
+/// The socket of a running bmu daemon. Background backups are left to the daemon when there is
+/// one, so that the same folders are not watched by both. Whether there is one is only checked
+/// by `watch`, commands don't wait for a busy daemon to find out.
+pub fn attached(state: &MutexState) -> Option<PathBuf> {
+    if ATTACHED.load(Ordering::SeqCst) {
+        Some(socket_path(state)?)
+    } else {
+        None
+    }
+}
+
+fn socket_path(state: &MutexState) -> Option<PathBuf> {
+    Some(control::socket_path(&state.app_cache_dir.lock().ok()?))
+}
+
+/// Runs a round trip to the daemon on a blocking thread, so that a slow daemon doesn't stall the
+/// thread of the command.
+async fn blocking<T: Send + 'static>(
+    round_trip: impl FnOnce() -> Result<T, Error> + Send + 'static,
+) -> Result<T, Error> {
+    tauri::async_runtime::spawn_blocking(round_trip)
+        .await
+        .map_err(|e| Error::Command(format!("Could not reach the daemon: {e}")))?
+}
+
+/// Sends a request to the daemon and returns its acknowledgement.
+pub async fn request(socket: PathBuf, request: Request) -> Result<String, Error> {
+    blocking(move || match control::send(&socket, &request)? {
+        Response::Ack(message) => Ok(message),
+        Response::Error(message) => Err(Error::Command(message)),
+        Response::Status(_) | Response::Metrics(_) => Err(Error::Command(String::from(
+            "The daemon did not answer with an acknowledgement",
+        ))),
+    })
+    .await
+}
+
+fn status(socket: &Path) -> Result<DaemonStatus, Error> {
+    match control::send(socket, &Request::Status)? {
+        Response::Status(status) => Ok(status),
+        Response::Error(message) => Err(Error::Command(message)),
+        Response::Ack(_) | Response::Metrics(_) => Err(Error::Command(String::from(
+            "The daemon did not answer with a status",
+        ))),
+    }
+}
+
+/// Status of the attached daemon, `None` when there is none.
+pub async fn attached_status(state: &MutexState) -> Result<Option<DaemonStatus>, Error> {
+    match attached(state) {
+        Some(socket) => Ok(Some(blocking(move || status(&socket)).await?)),
+        None => Ok(None),
+    }
+}
+
+/// The jobs run by the attached daemon, or by the app when there is none.
+pub async fn jobs(state: &MutexState) -> Result<DaemonStatus, Error> {
+    Ok(attached_status(state)
+        .await?
+        .unwrap_or_else(|| DaemonStatus::current(state, false)))
+}
+
+/// Asks the daemon for its status and remembers whether it answered.
+fn poll(state: &MutexState) -> Option<DaemonStatus> {
+    let status = socket_path(state).and_then(|socket| status(&socket).ok());
+    ATTACHED.store(status.is_some(), Ordering::SeqCst);
+    status
+}
+
+/// Polls the daemon and emits its status as `daemon-status`, `null` when there is none. The
+/// watchers of the app are stopped as soon as a daemon shows up, the frontend starts them again
+/// once it is gone. The first poll happens right away, before the frontend starts any watcher.
+pub fn watch(app: AppHandle) {
+    let mut was_attached = poll(&app.state::<MutexState>()).is_some();
+
+    thread::spawn(move || loop {
+        let state = app.state::<MutexState>();
+        let status = poll(&state);
+
+        if status.is_some() && !was_attached {
+            info!("Attached to the bmu daemon, stopping background backups of the app");
+            if let Err(e) = commands::app::terminate_watchers(&state) {
+                error!("Could not stop background backups: {e:?}");
+            }
+        } else if status.is_none() && was_attached {
+            info!("The bmu daemon is gone, background backups are run by the app again");
+        }
+        was_attached = status.is_some();
+
+        if let Err(e) = app.emit_all("daemon-status", &status) {
+            error!("Could not emit daemon status: {e:?}");
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    });
+}
@@ -1,21 +1,26 @@
-use crate::jobs::{self, Pool};
+use crate::daemon;
+use crate::jobs::status::{self, JobStatus};
+use crate::jobs::{self, history::History, Pool};
 use back_me_up::commands;
+use back_me_up::control::{self, DaemonStatus, Request};
 use back_me_up::models::app::{self, Config};
-use back_me_up::models::backup::Backup;
+use back_me_up::models::backup::{Backup, Destination};
+use back_me_up::models::history::Run;
 use back_me_up::models::storage::Folder;
-use back_me_up::ssh::{self, connect::Connection};
+use back_me_up::ssh::{self, address::Address, connect::Connection, host_key, key};
 use log::{debug, info};
 use serde::Serialize;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::{Arc, MutexGuard, PoisonError};
-use tauri::State;
+use tauri::{AppHandle, State};
 
 #[derive(Debug, Serialize)]
 pub enum Error {
     App(app::Error),
     Ssh(ssh::Error),
     Job(jobs::Error),
+    Daemon(control::Error),
     Command(String),
 }
 
@@ -25,6 +30,12 @@ impl From<ssh::Error> for Error {
     }
 }
 
+impl From<control::Error> for Error {
+    fn from(e: control::Error) -> Self {
+        Self::Daemon(e)
+    }
+}
+
 impl From<app::Error> for Error {
     fn from(e: app::Error) -> Self {
         Self::App(e)
@@ -61,6 +72,12 @@ impl From<PoisonError<MutexGuard<'_, HashMap<String, usize>>>> for Error {
     }
 }
 
+impl From<PoisonError<MutexGuard<'_, History>>> for Error {
+    fn from(e: PoisonError<MutexGuard<History>>) -> Self {
+        Self::App(app::Error::from(e))
+    }
+}
+
 impl From<PoisonError<MutexGuard<'_, PathBuf>>> for Error {
     fn from(e: PoisonError<MutexGuard<PathBuf>>) -> Self {
         Self::App(app::Error::from(e))
@@ -135,6 +152,11 @@ pub async fn backup_entity(
     backup: Backup,
     state: State<'_, app::MutexState>,
 ) -> Result<String, Error> {
+    if let Some(socket) = daemon::attached(&state) {
+        let client_path = backup.client_location.path;
+        return daemon::request(socket, Request::RunBackup(client_path)).await;
+    }
+
     Ok(jobs::backup::entity_to_server(backup, Arc::new(state.inner())).await?)
 }
 
@@ -149,25 +171,40 @@ pub fn drop_pool(state: State<'_, app::MutexState>) -> Result<(), Error> {
 
 #[tauri::command]
 #[allow(clippy::needless_pass_by_value)]
-pub fn backup_on_change(state: State<'_, app::MutexState>, backup: Backup) -> Result<(), Error> {
+pub async fn backup_on_change(
+    state: State<'_, app::MutexState>,
+    backup: Backup,
+) -> Result<(), Error> {
+    if let Some(socket) = daemon::attached(&state) {
+        return leave_to_daemon(socket, vec![Request::Watch(backup.client_location.path)]).await;
+    }
+
     Ok(commands::app::backup_on_change(&state, backup)?)
 }
 
 #[tauri::command]
 #[allow(clippy::needless_pass_by_value)]
-pub fn terminate_background_backup(
+pub async fn terminate_background_backup(
     state: State<'_, app::MutexState>,
     backup: Backup,
 ) -> Result<(), Error> {
+    if let Some(socket) = daemon::attached(&state) {
+        return leave_to_daemon(socket, vec![Request::Unwatch(backup.client_location.path)]).await;
+    }
+
     Ok(commands::app::terminate_background_backup(&state, &backup)?)
 }
 
 #[tauri::command]
 #[allow(clippy::needless_pass_by_value)]
-pub fn terminate_all_background_jobs(state: State<'_, app::MutexState>) -> Result<(), Error> {
+pub async fn terminate_all_background_jobs(state: State<'_, app::MutexState>) -> Result<(), Error> {
+    if let Some(socket) = daemon::attached(&state) {
+        return leave_to_daemon(socket, vec![Request::UnwatchAll]).await;
+    }
+
     let mut jobs = state.jobs.lock()?;
-    let mut pool = state.pool.lock()?;
-    jobs::backup::terminate_all(&mut jobs, &mut pool);
+    let pool = state.pool.lock()?;
+    jobs::backup::terminate_all(&mut jobs, &pool);
     Ok(())
 }
 
@@ -175,9 +212,9 @@ pub fn terminate_all_background_jobs(state: State<'_, app::MutexState>) -> Resul
 pub async fn reset(state: State<'_, app::MutexState>) -> Result<(), Error> {
     state.connection.lock().await.take();
     let mut jobs = state.jobs.lock()?;
-    let mut pool = state.pool.lock()?;
+    let pool = state.pool.lock()?;
 
-    jobs::backup::terminate_all(&mut jobs, &mut pool);
+    jobs::backup::terminate_all(&mut jobs, &pool);
 
     state.config.lock()?.take();
     drop(pool);
@@ -187,13 +224,89 @@ pub async fn reset(state: State<'_, app::MutexState>) -> Result<(), Error> {
 
 #[tauri::command]
 #[allow(clippy::needless_pass_by_value)]
-pub fn start_background_backups(
+pub async fn start_background_backups(
     state: State<'_, app::MutexState>,
     backups: Vec<Backup>,
 ) -> Result<(), Error> {
+    if let Some(socket) = daemon::attached(&state) {
+        let requests: Vec<Request> = backups
+            .into_iter()
+            .map(|backup| Request::Watch(backup.client_location.path))
+            .collect();
+        return leave_to_daemon(socket, requests).await;
+    }
+
     Ok(commands::app::start_background_backups(&state, &backups)?)
 }
 
+/// Asks the daemon to start or stop watchers. The app writes backups and config only after it
+/// changed the watchers, so a reload of the daemon would still see the previous ones.
+async fn leave_to_daemon(socket: PathBuf, requests: Vec<Request>) -> Result<(), Error> {
+    info!("Leaving background backups to the bmu daemon");
+    for request in requests {
+        daemon::request(socket.clone(), request).await?;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub fn is_valid_server_address(address: String) -> bool {
+    ssh::address::is_valid(&address)
+}
+
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub fn check_host_key(
+    state: State<'_, app::MutexState>,
+    config: Config,
+) -> Result<host_key::HostKeyScan, Error> {
+    let known_hosts = host_key::app_known_hosts(&state.app_cache_dir.lock()?);
+    let address = Address::resolve(&config);
+
+    Ok(host_key::check(&address, &known_hosts)?)
+}
+
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub fn trust_host_key(
+    state: State<'_, app::MutexState>,
+    config: Config,
+    fingerprint: String,
+) -> Result<(), Error> {
+    let known_hosts = host_key::app_known_hosts(&state.app_cache_dir.lock()?);
+    let address = Address::resolve(&config);
+
+    Ok(host_key::trust(&address, &known_hosts, &fingerprint)?)
+}
+
+/// Generates a key for the app, installs it on the server and returns the config using it. The
+/// password is asked for once through the askpass program of the system.
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub async fn install_ssh_key(app_handle: AppHandle, config: Config) -> Result<Config, Error> {
+    let config_dir = app_handle.path_resolver().app_config_dir().ok_or_else(|| {
+        Error::App(app::Error::Storage(String::from(
+            "Could not find app config directory",
+        )))
+    })?;
+
+    let private_key = key::generate(&config_dir, &format!("back-me-up@{}", config.client_name))?;
+    let config = Config {
+        identity_file: Some(private_key.display().to_string()),
+        ..config
+    };
+
+    key::install(
+        &Address::resolve(&config),
+        &private_key,
+        &key::PasswordPrompt::Askpass,
+    )?;
+
+    Ok(config)
+}
+
 #[tauri::command]
 pub fn get_client_name() -> Result<String, Error> {
     Ok(commands::os::get_hostname()?)
@@ -201,9 +314,87 @@ pub fn get_client_name() -> Result<String, Error> {
 
 #[tauri::command]
 #[allow(clippy::needless_pass_by_value)]
-pub fn check_job_status(
+pub async fn check_job_status(
     state: State<'_, app::MutexState>,
     id: String,
-) -> Result<jobs::Status, Error> {
-    Ok(jobs::check_status(&id, &state.jobs, &state.failed_jobs)?)
+) -> Result<JobStatus, Error> {
+    let lists = daemon::jobs(&state).await?;
+    let runs = state.history.lock()?.runs()?;
+
+    Ok(status::describe(&id, &lists, &runs))
+}
+
+/// Every job known to the pool or found in the history, run by the daemon if one is attached.
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub async fn list_jobs(state: State<'_, app::MutexState>) -> Result<Vec<JobStatus>, Error> {
+    let lists = daemon::jobs(&state).await?;
+    let runs = state.history.lock()?.runs()?;
+
+    Ok(status::list(&lists, &runs))
+}
+
+/// Cancels a one-off backup started by `backup_entity`, stopping the transfer it runs.
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub async fn cancel_job(state: State<'_, app::MutexState>, id: String) -> Result<(), Error> {
+    if let Some(socket) = daemon::attached(&state) {
+        daemon::request(socket, Request::CancelJob(id)).await?;
+        return Ok(());
+    }
+
+    Ok(state.pool.lock()?.cancel(&id)?)
+}
+
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub async fn check_destination_status(
+    state: State<'_, app::MutexState>,
+    id: String,
+    destination: Destination,
+) -> Result<JobStatus, Error> {
+    let lists = daemon::jobs(&state).await?;
+    let runs = state.history.lock()?.runs()?;
+
+    let status = status::describe(&id, &lists, &runs);
+    if matches!(
+        status.state,
+        status::State::Queued | status::State::Running | status::State::Cancelled
+    ) {
+        return Ok(status);
+    }
+
+    Ok(status::describe(
+        &jobs::id_for_destination(&id, &destination),
+        &lists,
+        &runs,
+    ))
+}
+
+/// Status of the bmu daemon, `None` when the app runs the backups itself.
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub async fn daemon_status(
+    state: State<'_, app::MutexState>,
+) -> Result<Option<DaemonStatus>, Error> {
+    daemon::attached_status(&state).await
+}
+
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub fn list_runs(
+    state: State<'_, app::MutexState>,
+    client_path: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<Run>, Error> {
+    Ok(state.history.lock()?.query(client_path.as_deref(), limit)?)
+}
+
+#[tauri::command]
+#[allow(clippy::needless_pass_by_value)]
+pub fn latest_successful_run(
+    state: State<'_, app::MutexState>,
+    client_path: String,
+) -> Result<Option<Run>, Error> {
+    Ok(state.history.lock()?.latest_success(&client_path)?)
 }
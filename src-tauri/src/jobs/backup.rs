@@ -1,41 +1,96 @@
-use super::{id_from_backup, Arguments, Error, Kind, Pool, ThreadAction};
+use super::cancel::Cancel;
+use super::history::{self, History};
+use super::scheduler::Priority;
+use super::{hooks, snapshot, transfer};
+use super::{
+    id_for_destination, id_from_backup, panic_message, retry, Active, Arguments, Error, Failed,
+    Kind, Pool, WorkerId,
+};
+use crate::commands;
+use crate::metrics::Metrics;
 use crate::models::app::{self, Config, MutexState};
-use crate::models::backup::{Backup, Location};
-use crate::ssh;
+use crate::models::backup::{Backup, Destination, Location};
+use crate::models::history::{HookRun, HookStage, Run, RunStatus, Transfer, Trigger};
+use crate::models::retry::RetryPolicy;
+use crate::notification;
+use crate::ssh::{self, commands::Copied};
 use chrono::{DateTime, Local};
+use futures::FutureExt;
 use log::{error, info};
 use notify::{self, Event, RecommendedWatcher, RecursiveMode, Watcher};
 use std::collections::HashMap;
 use std::io;
-use std::path::Path;
-use std::process::Command;
-use std::sync::{Arc, MutexGuard};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::mpsc;
+
+/// Delays before a watcher that panicked is started again, it waits for its directory as long
+/// as it takes
+const RESTART_POLICY: RetryPolicy = RetryPolicy {
+    max_attempts: u32::MAX,
+    initial_delay_seconds: 5,
+    max_delay_seconds: 300,
+};
 
 pub struct WatchDirectory {
     backup: Backup,
     config: app::Config,
+    id: String,
+    worker_id: WorkerId,
+    /// Set once the watcher is terminated, which stops the transfer it runs
+    cancel: Cancel,
+    failed_jobs: Arc<Mutex<Failed>>,
+    history: Arc<Mutex<History>>,
+    metrics: Arc<Mutex<Metrics>>,
+    staging_dir: PathBuf,
 }
 
-/// Starts a thread watching a directory for changes and backs up files accordingly.
+/// Watches a directory for changes and backs up files accordingly, until the job is
+/// terminated. Snapshots of its databases are staged in `app_cache_dir`. Waiting for events
+/// doesn't hold a thread, every change is handled as a transfer of the pool.
 ///
 /// # Panics
 /// Panics if the directory does not exist, or if the watcher for some reason could not start successfully.
-pub fn directory_on_change(worker: &Arguments, backup: &Backup, config: Config) {
-    let worker_receiver = worker.receiver.lock().expect("Must have a thread receiver");
+pub async fn directory_on_change(
+    worker: Arguments,
+    backup: Backup,
+    config: Config,
+    failed_jobs: Arc<Mutex<Failed>>,
+    history: Arc<Mutex<History>>,
+    metrics: Arc<Mutex<Metrics>>,
+    app_cache_dir: PathBuf,
+) {
     let path = Path::new(&backup.client_location.path);
-    let (sender, receiver) = std::sync::mpsc::channel();
-    let mut watcher = RecommendedWatcher::new(sender, notify::Config::default())
-        .expect("failed to create watcher");
-    let mut last_modified: DateTime<Local> = Path::new(&backup.client_location.path)
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let mut watcher = RecommendedWatcher::new(
+        move |event: notify::Result<Event>| {
+            if let Err(e) = sender.send(event) {
+                error!("Could not forward notify event: {e:?}");
+            }
+        },
+        notify::Config::default(),
+    )
+    .expect("failed to create watcher");
+    let mut last_modified: DateTime<Local> = path
         .metadata()
         .expect("failed to get metadata")
         .modified()
         .expect("expected last modification time (SystemTime) from metadata")
         .into();
-    let job = WatchDirectory {
+    let id = id_from_backup(&backup, &Kind::BackupOnChange);
+    let job = Arc::new(WatchDirectory {
         backup: backup.clone(),
         config,
-    };
+        staging_dir: snapshot::staging_dir(&app_cache_dir, &id),
+        id,
+        worker_id: worker.id,
+        cancel: worker.cancel.clone(),
+        failed_jobs,
+        history,
+        metrics,
+    });
 
     if let Err(e) = watcher.watch(path.as_ref(), RecursiveMode::Recursive) {
         error!("failed to watch directory: {e:?}");
@@ -45,48 +100,194 @@ pub fn directory_on_change(worker: &Arguments, backup: &Backup, config: Config)
     info!("watching {}", &backup.client_location.path);
 
     loop {
-        let response = worker_receiver.recv();
-        let thread_message = match response {
-            Ok(message) => message,
-            Err(e) => {
-                error!("thread message failed: {e:?}");
-                continue;
-            }
-        };
-
-        match thread_message {
-            ThreadAction::Terminate => {
-                break;
-            }
-            ThreadAction::Start | ThreadAction::Continue => (),
-        }
-
-        let watcher_res = match receiver.recv() {
-            Ok(response) => response,
-            Err(e) => {
-                error!("notify receiver failed: {e:?}");
-                continue;
-            }
+        let watcher_res = tokio::select! {
+            () = worker.cancel.cancelled() => break,
+            event = receiver.recv() => match event {
+                Some(event) => event,
+                None => {
+                    error!("notify channel closed");
+                    break;
+                }
+            },
         };
 
         match watcher_res {
             Ok(event) => {
-                if let Err(e) = handle_notify_event(&event, &job, &mut last_modified) {
-                    handle_notify_error(&e, &event, &job);
+                let watched = Arc::clone(&job);
+                let handled = worker
+                    .transfer(move || {
+                        if let Err(e) = handle_notify_event(&event, &watched, &mut last_modified) {
+                            handle_notify_error(&e, &event, &watched);
+                        }
+                        last_modified
+                    })
+                    .await;
+
+                match handled {
+                    Ok(modified) => last_modified = modified,
+                    // the watcher keeps running, only the run of the event failed
+                    Err(Error::Panicked(message)) => {
+                        let message = format!("Backup panicked: {message}");
+                        error!("{message}");
+                        job.failed_jobs
+                            .lock()
+                            .expect("Could not lock failed jobs")
+                            .insert(job.id.clone(), job.worker_id);
+                        fail_running_runs(
+                            &job.history,
+                            &job.metrics,
+                            &job.config,
+                            &job.id,
+                            &message,
+                        );
+                    }
+                    Err(e) => error!("Could not handle notify event: {e:?}"),
                 }
             }
             Err(e) => error!("notify event failed: {e:?}"),
         };
+    }
+
+    info!("stopped watching {}", &backup.client_location.path);
+}
 
-        match worker.sender.lock() {
-            Ok(sender) => {
-                if let Err(e) = sender.send(ThreadAction::Continue) {
-                    error!("Could not send continue message to thread: {e:?}");
+/// Runs `directory_on_change` until the job is terminated. A watcher that panics is marked as
+/// failed with the message of the panic and removed from `jobs`. It is started again with a
+/// backoff once its directory exists, unless another watcher took over the backup meanwhile.
+#[allow(clippy::too_many_arguments)]
+pub async fn supervise_directory(
+    worker: Arguments,
+    backup: Backup,
+    config: Config,
+    jobs: Arc<Mutex<Active>>,
+    failed_jobs: Arc<Mutex<Failed>>,
+    history: Arc<Mutex<History>>,
+    metrics: Arc<Mutex<Metrics>>,
+    app_cache_dir: PathBuf,
+) {
+    let id = id_from_backup(&backup, &Kind::BackupOnChange);
+    let mut restarts = 0;
+
+    loop {
+        let watcher = directory_on_change(
+            worker.clone(),
+            backup.clone(),
+            config.clone(),
+            Arc::clone(&failed_jobs),
+            Arc::clone(&history),
+            Arc::clone(&metrics),
+            app_cache_dir.clone(),
+        );
+        let message = match AssertUnwindSafe(watcher).catch_unwind().await {
+            Ok(()) => return,
+            Err(payload) => format!("Watcher panicked: {}", panic_message(&*payload)),
+        };
+
+        error!("{message}");
+        if let Ok(mut jobs) = jobs.lock() {
+            // the job may already be replaced by a new watcher
+            if jobs.get(&id) == Some(&worker.id) {
+                jobs.remove(&id);
+            }
+        }
+        failed_jobs
+            .lock()
+            .expect("Could not lock failed jobs")
+            .insert(id.clone(), worker.id);
+        if fail_running_runs(&history, &metrics, &config, &id, &message) == 0 {
+            let run = begin_run(
+                &history,
+                id.clone(),
+                &backup,
+                backup.server_location.path.clone(),
+                Trigger::Watcher,
+            );
+            let run = Run {
+                ended_at: history::now(),
+                status: RunStatus::Failure,
+                error: Some(message),
+                ..run
+            };
+            record_run(&history, &metrics, run, &config);
+        }
+
+        loop {
+            restarts += 1;
+            let delay = RESTART_POLICY.delay(restarts, retry::jitter());
+            info!(
+                "Restarting watcher of {} in {delay:?}",
+                backup.client_location.path
+            );
+
+            if retry::wait(&worker.cancel, delay).await {
+                return;
+            }
+            if Path::new(&backup.client_location.path).exists() {
+                break;
+            }
+        }
+
+        match jobs.lock() {
+            Ok(mut jobs) => {
+                if jobs.get(&id).map_or(false, |other| *other != worker.id) {
+                    info!("{id} is watched by another job, not restarting it");
+                    return;
                 }
+                jobs.insert(id.clone(), worker.id);
+            }
+            Err(e) => {
+                error!("Could not lock jobs: {e:?}");
+                return;
             }
-            Err(e) => error!("Could not lock worker sender: {e:?}"),
+        }
+        failed_jobs
+            .lock()
+            .expect("Could not lock failed jobs")
+            .remove(&id);
+    }
+}
+
+/// Records the runs of `job_id` this process left running as failed with `message`, after the
+/// job panicked. Returns how many there were.
+fn fail_running_runs(
+    history: &Mutex<History>,
+    metrics: &Mutex<Metrics>,
+    config: &Config,
+    job_id: &str,
+    message: &str,
+) -> usize {
+    let runs = match history.lock().map(|history| history.runs()) {
+        Ok(Ok(runs)) => runs,
+        Ok(Err(e)) => {
+            error!("Could not read history: {e:?}");
+            return 0;
+        }
+        Err(e) => {
+            error!("Could not lock history: {e:?}");
+            return 0;
+        }
+    };
+    let running: Vec<Run> = runs
+        .into_iter()
+        .filter(|run| {
+            run.status == RunStatus::Running
+                && run.job_id == job_id
+                && run.pid == Some(process::id())
+        })
+        .collect();
+    let count = running.len();
+
+    for run in running {
+        let run = Run {
+            ended_at: history::now(),
+            status: RunStatus::Failure,
+            error: Some(message.to_string()),
+            ..run
         };
+        record_run(history, metrics, run, config);
     }
+
+    count
 }
 
 fn handle_notify_error(e: &notify::Error, event: &Event, job: &WatchDirectory) {
@@ -111,6 +312,10 @@ fn handle_notify_error(e: &notify::Error, event: &Event, job: &WatchDirectory) {
         }
 
         let relative_path = target_path.replace(&client_folder_path, "");
+        let unescaped_relative_path = path_buf
+            .to_str()
+            .unwrap_or_default()
+            .replace(&job.backup.client_location.path, "");
 
         let file_name = path_buf
             .file_name()
@@ -120,29 +325,50 @@ fn handle_notify_error(e: &notify::Error, event: &Event, job: &WatchDirectory) {
             .to_string()
             .replace(' ', r"\ ");
 
-        let backup_realtive_to_root = Backup {
-            client_location: Location {
-                entity_name: file_name.clone(),
-                path: target_path,
-            },
-            server_location: Location {
-                entity_name: file_name,
-                path: format!(
-                    "{}/{}/{}{relative_path}",
-                    server_folder_path,
-                    job.config.client_name,
-                    job.backup.client_location.entity_name
-                ),
-            },
-            latest_run: None,
-            options: job.backup.options.clone(),
-        };
+        for destination in job.backup.destinations() {
+            let result = match &destination {
+                Destination::Server(location) => {
+                    let backup_realtive_to_root = Backup {
+                        client_location: Location {
+                            entity_name: file_name.clone(),
+                            path: target_path.clone(),
+                        },
+                        server_location: Location {
+                            entity_name: file_name.clone(),
+                            path: format!(
+                                "{}/{}/{}{relative_path}",
+                                location.path.replace(' ', r"\ "),
+                                job.config.client_name,
+                                job.backup.client_location.entity_name
+                            ),
+                        },
+                        latest_run: None,
+                        options: job.backup.options.clone(),
+                        mirrors: None,
+                        hooks: None,
+                        databases: None,
+                    };
 
-        info!("Deleting {}", backup_realtive_to_root.server_location.path);
-        let result = ssh::commands::delete_from_server(&backup_realtive_to_root, &job.config);
+                    info!("Deleting {}", backup_realtive_to_root.server_location.path);
+                    ssh::commands::delete_from_server(&backup_realtive_to_root, &job.config)
+                        .map_err(Error::from)
+                }
+                Destination::Disk(location) => {
+                    let path = format!(
+                        "{}/{}/{}{unescaped_relative_path}",
+                        location.path,
+                        job.config.client_name,
+                        job.backup.client_location.entity_name
+                    );
 
-        if let Err(e) = result {
-            error!("Could not delete_from_server: {e:?}");
+                    info!("Deleting {path}");
+                    commands::os::delete_from_disk(&path).map_err(Error::from)
+                }
+            };
+
+            if let Err(e) = result {
+                error!("Could not delete from {destination}: {e:?}");
+            }
         }
     }
 }
@@ -199,59 +425,432 @@ fn exec_backup_command(
         .unwrap_or_default()
         .replace(&job.backup.client_location.path, "");
 
-    let server_location_path_without_client_directory = format!(
-        "{}/{}{relative_path}",
-        job.backup.server_location.path, job.config.client_name,
+    let is_directory = if data.is_dir() {
+        info!("Backup directory: {path:?}");
+        true
+    } else {
+        info!("Backup file: {path:?}");
+        false
+    };
+
+    let use_client_directory = job
+        .backup
+        .options
+        .as_ref()
+        .map_or(false, |options| options.use_client_directory);
+
+    *latest_modified = entity_modified_date;
+
+    let run = begin_run(
+        &job.history,
+        job.id.clone(),
+        &job.backup,
+        job.backup.server_location.path.clone(),
+        Trigger::Watcher,
     );
+    let mut transfer = Transfer::default();
+    let mut errors = vec![];
+    let mut warnings = vec![];
+
+    // a database is uploaded from a snapshot, also when only one of its journals changed
+    let (source, relative_path, is_directory) = match snapshot::database_for(&job.backup, path) {
+        Some(database) => (
+            snapshot::database(&job.backup, &database, &job.staging_dir),
+            format!("/{database}"),
+            false,
+        ),
+        None => (Ok(path.clone()), relative_path, is_directory),
+    };
+    let source = source.unwrap_or_else(|e| {
+        error!("Could not snapshot database: {e:?}");
+        errors.push(format!("{e:?}"));
+        PathBuf::new()
+    });
+    // the databases inside a changed directory are left out by rsync and uploaded from snapshots
+    let databases = if is_directory {
+        snapshot::databases_in(&job.backup, &source)
+    } else {
+        vec![]
+    };
+    let backup_realtive_to_root = Backup {
+        client_location: Location {
+            path: source.to_str().unwrap_or_default().to_string(),
+            entity_name: source
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+        },
+        server_location: job.backup.server_location.clone(),
+        latest_run: None,
+        options: job.backup.options.clone(),
+        mirrors: None,
+        hooks: None,
+        databases: if databases.is_empty() {
+            None
+        } else {
+            Some(databases)
+        },
+    };
+    let snapshots = if errors.is_empty() {
+        take_snapshots(&backup_realtive_to_root, &job.staging_dir, &mut errors)
+    } else {
+        vec![]
+    };
+    // a failed snapshot leaves errors behind, the change is not backed up then
+    let destinations = if errors.is_empty() {
+        job.backup.destinations()
+    } else {
+        vec![]
+    };
 
-    let server_location_path = if let Some(option) = &job.backup.options {
-        if option.use_client_directory {
+    // every destination is attempted, so an unavailable disk does not stop the server backup
+    for destination in destinations {
+        if job.cancel.is_cancelled() {
+            break;
+        }
+
+        let destination_path = if use_client_directory {
             format!(
                 "{}/{}/{}{relative_path}",
-                job.backup.server_location.path,
+                destination.location().path,
                 job.config.client_name,
                 job.backup.client_location.entity_name
             )
         } else {
-            server_location_path_without_client_directory
+            format!(
+                "{}/{}{relative_path}",
+                destination.location().path,
+                job.config.client_name,
+            )
+        };
+
+        info!("Destination location: {destination_path}");
+
+        let destination_job_id = id_for_destination(&job.id, &destination);
+        let result = if destination_is_available(&destination) {
+            // terminating the watcher, e.g. on a reload, stops the transfer as well
+            with_snapshots(
+                &backup_realtive_to_root,
+                &destination.with_path(destination_path),
+                &snapshots,
+                &job.config,
+                is_directory,
+                &job.cancel,
+            )
+        } else {
+            Err(Error::NotFound(format!("{destination} is not available")))
+        };
+
+        match job.failed_jobs.lock() {
+            Ok(mut failed_jobs) => match result {
+                Ok(copied) => {
+                    transfer += copied.transfer;
+                    warnings.extend(
+                        copied
+                            .warnings
+                            .iter()
+                            .map(|warning| format!("{destination}: {warning}")),
+                    );
+                    failed_jobs.remove(&destination_job_id);
+                }
+                // the transfer was stopped, which is not a failure of the destination
+                Err(_) if job.cancel.is_cancelled() => {
+                    info!("Cancelled backup to {destination}");
+                    break;
+                }
+                Err(e) => {
+                    error!("Could not backup to {destination}: {e:?}");
+                    errors.push(format!("{destination}: {e:?}"));
+                    failed_jobs.insert(destination_job_id, job.worker_id);
+                }
+            },
+            Err(e) => error!("Could not lock failed jobs: {e:?}"),
+        }
+    }
+    snapshot::clean(&job.staging_dir);
+
+    let (status, error) = run_outcome(&job.cancel, &errors, &warnings);
+    let run = Run {
+        ended_at: history::now(),
+        transfer,
+        status,
+        error,
+        ..run
+    };
+    record_run(&job.history, &job.metrics, run, &job.config);
+
+    Ok(())
+}
+
+/// Runs the pre hook of `backup` before `run`. A failure skips the backup as an error, or only
+/// makes the run partial as a warning if the hooks continue on it.
+pub fn run_pre_hook(
+    backup: &Backup,
+    run: &Run,
+    client_name: &str,
+    errors: &mut Vec<String>,
+    warnings: &mut Vec<String>,
+) -> Option<HookRun> {
+    let hooks = backup.hooks.as_ref()?;
+    let hook_run = hooks::run(
+        hooks.pre.as_ref()?,
+        HookStage::Pre,
+        &hooks::environment(run, client_name),
+    );
+
+    if !hook_run.succeeded() {
+        if hooks.abort_on_pre_failure {
+            errors.push(hooks::failure(&hook_run));
+        } else {
+            warnings.push(hooks::failure(&hook_run));
         }
+    }
+
+    Some(hook_run)
+}
+
+/// Runs the post hook of `backup` once `run` ended, whatever its status.
+fn run_post_hook(backup: &Backup, run: &Run, client_name: &str) -> Option<HookRun> {
+    let hook = backup
+        .hooks
+        .as_ref()
+        .and_then(|hooks| hooks.post.as_ref())?;
+
+    Some(hooks::run(
+        hook,
+        HookStage::Post,
+        &hooks::environment(run, client_name),
+    ))
+}
+
+/// Status and error message of a run from the errors of its destinations and the warnings about
+/// files they skipped.
+fn outcome(errors: &[String], warnings: &[String]) -> (RunStatus, Option<String>) {
+    if !errors.is_empty() {
+        let messages: Vec<String> = errors.iter().chain(warnings).cloned().collect();
+        (RunStatus::Failure, Some(messages.join("\n")))
+    } else if !warnings.is_empty() {
+        (RunStatus::Partial, Some(warnings.join("\n")))
+    } else {
+        (RunStatus::Success, None)
+    }
+}
+
+/// Like `outcome`, but a cancelled run stays cancelled, whatever failed while it was stopped.
+fn run_outcome(
+    cancel: &Cancel,
+    errors: &[String],
+    warnings: &[String],
+) -> (RunStatus, Option<String>) {
+    if cancel.is_cancelled() {
+        (RunStatus::Cancelled, Some(String::from("Cancelled")))
     } else {
-        server_location_path_without_client_directory
+        outcome(errors, warnings)
+    }
+}
+
+/// Creates a run and stores it in the history as running, so it is detected as interrupted if
+/// the process exits before `record_run` is called.
+fn begin_run(
+    history: &Mutex<History>,
+    job_id: String,
+    backup: &Backup,
+    server_path: String,
+    trigger: Trigger,
+) -> Run {
+    let started_at = history::now();
+    let run = Run {
+        job_id,
+        client_path: backup.client_location.path.clone(),
+        server_path,
+        trigger,
+        started_at,
+        ended_at: started_at,
+        transfer: Transfer::default(),
+        status: RunStatus::Running,
+        error: None,
+        pid: Some(process::id()),
+        hooks: None,
     };
 
-    let backup_realtive_to_root = Backup {
-        client_location: Location {
-            path: path.to_str().unwrap_or_default().to_string(),
-            entity_name: job.backup.client_location.entity_name.clone(),
-        },
-        server_location: Location {
-            path: server_location_path,
-            entity_name: job.backup.server_location.entity_name.clone(),
-        },
-        latest_run: None,
-        options: job.backup.options.clone(),
+    match history.lock() {
+        Ok(history) => {
+            if let Err(e) = history.begin(&run) {
+                error!("Could not record run in history: {e:?}");
+            }
+        }
+        Err(e) => error!("Could not lock history: {e:?}"),
+    }
+
+    run
+}
+
+/// Stores a run in the history, counts it in the metrics and notifies about failures and
+/// recoveries it shows. A history that can't be written must not fail the backup itself, so
+/// errors are only logged.
+fn record_run(history: &Mutex<History>, metrics: &Mutex<Metrics>, run: Run, config: &Config) {
+    match metrics.lock() {
+        Ok(mut metrics) => metrics.observe(&run),
+        Err(e) => error!("Could not lock metrics: {e:?}"),
+    }
+
+    let history = match history.lock() {
+        Ok(history) => history,
+        Err(e) => {
+            error!("Could not lock history: {e:?}");
+            return;
+        }
     };
 
-    let is_directory = if data.is_dir() {
-        info!("Backup directory: {path:?}");
-        true
+    let event = config
+        .notifications
+        .as_ref()
+        .and_then(|notifications| match history.runs() {
+            Ok(previous) => notification::event_for(
+                &run,
+                &previous,
+                notifications.failing_threshold(),
+                notifications.completions,
+            ),
+            Err(e) => {
+                error!("Could not read history for notifications: {e:?}");
+                None
+            }
+        });
+
+    if let Err(e) = history.record(run) {
+        error!("Could not record run in history: {e:?}");
+    }
+
+    if let (Some(notifications), Some(event)) = (&config.notifications, event) {
+        notification::dispatch(notifications, event);
+    }
+}
+
+/// Backs up `backup` to a single destination. The server location of `backup` is replaced by
+/// the location of the destination. Files that change during the transfer are sent again, until
+/// `cancel` is set.
+pub fn to_destination(
+    backup: &Backup,
+    destination: &Destination,
+    config: &Config,
+    is_directory: bool,
+    cancel: &Cancel,
+) -> Result<Copied, Error> {
+    let backup_to_destination = Backup {
+        server_location: destination.location().clone(),
+        mirrors: None,
+        ..backup.clone()
+    };
+    // databases are uploaded from snapshots, changes to them don't matter
+    let skip = |path: &Path| snapshot::database_for(backup, path).is_some();
+    // rsync lists the files it sent relative to where the copy starts
+    let client_path = Path::new(&backup.client_location.path);
+    let root = if backup.client_source(is_directory).ends_with('/') {
+        client_path
     } else {
-        info!("Backup file: {path:?}");
-        false
+        client_path.parent().unwrap_or(client_path)
     };
-    info!(
-        "Server location: {}",
-        backup_realtive_to_root.server_location.path
-    );
 
-    *latest_modified = entity_modified_date;
-    if let Err(e) =
-        ssh::commands::backup_to_server(&backup_realtive_to_root, &job.config, is_directory)
-    {
-        error!("Could not backup: {e:?}");
+    transfer::with_retries(root, &skip, || match destination {
+        Destination::Server(_) => Ok(ssh::commands::backup_to_server(
+            &backup_to_destination,
+            config,
+            is_directory,
+            cancel,
+        )?),
+        Destination::Disk(_) => Ok(commands::os::backup_to_disk(
+            &backup_to_destination,
+            is_directory,
+            cancel,
+        )?),
+    })
+}
+
+/// Takes a snapshot of every database of `backup` in `staging_dir`. Databases that could not be
+/// snapshotted are added to `errors`.
+fn take_snapshots(
+    backup: &Backup,
+    staging_dir: &Path,
+    errors: &mut Vec<String>,
+) -> Vec<(String, PathBuf)> {
+    let mut snapshots = vec![];
+
+    for database in backup.databases.iter().flatten() {
+        match snapshot::database(backup, database, staging_dir) {
+            Ok(snapshot) => snapshots.push((database.clone(), snapshot)),
+            Err(e) => {
+                error!("Could not snapshot {database}: {e:?}");
+                errors.push(format!("{database}: {e:?}"));
+            }
+        }
     }
 
-    Ok(())
+    snapshots
+}
+
+/// Backs up `backup` to `destination`, then uploads the `snapshots` of the databases rsync left
+/// out next to the other files.
+fn with_snapshots(
+    backup: &Backup,
+    destination: &Destination,
+    snapshots: &[(String, PathBuf)],
+    config: &Config,
+    is_directory: bool,
+    cancel: &Cancel,
+) -> Result<Copied, Error> {
+    let mut copied = to_destination(backup, destination, config, is_directory, cancel)?;
+    // rsync copies the client directory itself unless its content is backed up
+    let root = if backup.client_source(is_directory).ends_with('/') {
+        String::new()
+    } else {
+        format!("/{}", backup.client_location.entity_name)
+    };
+
+    for (database, snapshot) in snapshots {
+        let path = format!("{}{root}/{database}", destination.location().path);
+        copied += snapshot_to_destination(
+            backup,
+            snapshot,
+            &destination.with_path(path),
+            config,
+            cancel,
+        )?;
+    }
+
+    Ok(copied)
+}
+
+/// Uploads the snapshot of a database of `backup` to `destination`, which is the path of the
+/// database in the destination.
+fn snapshot_to_destination(
+    backup: &Backup,
+    snapshot: &Path,
+    destination: &Destination,
+    config: &Config,
+    cancel: &Cancel,
+) -> Result<Copied, Error> {
+    let snapshot_backup = Backup {
+        client_location: Location {
+            path: snapshot.to_string_lossy().to_string(),
+            entity_name: backup.client_location.entity_name.clone(),
+        },
+        databases: None,
+        ..backup.clone()
+    };
+
+    to_destination(&snapshot_backup, destination, config, false, cancel)
+}
+
+/// A disk destination is only available while its root folder exists, which prevents writing
+/// into the mount point of an external drive that is not connected.
+#[must_use]
+pub fn destination_is_available(destination: &Destination) -> bool {
+    match destination {
+        Destination::Server(_) => true,
+        Destination::Disk(location) => Path::new(&location.path).is_dir(),
+    }
 }
 
 fn handle_notify_event(
@@ -273,60 +872,38 @@ fn handle_notify_event(
 #[allow(clippy::needless_pass_by_value)]
 pub fn terminate_all<S: ::std::hash::BuildHasher>(
     jobs: &mut MutexGuard<HashMap<String, usize, S>>,
-    pool: &mut MutexGuard<Pool>,
+    pool: &MutexGuard<Pool>,
 ) {
     jobs.iter_mut().for_each(|(job_id, worker)| {
         info!("Terminating job: {job_id}");
-        let client_folder_path = job_id.split('_').next().expect("Could not split job_id");
-
-        if let Err(why) = pool.terminate_job(*worker, || {
-            let file_path = format!("{client_folder_path}/.bmu_event_trigger");
-
-            match Command::new("touch").args([&file_path]).output() {
-                Ok(output) => {
-                    if !output.status.success() {
-                        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-                        let why = format!("Failed to create file: {stdout}\n{stderr}");
-                        error!("{why}");
-                    }
-                }
-                Err(e) => {
-                    error!("Failed to create file: {e:?}");
-                }
-            }
-
-            match Command::new("rm").args([&file_path]).output() {
-                Ok(output) => {
-                    if !output.status.success() {
-                        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-                        let why = format!("Failed to remove file: {stdout}\n{stderr}");
-                        error!("{why}");
-                    }
-                }
-                Err(e) => {
-                    error!("Failed to remove file: {e:?}");
-                }
-            }
-        }) {
+        if let Err(why) = pool.terminate_job(*worker) {
             error!("Could not terminate job: {why}");
         };
     });
 
+    // watchers that panicked and wait to be restarted are only in the pool
+    for (job_id, worker) in pool.jobs() {
+        if jobs.values().any(|active| *active == worker) {
+            continue;
+        }
+        info!("Terminating job: {job_id}");
+        if let Err(why) = pool.terminate_job(worker) {
+            error!("Could not terminate job: {why}");
+        };
+    }
+
     jobs.clear();
 }
 
-pub async fn entity_to_server(mut backup: Backup, state: Arc<&MutexState>) -> Result<String, Error> {
+pub async fn entity_to_server(
+    mut backup: Backup,
+    state: Arc<&MutexState>,
+) -> Result<String, Error> {
     let config_mutex = state.config.lock()?.clone();
     let config = match config_mutex {
         Some(config) => config.clone(),
         None => return Err(Error::App(app::Error::Config(String::from("No config")))),
     };
-    let folder_to_assert = format!(
-        "./{}/{}",
-        backup.server_location.entity_name, config.client_name
-    );
 
     let connection = state.connection.lock().await;
     let connection_ref = connection.as_ref();
@@ -339,40 +916,201 @@ pub async fn entity_to_server(mut backup: Backup, state: Arc<&MutexState>) -> Re
         }
     };
 
-    let path = Path::new(&folder_to_assert);
-    ssh::commands::assert_client_directory_on_server(&client, path).await?;
+    for destination in backup.destinations() {
+        if let Destination::Server(location) = destination {
+            let folder_to_assert = format!("./{}/{}", location.entity_name, config.client_name);
+            let path = Path::new(&folder_to_assert);
+            ssh::commands::assert_client_directory_on_server(&client, path).await?;
+        }
+    }
 
     let mut pool = state.pool.lock()?;
     let jobs = Arc::clone(&state.jobs);
     let failed_jobs = Arc::clone(&state.failed_jobs);
+    let history = Arc::clone(&state.history);
+    let metrics = Arc::clone(&state.metrics);
+    let server_path = backup.server_location.path.clone();
 
-    // prepend client_name as a root folder on the server for the backup
+    // prepend client_name as a root folder on each destination for the backup, availability is
+    // checked against the root given by the user before every attempt
+    let destinations: Vec<(Destination, Destination)> = backup
+        .destinations()
+        .into_iter()
+        .map(|destination| {
+            let destination_for_client = destination.with_path(format!(
+                "{}/{}",
+                destination.location().path,
+                config.client_name
+            ));
+            (destination_for_client, destination)
+        })
+        .collect();
     backup.server_location.path = format!("{}/{}", backup.server_location.path, config.client_name);
     let job_id_for_client = id_from_backup(&backup, &Kind::Backup);
-    if failed_jobs.lock()?.contains_key(&job_id_for_client) {
-        failed_jobs.lock()?.remove(&job_id_for_client);
-    }
+    let staging_dir = snapshot::staging_dir(&state.app_cache_dir.lock()?, &job_id_for_client);
+    let policy = config.retry.clone().unwrap_or_default();
+    pool.set_max_transfers(config.max_transfers);
+    let clear_failures = {
+        let failed_jobs = Arc::clone(&failed_jobs);
+        let ids: Vec<String> = destinations
+            .iter()
+            .map(|(destination, _)| id_for_destination(&job_id_for_client, destination))
+            .chain([job_id_for_client.clone()])
+            .collect();
+
+        move || {
+            let mut failed_jobs = failed_jobs.lock().expect("Could not lock failed jobs");
+            for id in &ids {
+                failed_jobs.remove(id);
+            }
+        }
+    };
 
-    pool.execute(move |worker| {
+    clear_failures();
+
+    // a panic must not leave the backup running in the active jobs or the history
+    let clean_up_panic = {
+        let jobs = Arc::clone(&jobs);
+        let failed_jobs = Arc::clone(&failed_jobs);
+        let history = Arc::clone(&history);
+        let metrics = Arc::clone(&metrics);
+        let config = config.clone();
+
+        move |worker: &Arguments, message: &str| {
+            jobs.lock()
+                .expect("Could not lock jobs")
+                .remove(&worker.job_id);
+            failed_jobs
+                .lock()
+                .expect("Could not lock failed jobs")
+                .insert(worker.job_id.clone(), worker.id);
+            fail_running_runs(&history, &metrics, &config, &worker.job_id, message);
+        }
+    };
+    let attempt = move |worker: &Arguments| -> Result<(), String> {
+        let cancel = &worker.cancel;
         let job_id = id_from_backup(&backup, &Kind::Backup);
+        // failures of a previous attempt are outdated
+        clear_failures();
         jobs.lock()
             .expect("Could not lock jobs")
             .insert(job_id.clone(), worker.id);
 
-        match ssh::commands::backup_to_server(&backup, &config, true) {
-            Ok(_) => {
-                jobs.lock().expect("Could not lock jobs").remove(&job_id);
-            }
-            Err(e) => {
-                error!("{e:?}");
-                jobs.lock().expect("Could not lock jobs").remove(&job_id);
-                failed_jobs
-                    .lock()
-                    .expect("Could not lock failed jobs")
-                    .insert(job_id, worker.id);
+        let run = begin_run(
+            &history,
+            job_id.clone(),
+            &backup,
+            server_path.clone(),
+            Trigger::Manual,
+        );
+        let mut transfer = Transfer::default();
+        let mut errors = vec![];
+        let mut warnings = vec![];
+        let mut hook_runs = vec![];
+
+        hook_runs.extend(run_pre_hook(
+            &backup,
+            &run,
+            &config.client_name,
+            &mut errors,
+            &mut warnings,
+        ));
+
+        // a failed pre hook leaves errors behind, the backup is skipped then
+        if errors.is_empty() && !cancel.is_cancelled() {
+            let snapshots = take_snapshots(&backup, &staging_dir, &mut errors);
+
+            // one failing destination must not block the others
+            for (destination, root_destination) in &destinations {
+                if cancel.is_cancelled() {
+                    break;
+                }
+
+                let result = if destination_is_available(root_destination) {
+                    with_snapshots(&backup, destination, &snapshots, &config, true, cancel)
+                } else {
+                    Err(Error::NotFound(format!("{destination} is not available")))
+                };
+
+                match result {
+                    Ok(copied) => {
+                        transfer += copied.transfer;
+                        warnings.extend(
+                            copied
+                                .warnings
+                                .iter()
+                                .map(|warning| format!("{destination}: {warning}")),
+                        );
+                    }
+                    // the transfer was stopped, which is not a failure of the destination
+                    Err(_) if cancel.is_cancelled() => {
+                        info!("Cancelled backup to {destination}");
+                        break;
+                    }
+                    Err(e) => {
+                        error!("Could not backup to {destination}: {e:?}");
+                        errors.push(format!("{destination}: {e:?}"));
+                        failed_jobs
+                            .lock()
+                            .expect("Could not lock failed jobs")
+                            .insert(id_for_destination(&job_id, destination), worker.id);
+                    }
+                }
             }
+            snapshot::clean(&staging_dir);
+        }
+
+        let (status, error) = run_outcome(cancel, &errors, &warnings);
+        let mut run = Run {
+            ended_at: history::now(),
+            transfer,
+            status,
+            error,
+            ..run
         };
-    })?;
+
+        // the post hook also runs after a cancelled backup, it may undo what the pre hook did
+        if let Some(hook_run) = run_post_hook(&backup, &run, &config.client_name) {
+            if !hook_run.succeeded() {
+                errors.push(hooks::failure(&hook_run));
+                (run.status, run.error) = run_outcome(cancel, &errors, &warnings);
+            }
+            hook_runs.push(hook_run);
+            run.ended_at = history::now();
+        }
+
+        let (status, error) = (run.status, run.error.clone());
+        if !hook_runs.is_empty() {
+            run.hooks = Some(hook_runs);
+        }
+        record_run(&history, &metrics, run, &config);
+
+        jobs.lock().expect("Could not lock jobs").remove(&job_id);
+
+        if status == RunStatus::Failure {
+            failed_jobs
+                .lock()
+                .expect("Could not lock failed jobs")
+                .insert(job_id, worker.id);
+            return Err(error.unwrap_or_default());
+        }
+
+        Ok(())
+    };
+
+    pool.execute_with_retries(
+        job_id_for_client.clone(),
+        Priority::Manual,
+        policy,
+        move |worker| {
+            panic::catch_unwind(AssertUnwindSafe(|| attempt(worker))).map_err(|payload| {
+                let message = format!("Backup panicked: {}", panic_message(&*payload));
+                error!("{message}");
+                clean_up_panic(worker, &message);
+                message
+            })?
+        },
+    )?;
 
     Ok(job_id_for_client)
 }
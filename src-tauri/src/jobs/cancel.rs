@@ -0,0 +1,155 @@
+use log::{error, info};
+use std::io::{self, Read};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Time a cancelled command gets to clean up after SIGTERM before it is killed
+const GRACE_PERIOD: Duration = Duration::from_secs(10);
+/// Time to wait for the output once the command has exited, since processes it started may keep
+/// the pipes open
+const OUTPUT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Shared flag a running job checks between its steps, and that stops the command it runs.
+/// Clones share the flag, tasks can wait for it with `cancelled`.
+#[derive(Clone, Default, Debug)]
+pub struct Cancel {
+    cancelled: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl Cancel {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Completes once the token is cancelled.
+    pub async fn cancelled(&self) {
+        // a waiter created before the flag is checked can't miss the notification
+        let notified = self.notify.notified();
+        if self.is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+}
+
+/// Runs `command` like `Command::output`, but terminates it once `cancel` is set. The command
+/// gets SIGTERM first, which lets rsync remove its temporary files, and is killed if it is still
+/// running after a grace period. A cancelled command fails with `ErrorKind::Interrupted`.
+pub fn output(command: &mut Command, cancel: &Cancel) -> io::Result<Output> {
+    if cancel.is_cancelled() {
+        return Err(cancelled());
+    }
+
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // a full pipe would block the command, so the output is read while it runs
+    let (sender, receiver) = mpsc::channel();
+    let pipes: [Option<Box<dyn Read + Send>>; 2] = [
+        child
+            .stdout
+            .take()
+            .map(|pipe| Box::new(pipe) as Box<dyn Read + Send>),
+        child
+            .stderr
+            .take()
+            .map(|pipe| Box::new(pipe) as Box<dyn Read + Send>),
+    ];
+    let mut pipe_count = 0;
+
+    for (index, pipe) in pipes.into_iter().enumerate() {
+        if let Some(mut pipe) = pipe {
+            let sender = sender.clone();
+            pipe_count += 1;
+            thread::spawn(move || {
+                let mut output = vec![];
+                _ = pipe.read_to_end(&mut output);
+                _ = sender.send((index, output));
+            });
+        }
+    }
+
+    let mut terminated_at: Option<Instant> = None;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+
+        match terminated_at {
+            None if cancel.is_cancelled() => {
+                info!("Terminating cancelled command {}", child.id());
+                if let Err(e) = terminate(&child) {
+                    error!(
+                        "Could not terminate command {}, killing it: {e:?}",
+                        child.id()
+                    );
+                    child.kill()?;
+                    child.wait()?;
+                    return Err(cancelled());
+                }
+                terminated_at = Some(Instant::now());
+            }
+            Some(at) if at.elapsed() >= GRACE_PERIOD => {
+                error!("Cancelled command {} did not exit, killing it", child.id());
+                _ = child.kill();
+                _ = child.wait();
+                return Err(cancelled());
+            }
+            _ => (),
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    if terminated_at.is_some() {
+        return Err(cancelled());
+    }
+
+    let mut output = Output {
+        status,
+        stdout: vec![],
+        stderr: vec![],
+    };
+    for _ in 0..pipe_count {
+        match receiver.recv_timeout(OUTPUT_TIMEOUT) {
+            Ok((0, part)) => output.stdout = part,
+            Ok((_, part)) => output.stderr = part,
+            Err(_) => break,
+        }
+    }
+
+    Ok(output)
+}
+
+/// Sends SIGTERM to `child`. The remote rsync drops its temporary file once the connection
+/// closes, files it already renamed into place are complete.
+fn terminate(child: &Child) -> io::Result<()> {
+    let pid = libc::pid_t::try_from(child.id())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    // SAFETY: kill only sends a signal, the child is not reaped before it has exited
+    if unsafe { libc::kill(pid, libc::SIGTERM) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+fn cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "cancelled")
+}
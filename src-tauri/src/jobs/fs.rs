@@ -1,6 +1,19 @@
-use std::fs;
-use glob::{glob, PatternError};
 use super::Error;
+use glob::{glob, PatternError};
+use log::warn;
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How long to wait for another process to release a lock
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Age after which a lock is left over from a process that died while holding it
+const STALE_LOCK: Duration = Duration::from_secs(60);
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(20);
 
 impl From<PatternError> for Error {
     fn from(e: PatternError) -> Self {
@@ -26,3 +39,75 @@ pub fn cleanup_entities_by_pattern(pattern: &str) -> Result<(), Error> {
     log::info!("cleanup successfull!");
     Ok(())
 }
+
+/// An advisory lock on a file shared between the app, the CLI and the daemon, held as long as
+/// the guard lives. The lock is a `.lock` file next to it, created exclusively.
+pub struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    /// Waits until no other process holds the lock of `path` and takes it.
+    pub fn acquire(path: &Path) -> io::Result<Self> {
+        let lock_path = with_suffix(path, ".lock");
+        let started = SystemTime::now();
+
+        loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&lock_path)
+            {
+                Ok(_) => return Ok(Self { path: lock_path }),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    let age = fs::metadata(&lock_path)
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok());
+                    if age.map_or(false, |age| age > STALE_LOCK) {
+                        warn!("Removing stale lock {}", lock_path.display());
+                        _ = fs::remove_file(&lock_path);
+                        continue;
+                    }
+                    if started
+                        .elapsed()
+                        .map_or(false, |waited| waited > LOCK_TIMEOUT)
+                    {
+                        return Err(io::Error::new(
+                            ErrorKind::TimedOut,
+                            format!("{} is locked by another process", path.display()),
+                        ));
+                    }
+                    thread::sleep(LOCK_POLL_INTERVAL);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Could not remove lock {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Replaces the file at `path` with `contents` at once, so that readers never see it half
+/// written.
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let temporary = with_suffix(path, &format!(".{}.tmp", process::id()));
+    fs::write(&temporary, contents)?;
+
+    fs::rename(&temporary, path).map_err(|e| {
+        _ = fs::remove_file(&temporary);
+        e
+    })
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
@@ -0,0 +1,156 @@
+use super::fs::{write_atomic, FileLock};
+use super::Error;
+use crate::models::history::{Run, RunStatus};
+use log::warn;
+use std::fs;
+use std::path::PathBuf;
+
+pub const FILE_NAME: &str = "history.json";
+const MAX_RUNS: usize = 1000;
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Storage(e.to_string())
+    }
+}
+
+/// Persistent history of backup runs, stored as json next to `backups.json` in the app data
+/// directory. The file is read on every access, so that the app and the daemon can share it.
+/// Changes hold a lock on the file and replace it at once.
+#[derive(Default)]
+pub struct History {
+    data_dir: Option<PathBuf>,
+}
+
+/// Current unix timestamp in seconds.
+#[must_use]
+pub fn now() -> u64 {
+    u64::try_from(chrono::Utc::now().timestamp()).unwrap_or_default()
+}
+
+impl History {
+    #[must_use]
+    pub const fn new(data_dir: PathBuf) -> Self {
+        Self {
+            data_dir: Some(data_dir),
+        }
+    }
+
+    fn file_path(&self) -> Option<PathBuf> {
+        self.data_dir.as_ref().map(|dir| dir.join(FILE_NAME))
+    }
+
+    /// All stored runs, oldest first.
+    pub fn runs(&self) -> Result<Vec<Run>, Error> {
+        let path = match self.file_path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(vec![]),
+        };
+
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Runs for the backup of `client_path`, or every backup if none is given, newest first.
+    pub fn query(
+        &self,
+        client_path: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<Run>, Error> {
+        let runs = self
+            .runs()?
+            .into_iter()
+            .rev()
+            .filter(|run| client_path.map_or(true, |path| run.client_path == path))
+            .take(limit.unwrap_or(usize::MAX))
+            .collect();
+
+        Ok(runs)
+    }
+
+    /// The latest successful run for the backup of `client_path`.
+    pub fn latest_success(&self, client_path: &str) -> Result<Option<Run>, Error> {
+        self.latest(client_path, RunStatus::Success)
+    }
+
+    /// The latest failed run for the backup of `client_path`.
+    pub fn latest_failure(&self, client_path: &str) -> Result<Option<Run>, Error> {
+        self.latest(client_path, RunStatus::Failure)
+    }
+
+    fn latest(&self, client_path: &str, status: RunStatus) -> Result<Option<Run>, Error> {
+        Ok(self
+            .runs()?
+            .into_iter()
+            .rev()
+            .find(|run| run.client_path == client_path && run.status == status))
+    }
+
+    /// Stores a run as `Running`, to be replaced by `record` once it has finished.
+    pub fn begin(&self, run: &Run) -> Result<(), Error> {
+        self.record(Run {
+            status: RunStatus::Running,
+            ..run.clone()
+        })
+    }
+
+    /// Marks every run as `Interrupted` that is still `Running`, but whose process is gone
+    /// according to `is_running`, and returns them. `is_running` gets the pid and the start of
+    /// a run, so that a process reusing the pid can be told apart.
+    pub fn interrupted(&self, is_running: impl Fn(u32, u64) -> bool) -> Result<Vec<Run>, Error> {
+        let path = match self.file_path() {
+            Some(path) => path,
+            None => return Ok(vec![]),
+        };
+        let _lock = FileLock::acquire(&path)?;
+        let mut runs = self.runs()?;
+        let mut interrupted = vec![];
+
+        for run in runs.iter_mut().filter(|run| {
+            run.status == RunStatus::Running
+                && !run.pid.map_or(false, |pid| is_running(pid, run.started_at))
+        }) {
+            run.status = RunStatus::Interrupted;
+            interrupted.push(run.clone());
+        }
+
+        if !interrupted.is_empty() {
+            write_atomic(&path, &serde_json::to_string(&runs)?)?;
+        }
+
+        Ok(interrupted)
+    }
+
+    /// Stores a run, replacing the `Running` entry written by `begin`. The latest run of a backup
+    /// is only kept here, `backups.json` is not written, since the daemon reloads its watchers
+    /// whenever that file changes.
+    pub fn record(&self, run: Run) -> Result<(), Error> {
+        let path = match self.file_path() {
+            Some(path) => path,
+            None => {
+                warn!(
+                    "No data directory for the history, run of {} is not stored",
+                    run.job_id
+                );
+                return Ok(());
+            }
+        };
+
+        let _lock = FileLock::acquire(&path)?;
+        let mut runs = self.runs()?;
+        runs.retain(|stored| {
+            !(stored.status == RunStatus::Running
+                && stored.job_id == run.job_id
+                && stored.started_at == run.started_at
+                && stored.pid == run.pid)
+        });
+        runs.push(run);
+
+        if runs.len() > MAX_RUNS {
+            runs.drain(..runs.len() - MAX_RUNS);
+        }
+
+        write_atomic(&path, &serde_json::to_string(&runs)?)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+use crate::models::backup::Hook;
+use crate::models::history::{HookRun, HookStage, Run};
+use log::{error, info};
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Time to wait for the output once the command has exited, since processes it started in the
+/// background may keep the pipes open
+const OUTPUT_TIMEOUT: Duration = Duration::from_secs(1);
+/// Bytes of output kept in the history
+const MAX_OUTPUT: usize = 4096;
+
+/// Variables describing the backup and its run, added to the environment of a hook.
+#[must_use]
+pub fn environment(run: &Run, client_name: &str) -> Vec<(String, String)> {
+    [
+        ("BMU_JOB_ID", run.job_id.clone()),
+        ("BMU_CLIENT_NAME", client_name.to_string()),
+        ("BMU_CLIENT_PATH", run.client_path.clone()),
+        ("BMU_SERVER_PATH", run.server_path.clone()),
+        ("BMU_TRIGGER", format!("{:?}", run.trigger)),
+        ("BMU_STARTED_AT", run.started_at.to_string()),
+        ("BMU_STATUS", format!("{:?}", run.status)),
+        ("BMU_TRANSFERRED_FILES", run.transfer.files.to_string()),
+        ("BMU_TRANSFERRED_BYTES", run.transfer.bytes.to_string()),
+        ("BMU_ERROR", run.error.clone().unwrap_or_default()),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value))
+    .collect()
+}
+
+/// Runs `hook` with `sh -c` and the variables of `environment`, and kills it once its timeout
+/// has passed. Stdout and stderr are captured together.
+#[must_use]
+pub fn run(hook: &Hook, stage: HookStage, environment: &[(String, String)]) -> HookRun {
+    let mut hook_run = HookRun {
+        stage,
+        command: hook.command.clone(),
+        exit_code: None,
+        timed_out: false,
+        output: String::new(),
+    };
+    let timeout = hook.timeout_seconds.map_or(DEFAULT_TIMEOUT, |seconds| {
+        Duration::from_secs(u64::from(seconds))
+    });
+
+    info!("Running {stage:?} hook: {}", hook.command);
+    let mut child = match Command::new("sh")
+        .args(["-c", &hook.command])
+        .env("BMU_HOOK", format!("{stage:?}").to_lowercase())
+        .envs(environment.iter().map(|(key, value)| (key, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            error!("Could not start {stage:?} hook: {e:?}");
+            hook_run.output = format!("Could not start hook: {e}");
+            return hook_run;
+        }
+    };
+
+    // a full pipe would block the command, so the output is read while it runs
+    let (sender, receiver) = mpsc::channel();
+    let pipes: Vec<Box<dyn Read + Send>> = [
+        child
+            .stdout
+            .take()
+            .map(|pipe| Box::new(pipe) as Box<dyn Read + Send>),
+        child
+            .stderr
+            .take()
+            .map(|pipe| Box::new(pipe) as Box<dyn Read + Send>),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let pipe_count = pipes.len();
+
+    for mut pipe in pipes {
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let mut output = vec![];
+            _ = pipe.read_to_end(&mut output);
+            _ = sender.send(output);
+        });
+    }
+
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                hook_run.exit_code = status.code();
+                break;
+            }
+            Ok(None) if started.elapsed() >= timeout => {
+                error!(
+                    "{stage:?} hook timed out after {} seconds",
+                    timeout.as_secs()
+                );
+                _ = child.kill();
+                _ = child.wait();
+                hook_run.timed_out = true;
+                break;
+            }
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                error!("Could not wait for {stage:?} hook: {e:?}");
+                break;
+            }
+        }
+    }
+
+    let mut output = vec![];
+    for _ in 0..pipe_count {
+        match receiver.recv_timeout(OUTPUT_TIMEOUT) {
+            Ok(part) => output.extend(part),
+            Err(_) => break,
+        }
+    }
+    hook_run.output = truncate(&String::from_utf8_lossy(&output));
+
+    hook_run
+}
+
+/// The end of `output`, which usually holds the error.
+fn truncate(output: &str) -> String {
+    let output = output.trim();
+    if output.len() <= MAX_OUTPUT {
+        return output.to_string();
+    }
+
+    let mut start = output.len() - MAX_OUTPUT;
+    while !output.is_char_boundary(start) {
+        start += 1;
+    }
+
+    format!("...{}", &output[start..])
+}
+
+/// Why a hook did not succeed, for the error of its run.
+#[must_use]
+pub fn failure(hook_run: &HookRun) -> String {
+    let reason = if hook_run.timed_out {
+        String::from("timed out")
+    } else {
+        hook_run.exit_code.map_or_else(
+            || String::from("could not be run"),
+            |code| format!("exited with {code}"),
+        )
+    };
+
+    format!("{:?} hook {reason}: {}", hook_run.stage, hook_run.output)
+}
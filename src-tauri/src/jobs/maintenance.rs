@@ -1,6 +1,11 @@
 use std::path::PathBuf;
+use std::sync::mpsc;
 
-use super::Error;
+use super::scheduler::Priority;
+use super::{Error, Pool, WorkerId};
+
+/// Id of the cleaning job in the pool
+pub const JOB_ID: &str = "clean";
 
 pub struct Options {
     pub connections: bool,
@@ -43,3 +48,24 @@ pub fn clean(directories: &Directories, opts: Option<Options>) -> Result<(), Err
 
     Ok(())
 }
+
+/// Runs `clean` as a job of `pool`. It starts once no backup waits for a transfer, the result is
+/// sent to `done`.
+pub fn spawn(
+    pool: &mut Pool,
+    directories: Directories,
+    options: Options,
+    done: mpsc::Sender<Result<(), Error>>,
+) -> Result<WorkerId, Error> {
+    pool.spawn(
+        String::from(JOB_ID),
+        Priority::Maintenance,
+        move |arguments| async move {
+            let result = arguments
+                .transfer(move || clean(&directories, Some(options)))
+                .await
+                .and_then(|cleaned| cleaned);
+            _ = done.send(result);
+        },
+    )
+}
@@ -1,37 +1,64 @@
+use crate::commands;
 use crate::models::app::{self, Config};
-use crate::models::backup::Backup;
+use crate::models::backup::{Backup, Destination};
+use crate::models::retry::RetryPolicy;
 use crate::ssh;
+use cancel::Cancel;
+use futures::{future, FutureExt};
 use log::{error, info, warn};
-use serde::Serialize;
+use scheduler::{Priority, Scheduler, DEFAULT_MAX_TRANSFERS};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
 use std::collections::HashMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
 use std::path::PathBuf;
-use std::sync::mpsc::{self, Receiver, SendError, Sender};
+use std::sync::mpsc::SendError;
 use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 use std::thread;
+use std::time::Duration;
+use tokio::runtime::{self, Runtime};
+use tokio::task::{self, JoinHandle};
+use tokio::time;
 use ts_rs::TS;
 
-const IS_AVAILABLE_SHOULD_LOCK: &str = "could not lock field 'is_available'";
+/// Threads of the runtime, jobs only wait on them, transfers run on blocking threads
+const RUNTIME_THREADS: usize = 2;
 
 pub mod backup;
+pub mod cancel;
 pub mod fs;
+pub mod history;
+pub mod hooks;
 pub mod maintenance;
+pub mod retry;
+pub mod scheduler;
+pub mod snapshot;
+pub mod status;
+pub mod transfer;
 
 pub type Id = String;
+/// Id of the task running a job
 pub type WorkerId = usize;
-pub type Channel = (Sender<ThreadAction>, Receiver<ThreadAction>);
+pub type Tasks = HashMap<WorkerId, Task>;
 pub type Active = HashMap<Id, WorkerId>;
 pub type Failed = HashMap<Id, WorkerId>;
+pub type Retrying = HashMap<Id, Attempt>;
+pub type Cancellations = HashMap<Id, Cancel>;
 
 #[derive(Debug, Serialize)]
 pub enum Error {
     App(app::Error),
     Ssh(ssh::Error),
     NotFound(String),
+    /// The job panicked, with the message of the panic
+    Panicked(String),
     Send(String),
     Terminate(String),
     Pattern(String),
     Command(String),
     Failed(String),
+    Storage(String),
 }
 
 impl From<PoisonError<std::sync::MutexGuard<'_, PathBuf>>> for Error {
@@ -64,282 +91,491 @@ impl From<PoisonError<MutexGuard<'_, Pool>>> for Error {
     }
 }
 
+impl From<PoisonError<MutexGuard<'_, Retrying>>> for Error {
+    fn from(e: PoisonError<MutexGuard<Retrying>>) -> Self {
+        Self::App(app::Error::JobPool(e.to_string()))
+    }
+}
+
+impl From<PoisonError<MutexGuard<'_, Cancellations>>> for Error {
+    fn from(e: PoisonError<MutexGuard<Cancellations>>) -> Self {
+        Self::App(app::Error::JobPool(e.to_string()))
+    }
+}
+
+impl From<PoisonError<MutexGuard<'_, Tasks>>> for Error {
+    fn from(e: PoisonError<MutexGuard<Tasks>>) -> Self {
+        Self::App(app::Error::JobPool(e.to_string()))
+    }
+}
+
+impl From<commands::Error> for Error {
+    fn from(e: commands::Error) -> Self {
+        Self::Command(e.to_string())
+    }
+}
+
 impl<T> From<SendError<T>> for Error {
     fn from(e: SendError<T>) -> Self {
         Self::Send(e.to_string())
     }
 }
 
-#[derive(Default)]
-pub struct Handle {
-    pub id: Id,
-    pub value: Option<thread::JoinHandle<()>>,
-}
-
 pub enum Kind {
     BackupOnChange,
     Backup,
 }
 
-#[derive(TS, Serialize)]
-#[ts(
-    export,
-    export_to = "../../bindings/JobStatus.ts",
-    rename = "JobStatus"
-)]
+/// Where a job is in the pool, `status::JobStatus` adds its runs for the user.
+#[derive(Serialize)]
 pub enum Status {
+    /// The job waits for one of the running transfers to finish
+    Queued,
     Running,
+    /// The job failed and is run again, or waits to be
+    Retrying(Attempt),
     Failed,
     Completed,
+    /// The job was cancelled, it may still be cleaning up
+    Cancelled,
 }
 
-pub enum ThreadAction {
-    Start,
-    Continue,
-    Terminate,
+/// The attempt a job retried by the pool is at.
+#[derive(TS, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub struct Attempt {
+    pub job_id: Id,
+    /// Starting at 1
+    pub attempt: u32,
+    pub max_attempts: u32,
+    /// Unix timestamp the attempt starts at, `None` once it is running
+    pub next_attempt_at: Option<u64>,
 }
 
-pub enum Message {
-    New(Job),
-    Terminate(WorkerId),
+/// A job spawned on the runtime of the pool.
+pub struct Task {
+    job_id: Id,
+    cancel: Cancel,
+    handle: Option<JoinHandle<()>>,
 }
 
-pub struct Pool {
-    workers: Vec<Worker>,
-    sender: Sender<Message>,
-    receiver: Arc<Mutex<Receiver<Message>>>,
+/// Removes a task from the pool once it is done, also if it panicked.
+struct Registration {
+    id: WorkerId,
+    tasks: Arc<Mutex<Tasks>>,
 }
 
-pub trait FnBox {
-    fn call_box(self: Box<Self>, arguments: Arguments);
+impl Drop for Registration {
+    fn drop(&mut self) {
+        match self.tasks.lock() {
+            Ok(mut tasks) => {
+                tasks.remove(&self.id);
+            }
+            Err(e) => error!("Could not lock tasks: {e:?}"),
+        }
+    }
 }
 
-impl<F: FnOnce(Arguments)> FnBox for F {
-    fn call_box(self: Box<F>, arguments: Arguments) {
-        (*self)(arguments);
-    }
+/// Runs jobs as tasks on its own tokio runtime. Watchers wait for events without holding a
+/// thread, transfers run on blocking threads, of which only a limited number run at a time.
+pub struct Pool {
+    runtime: Option<Runtime>,
+    next_id: WorkerId,
+    tasks: Arc<Mutex<Tasks>>,
+    scheduler: Arc<Scheduler>,
+    retrying: Arc<Mutex<Retrying>>,
+    cancellations: Arc<Mutex<Cancellations>>,
 }
 
+/// What a job gets from the pool it runs on.
 #[derive(Clone)]
 pub struct Arguments {
     pub id: WorkerId,
-    pub sender: Arc<Mutex<Sender<ThreadAction>>>,
-    pub receiver: Arc<Mutex<mpsc::Receiver<ThreadAction>>>,
+    /// Job the task runs, its transfers are queued under it
+    pub job_id: Id,
+    /// Set once the job is told to terminate, or is cancelled
+    pub cancel: Cancel,
+    priority: Priority,
+    scheduler: Arc<Scheduler>,
 }
 
-type Job = Box<dyn FnBox + Send + 'static>;
-
-impl Pool {
-    pub fn new(size: Option<usize>) -> Self {
-        let mut workers = size.map_or_else(Vec::new, Vec::with_capacity);
-        let (sender, receiver) = mpsc::channel();
+impl Arguments {
+    /// Runs the blocking `f` once the scheduler starts it, by the priority of the job. A job
+    /// cancelled while it waits does not run `f`.
+    pub async fn transfer<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = tokio::select! {
+            permit = self.scheduler.acquire(self.job_id.clone(), self.priority) => permit?,
+            () = self.cancel.cancelled() => {
+                return Err(Error::Failed(format!("{} was cancelled while queued", self.job_id)));
+            }
+        };
 
-        let receiver = Arc::new(Mutex::new(receiver));
+        let result = task::spawn_blocking(f).await;
+        drop(permit);
+        result.map_err(|e| match e.try_into_panic() {
+            Ok(payload) => Error::Panicked(panic_message(&*payload)),
+            Err(e) => Error::Failed(format!("Transfer of job {} failed: {e}", self.id)),
+        })
+    }
+}
 
-        if let Some(size) = size {
-            for id in 0..size {
-                workers.push(Worker::new(id, Arc::clone(&receiver)));
-            }
-        }
+impl Pool {
+    /// Creates a pool running at most `transfers` transfers at a time, `DEFAULT_MAX_TRANSFERS`
+    /// if not given. Jobs waiting for events don't count towards it.
+    ///
+    /// # Panics
+    /// Panics if the runtime of the pool could not be started.
+    #[must_use]
+    pub fn new(transfers: Option<usize>) -> Self {
+        let runtime = runtime::Builder::new_multi_thread()
+            .worker_threads(RUNTIME_THREADS)
+            .thread_name("bmu-jobs")
+            .enable_all()
+            .build()
+            .expect("could not start the job runtime");
 
         Self {
-            workers,
-            sender,
-            receiver,
+            runtime: Some(runtime),
+            next_id: 0,
+            tasks: Arc::new(Mutex::new(Tasks::new())),
+            scheduler: Arc::new(Scheduler::new(transfers.unwrap_or(DEFAULT_MAX_TRANSFERS))),
+            retrying: Arc::new(Mutex::new(Retrying::new())),
+            cancellations: Arc::new(Mutex::new(Cancellations::new())),
         }
     }
 
-    pub fn execute<F>(&mut self, f: F) -> Result<(), Error>
+    /// Spawns the future `f` returns as a task running `job_id`, whose transfers are started
+    /// with `priority`. The token in its `Arguments` is set by `terminate_job`.
+    pub fn spawn<F, Fut>(&mut self, job_id: Id, priority: Priority, f: F) -> Result<WorkerId, Error>
     where
-        F: FnOnce(Arguments) + Send + 'static,
+        F: FnOnce(Arguments) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn_with(job_id, priority, Cancel::default(), f)
+    }
+
+    fn spawn_with<F, Fut>(
+        &mut self,
+        job_id: Id,
+        priority: Priority,
+        cancel: Cancel,
+        f: F,
+    ) -> Result<WorkerId, Error>
+    where
+        F: FnOnce(Arguments) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
     {
-        let job = Box::new(f);
+        let runtime = match self.runtime.as_ref() {
+            Some(runtime) => runtime,
+            None => return Err(Error::Failed(String::from("The job runtime is shut down"))),
+        };
+        self.next_id += 1;
+        let id = self.next_id;
+        let future = f(Arguments {
+            id,
+            job_id: job_id.clone(),
+            cancel: cancel.clone(),
+            priority,
+            scheduler: Arc::clone(&self.scheduler),
+        });
 
-        if !self.has_available_worker() {
-            self.create_workers(1);
+        // the task is known before it is spawned, so it can't remove itself too early
+        self.tasks.lock()?.insert(
+            id,
+            Task {
+                job_id: job_id.clone(),
+                cancel,
+                handle: None,
+            },
+        );
+        let registration = Registration {
+            id,
+            tasks: Arc::clone(&self.tasks),
+        };
+        let handle = runtime.spawn(async move {
+            let _registration = registration;
+            // a panicking job ends its own task only
+            if let Err(payload) = AssertUnwindSafe(future).catch_unwind().await {
+                error!("{job_id} panicked: {}", panic_message(&*payload));
+            }
+        });
+
+        if let Some(task) = self.tasks.lock()?.get_mut(&id) {
+            task.handle = Some(handle);
         }
 
-        Ok(self.sender.send(Message::New(job))?)
+        Ok(id)
     }
 
-    #[must_use]
-    pub fn available_workers(&self) -> usize {
-        self.workers.iter().fold(0, |acc, w| {
-            if *w.is_available.lock().expect(IS_AVAILABLE_SHOULD_LOCK) {
-                acc + 1
-            } else {
-                acc
+    /// Runs the blocking `f` as a task once the scheduler starts it.
+    pub fn execute<F>(&mut self, job_id: Id, priority: Priority, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(Arguments) + Send + 'static,
+    {
+        self.spawn(job_id, priority, |arguments| async move {
+            let job = arguments.clone();
+            if let Err(e) = arguments.transfer(move || f(job)).await {
+                error!("Job {} failed: {e:?}", arguments.id);
             }
-        })
+        })?;
+
+        Ok(())
     }
 
-    fn has_available_worker(&mut self) -> bool {
-        if self.workers.is_empty() {
-            return false;
+    /// Runs `f` like `execute`, and again after a backoff as long as it fails with an error
+    /// worth retrying and `policy` allows further attempts. The attempt of the job is kept under
+    /// `id` until it succeeds or gives up. `cancel` and `terminate_job` set the token in the
+    /// `Arguments` of `f`, a cancelled job is not retried.
+    pub fn execute_with_retries<F>(
+        &mut self,
+        id: Id,
+        priority: Priority,
+        policy: RetryPolicy,
+        f: F,
+    ) -> Result<(), Error>
+    where
+        F: Fn(&Arguments) -> Result<(), String> + Send + 'static,
+    {
+        let job_id = id.clone();
+        let queued_id = id.clone();
+        let token = Cancel::default();
+        // a new run replaces the token of a cancelled one
+        self.cancellations.lock()?.insert(id.clone(), token.clone());
+        let cancellations = Arc::clone(&self.cancellations);
+        let retrying = Arc::clone(&self.retrying);
+        let set_attempt = move |attempt: Option<Attempt>| match retrying.lock() {
+            Ok(mut retrying) => {
+                match attempt {
+                    Some(attempt) => retrying.insert(attempt.job_id.clone(), attempt),
+                    None => retrying.remove(&id),
+                };
+            }
+            Err(e) => error!("Could not lock retrying jobs: {e:?}"),
         };
+        // attempts run one after another, the lock only lets them share `f` across threads
+        let f = Arc::new(Mutex::new(f));
+
+        self.spawn_with(queued_id, priority, token, move |arguments| async move {
+            let mut attempt = 1;
+
+            loop {
+                set_attempt(Some(Attempt {
+                    job_id: job_id.clone(),
+                    attempt,
+                    max_attempts: policy.max_attempts,
+                    next_attempt_at: None,
+                }));
+
+                let job = arguments.clone();
+                let f = Arc::clone(&f);
+                let run = move || match f.lock() {
+                    Ok(f) => f(&job),
+                    Err(e) => Err(format!("Could not lock job: {e:?}")),
+                };
+                let why = match arguments.transfer(run).await {
+                    Ok(Ok(())) => break,
+                    Ok(Err(why)) => why,
+                    // whatever made it panic likely does again
+                    Err(Error::Panicked(message)) => {
+                        error!("{job_id} panicked, it is not retried: {message}");
+                        break;
+                    }
+                    Err(e) => format!("{e:?}"),
+                };
+                if arguments.cancel.is_cancelled() {
+                    info!("{job_id} was cancelled");
+                    break;
+                }
+                let kind = retry::classify(&why);
 
-        for worker in &mut self.workers {
-            if *worker.is_available.lock().expect(IS_AVAILABLE_SHOULD_LOCK) {
-                if worker.thread.is_none() {
-                    *worker.is_available.lock().expect(IS_AVAILABLE_SHOULD_LOCK) = false;
-                    worker.start();
+                if !kind.is_retryable() || attempt >= policy.max_attempts {
+                    info!("{job_id} failed with a {kind:?} error after {attempt} attempts");
+                    break;
                 }
 
-                return true;
+                let delay = policy.delay(attempt, retry::jitter());
+                info!("{job_id} failed with a {kind:?} error, retrying in {delay:?}");
+                set_attempt(Some(Attempt {
+                    job_id: job_id.clone(),
+                    attempt: attempt + 1,
+                    max_attempts: policy.max_attempts,
+                    next_attempt_at: Some(history::now() + delay.as_secs()),
+                }));
+
+                if retry::wait(&arguments.cancel, delay).await {
+                    info!("{job_id} was cancelled while waiting for a retry");
+                    break;
+                }
+                attempt += 1;
             }
-        }
-
-        false
-    }
 
-    pub fn create_workers(&mut self, size: usize) {
-        if size == 0 {
-            warn!("size must be greater than 0");
-            return;
-        }
+            set_attempt(None);
+            // the token of a cancelled job stays, so its status can be told apart from a success
+            if !arguments.cancel.is_cancelled() {
+                match cancellations.lock() {
+                    Ok(mut cancellations) => {
+                        cancellations.remove(&job_id);
+                    }
+                    Err(e) => error!("Could not lock cancellations: {e:?}"),
+                }
+            }
+        })?;
 
-        for _ in 0..size {
-            let id = self.workers.len() + 1;
-            let mut worker = Worker::new(id, Arc::clone(&self.receiver));
-            worker.start();
-            info!("Adding new worker with id {id}");
-            self.workers.push(worker);
-        }
+        Ok(())
     }
 
-    pub fn start_all_stopped_workers(&mut self) {
-        for worker in &mut self.workers {
-            if *worker.is_available.lock().expect(IS_AVAILABLE_SHOULD_LOCK)
-                && worker.thread.is_none()
-            {
-                worker.start();
+    /// Cancels the job run with `id` by `execute_with_retries`. The command it runs is
+    /// terminated, and it is neither retried nor continued with further destinations.
+    pub fn cancel(&self, id: &str) -> Result<(), Error> {
+        match self.cancellations.lock()?.get(id) {
+            Some(token) => {
+                info!("Cancelling {id}");
+                token.cancel();
+                Ok(())
             }
+            None => Err(Error::NotFound(format!("No running job with id {id}"))),
         }
     }
 
-    pub fn stop_all_workers(&mut self) {
-        info!("Sending terminate message to all workers.");
+    /// Tokens of the jobs run by `execute_with_retries`, including the cancelled ones.
+    #[must_use]
+    pub fn cancellations(&self) -> Arc<Mutex<Cancellations>> {
+        Arc::clone(&self.cancellations)
+    }
 
-        for worker in &mut self.workers {
-            if let Err(e) = self.sender.send(Message::Terminate(worker.id)) {
-                info!("Error sending terminate message: {e:?}");
-            }
-        }
+    /// Jobs retried by the pool along with their attempt.
+    #[must_use]
+    pub fn retrying(&self) -> Arc<Mutex<Retrying>> {
+        Arc::clone(&self.retrying)
+    }
 
-        for worker in &mut self.workers {
-            match worker.thread.take() {
-                Some(thread) => match thread.join() {
-                    Ok(_) => {
-                        info!("Worker {} terminated.", worker.id);
-                    }
-                    Err(e) => {
-                        info!("Error shutting down worker {}: {e:?}", worker.id);
-                    }
-                },
-                None => {
-                    info!("Worker {} already terminated.", worker.id);
-                }
-            }
-        }
+    /// Transfers waiting for one of the running ones to finish.
+    #[must_use]
+    pub fn queued(&self) -> usize {
+        self.scheduler.queued().len()
     }
 
-    pub fn terminate_job(&mut self, id: WorkerId, event_trigger: impl Fn()) -> Result<(), String> {
-        let worker = match self.workers.iter_mut().find(|w| w.id == id) {
-            Some(worker) => worker,
-            None => return Err("Could not find worker".to_string()),
-        };
+    /// Scheduler starting the transfers of the jobs, which knows the queued ones.
+    #[must_use]
+    pub fn scheduler(&self) -> Arc<Scheduler> {
+        Arc::clone(&self.scheduler)
+    }
 
-        match worker.local_sender.lock() {
-            Ok(sender) => {
-                if let Err(error) = sender.send(ThreadAction::Terminate) {
-                    return Err(error.to_string());
-                }
-            }
-            Err(e) => return Err(format!("Could not send terminate message: \n{e:?}")),
-        }
+    /// Changes the number of transfers running at a time, `DEFAULT_MAX_TRANSFERS` if not given.
+    pub fn set_max_transfers(&self, max_transfers: Option<usize>) {
+        self.scheduler
+            .set_limit(max_transfers.unwrap_or(DEFAULT_MAX_TRANSFERS));
+    }
 
-        // INFO: trigger event on the running job so that it can be closed
-        event_trigger();
+    /// Tasks that have not finished yet.
+    #[must_use]
+    pub fn running(&self) -> usize {
+        self.tasks.lock().map_or(0, |tasks| tasks.len())
+    }
 
-        Ok(())
+    /// Jobs of the tasks that have not finished yet, along with their task. A watcher waiting to
+    /// be restarted is only found here.
+    #[must_use]
+    pub fn jobs(&self) -> Vec<(Id, WorkerId)> {
+        self.tasks.lock().map_or_else(
+            |_| vec![],
+            |tasks| {
+                tasks
+                    .iter()
+                    .map(|(id, task)| (task.job_id.clone(), *id))
+                    .collect()
+            },
+        )
     }
-}
 
-pub struct Worker {
-    pub id: WorkerId,
-    is_available: Arc<Mutex<bool>>,
-    pub thread: Option<thread::JoinHandle<()>>,
-    local_sender: Arc<Mutex<Sender<ThreadAction>>>,
-    local_receiver: Arc<Mutex<Receiver<ThreadAction>>>,
-    receiver: Arc<Mutex<mpsc::Receiver<Message>>>,
-}
+    /// Jobs of the tasks that were not told to terminate, along with their task. A terminated
+    /// task may still be finishing, a new task of its job can start in the meantime.
+    #[must_use]
+    pub fn active_jobs(&self) -> Vec<(Id, WorkerId)> {
+        self.tasks.lock().map_or_else(
+            |_| vec![],
+            |tasks| {
+                tasks
+                    .iter()
+                    .filter(|(_, task)| !task.cancel.is_cancelled())
+                    .map(|(id, task)| (task.job_id.clone(), *id))
+                    .collect()
+            },
+        )
+    }
 
-impl Worker {
-    pub fn new(id: WorkerId, receiver: Arc<Mutex<mpsc::Receiver<Message>>>) -> Self {
-        let (local_sender, local_receiver): Channel = mpsc::channel();
-        let local_sender = Arc::new(Mutex::new(local_sender));
-        let local_receiver = Arc::new(Mutex::new(local_receiver));
-        let is_available = Arc::new(Mutex::new(true));
+    /// Whether a task of `job_id` has not finished yet. The task exists as soon as the job is
+    /// spawned, before the job shows up in any list of `check_status`.
+    #[must_use]
+    pub fn has_task(&self, job_id: &str) -> bool {
+        self.jobs().iter().any(|(id, _)| id == job_id)
+    }
 
-        Self {
-            id,
-            is_available,
-            thread: None,
-            local_sender,
-            local_receiver,
-            receiver,
+    /// Tells the job run by the task `id` to terminate. Watchers stop right away, the command
+    /// of a transfer is terminated.
+    pub fn terminate_job(&self, id: WorkerId) -> Result<(), String> {
+        match self.tasks.lock() {
+            Ok(tasks) => match tasks.get(&id) {
+                Some(task) => {
+                    task.cancel.cancel();
+                    Ok(())
+                }
+                None => Err(String::from("Could not find task")),
+            },
+            Err(e) => Err(format!("Could not lock tasks: \n{e:?}")),
         }
     }
 
-    pub fn start(&mut self) {
-        info!("Starting worker {}", self.id);
-        let local_sender = Arc::clone(&self.local_sender);
-        let local_receiver = Arc::clone(&self.local_receiver);
-
-        let arguments = Arguments {
-            id: self.id,
-            receiver: local_receiver,
-            sender: local_sender,
-        };
-        let is_available = Arc::clone(&self.is_available);
-
-        arguments
-            .sender
-            .lock()
-            .expect("could not lock sender")
-            .send(ThreadAction::Start)
-            .expect("could not send start message");
-
-        let id = self.id;
-        let receiver = Arc::clone(&self.receiver);
-
-        let thread = thread::spawn(move || loop {
-            let message = receiver
-                .lock()
-                .expect("could not lock receiver")
-                .recv()
-                .expect("could not receive message");
-
-            match message {
-                Message::New(job) => {
-                    *is_available.lock().expect(IS_AVAILABLE_SHOULD_LOCK) = false;
-                    info!("Worker {id} got a job; executing.");
-
-                    job.call_box(arguments.clone());
-                    info!("Worker {id} finished.");
-                    *is_available.lock().expect(IS_AVAILABLE_SHOULD_LOCK) = true;
-                }
-                Message::Terminate(termination_id) => {
-                    if termination_id == id {
-                        info!("Worker {id} was told to terminate.");
-                        *is_available.lock().expect(IS_AVAILABLE_SHOULD_LOCK) = true;
-                        break;
-                    }
-                }
+    /// Terminates every task and waits up to `timeout` for them to finish, so commands they run
+    /// can clean up before the app exits.
+    pub fn shutdown(&mut self, timeout: Duration) {
+        let handles: Vec<JoinHandle<()>> = match self.tasks.lock() {
+            Ok(mut tasks) => tasks
+                .values_mut()
+                .filter_map(|task| {
+                    task.cancel.cancel();
+                    task.handle.take()
+                })
+                .collect(),
+            Err(e) => {
+                error!("Could not lock tasks: {e:?}");
+                return;
             }
+        };
+        let runtime = match self.runtime.as_ref() {
+            Some(runtime) => runtime.handle().clone(),
+            None => return,
+        };
+
+        // the caller may run on a runtime itself, which can't block on another one
+        let waiter = thread::spawn(move || {
+            runtime.block_on(async { time::timeout(timeout, future::join_all(handles)).await })
         });
+        match waiter.join() {
+            Ok(Ok(_)) => info!("All tasks finished"),
+            Ok(Err(_)) => warn!("Not all tasks finished within {timeout:?}"),
+            Err(e) => error!("Could not wait for tasks: {e:?}"),
+        }
+    }
+}
+
+impl Drop for Pool {
+    /// Terminates every task without waiting for them, since the pool may be dropped by async
+    /// code, where the runtime can't be shut down blocking.
+    fn drop(&mut self) {
+        if let Ok(tasks) = self.tasks.lock() {
+            for task in tasks.values() {
+                task.cancel.cancel();
+            }
+        }
 
-        self.thread = Some(thread);
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
     }
 }
 
@@ -361,18 +597,72 @@ pub fn id_from_backup(backup: &Backup, kind: &Kind) -> String {
     }
 }
 
+/// Id used to track the outcome of a job for a single destination of its backup.
+#[must_use]
+pub fn id_for_destination(job_id: &str, destination: &Destination) -> String {
+    format!("{job_id}->{destination}")
+}
+
+/// Message of a caught panic. `panic!` gives a `&str` without arguments and a `String` with
+/// them.
+#[must_use]
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| (*message).to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| String::from("unknown panic"))
+}
+
 pub fn check_status(
     id: &String,
     running_jobs: &Arc<Mutex<Active>>,
     failed_jobs: &Arc<Mutex<Failed>>,
+    retrying: &Arc<Mutex<Retrying>>,
+    cancellations: &Arc<Mutex<Cancellations>>,
+    scheduler: &Scheduler,
 ) -> Result<Status, Error> {
-    if failed_jobs.lock()?.contains_key(id) {
-        error!("{id}: failed");
-        return Ok(Status::Failed);
-    } else if running_jobs.lock()?.contains_key(id) {
-        return Ok(Status::Running);
+    let status = status_from(
+        retrying.lock()?.get(id),
+        running_jobs.lock()?.contains_key(id),
+        failed_jobs.lock()?.contains_key(id),
+        cancellations
+            .lock()?
+            .get(id)
+            .map_or(false, Cancel::is_cancelled),
+        scheduler.is_queued(id),
+    );
+
+    match status {
+        Status::Failed => error!("{id}: failed"),
+        Status::Completed => info!("{id}: completed"),
+        Status::Cancelled => info!("{id}: cancelled"),
+        Status::Queued | Status::Running | Status::Retrying(_) => (),
     }
 
-    info!("{id}: completed");
-    Ok(Status::Completed)
+    Ok(status)
+}
+
+/// The status of a job from the lists it is found in. A job retried by the pool keeps running
+/// until it gives up, even if its latest attempt failed. A cancelled job is reported as such
+/// right away, a job waiting for its transfer to start is queued, whatever attempt it is at.
+#[must_use]
+pub fn status_from(
+    attempt: Option<&Attempt>,
+    running: bool,
+    failed: bool,
+    cancelled: bool,
+    queued: bool,
+) -> Status {
+    match attempt {
+        _ if cancelled => Status::Cancelled,
+        _ if queued => Status::Queued,
+        Some(attempt) if attempt.attempt > 1 || attempt.next_attempt_at.is_some() => {
+            Status::Retrying(attempt.clone())
+        }
+        Some(_) => Status::Running,
+        None if failed => Status::Failed,
+        None if running => Status::Running,
+        None => Status::Completed,
+    }
 }
@@ -0,0 +1,92 @@
+use super::cancel::Cancel;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+use ts_rs::TS;
+
+/// What made a job fail, which decides whether running it again may help.
+#[derive(TS, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[ts(export)]
+pub enum FailureKind {
+    /// The server or a disk could not be reached, which is often temporary
+    Network,
+    /// Access was denied, retrying fails the same way
+    Permission,
+    /// The directory to back up is gone
+    MissingSource,
+    /// The job panicked, which a retry likely repeats
+    Panic,
+    Other,
+}
+
+impl FailureKind {
+    #[must_use]
+    pub const fn is_retryable(self) -> bool {
+        matches!(self, Self::Network | Self::Other)
+    }
+}
+
+const NETWORK_ERRORS: [&str; 13] = [
+    "connection refused",
+    "connection reset",
+    "connection timed out",
+    "connection unexpectedly closed",
+    "connection closed",
+    "operation timed out",
+    "could not resolve hostname",
+    "name or service not known",
+    "no route to host",
+    "network is unreachable",
+    "broken pipe",
+    "error in socket io",
+    "is not available",
+];
+
+const PERMISSION_ERRORS: [&str; 4] = [
+    "permission denied",
+    "operation not permitted",
+    "read-only file system",
+    "host key verification failed",
+];
+
+const PANIC_ERRORS: [&str; 1] = ["panicked"];
+
+const MISSING_SOURCE_ERRORS: [&str; 3] =
+    ["no such file or directory", "does not exist", "link_stat"];
+
+/// Classifies the error message of a failed run. A run failing on several destinations is
+/// retryable if any of them failed on the network, unless it panicked.
+#[must_use]
+pub fn classify(error: &str) -> FailureKind {
+    let error = error.to_lowercase();
+    let contains_any = |patterns: &[&str]| patterns.iter().any(|pattern| error.contains(pattern));
+
+    if contains_any(&PANIC_ERRORS) {
+        FailureKind::Panic
+    } else if contains_any(&NETWORK_ERRORS) {
+        FailureKind::Network
+    } else if contains_any(&PERMISSION_ERRORS) {
+        FailureKind::Permission
+    } else if contains_any(&MISSING_SOURCE_ERRORS) {
+        FailureKind::MissingSource
+    } else {
+        FailureKind::Other
+    }
+}
+
+/// A number between 0 and 1 that differs between calls. The hasher of `RandomState` is seeded
+/// randomly, which is good enough to spread retries.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn jitter() -> f64 {
+    RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64
+}
+
+/// Waits for `delay`. Returns `true` if the job was terminated or cancelled in the meantime.
+pub async fn wait(cancel: &Cancel, delay: Duration) -> bool {
+    tokio::select! {
+        () = tokio::time::sleep(delay) => false,
+        () = cancel.cancelled() => true,
+    }
+}
@@ -0,0 +1,185 @@
+use super::{Error, Id};
+use log::info;
+use std::cmp::Reverse;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::oneshot;
+
+/// Transfers running at the same time if the config doesn't limit them
+pub const DEFAULT_MAX_TRANSFERS: usize = 4;
+
+/// Which waiting transfer starts first, from lowest to highest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Cleaning up after the app, which can wait for every backup
+    Maintenance,
+    Watcher,
+    /// Started by the user, who waits for it
+    Manual,
+}
+
+struct Waiter {
+    job_id: Id,
+    priority: Priority,
+    /// Order of arrival, transfers of the same priority start in it
+    sequence: u64,
+    start: oneshot::Sender<Permit>,
+}
+
+struct State {
+    limit: usize,
+    running: usize,
+    sequence: u64,
+    waiting: Vec<Waiter>,
+}
+
+/// Starts at most a limited number of transfers at a time, waiting ones start by priority.
+pub struct Scheduler {
+    state: Mutex<State>,
+}
+
+/// A running transfer, the next waiting one starts once it is dropped.
+pub struct Permit {
+    scheduler: Arc<Scheduler>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.scheduler.lock().running -= 1;
+        self.scheduler.dispatch();
+    }
+}
+
+/// Takes a transfer out of the queue if it stops waiting before it is started.
+struct Ticket<'a> {
+    scheduler: &'a Scheduler,
+    sequence: u64,
+}
+
+impl Drop for Ticket<'_> {
+    fn drop(&mut self) {
+        self.scheduler
+            .lock()
+            .waiting
+            .retain(|waiter| waiter.sequence != self.sequence);
+    }
+}
+
+impl Scheduler {
+    #[must_use]
+    pub fn new(limit: usize) -> Self {
+        Self {
+            state: Mutex::new(State {
+                limit: limit.max(1),
+                running: 0,
+                sequence: 0,
+                waiting: vec![],
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<State> {
+        // the state is consistent after every statement, so it is still used after a panic
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Waits until the transfer of `job_id` may start. The transfer runs until the permit is
+    /// dropped.
+    pub async fn acquire(
+        self: &Arc<Self>,
+        job_id: Id,
+        priority: Priority,
+    ) -> Result<Permit, Error> {
+        let (sequence, receiver) = {
+            let mut state = self.lock();
+            if state.running < state.limit && state.waiting.is_empty() {
+                state.running += 1;
+                return Ok(Permit {
+                    scheduler: Arc::clone(self),
+                });
+            }
+
+            let (start, receiver) = oneshot::channel();
+            state.sequence += 1;
+            let sequence = state.sequence;
+            state.waiting.push(Waiter {
+                job_id,
+                priority,
+                sequence,
+                start,
+            });
+            (sequence, receiver)
+        };
+        let _ticket = Ticket {
+            scheduler: self,
+            sequence,
+        };
+
+        receiver
+            .await
+            .map_err(|_| Error::Failed(String::from("The transfer was dropped from the queue")))
+    }
+
+    /// Changes the number of transfers running at a time. Running transfers are not stopped if
+    /// the limit shrinks, waiting ones start right away if it grows.
+    pub fn set_limit(self: &Arc<Self>, limit: usize) {
+        self.lock().limit = limit.max(1);
+        self.dispatch();
+    }
+
+    /// Ids of the jobs with a waiting transfer, in the order they start.
+    #[must_use]
+    pub fn queued(&self) -> Vec<Id> {
+        let state = self.lock();
+        let mut waiting: Vec<&Waiter> = state.waiting.iter().collect();
+        waiting.sort_by_key(|waiter| (Reverse(waiter.priority), waiter.sequence));
+
+        waiting.iter().map(|waiter| waiter.job_id.clone()).collect()
+    }
+
+    #[must_use]
+    pub fn is_queued(&self, job_id: &str) -> bool {
+        self.lock()
+            .waiting
+            .iter()
+            .any(|waiter| waiter.job_id == job_id)
+    }
+
+    /// Starts waiting transfers as long as the limit allows.
+    fn dispatch(self: &Arc<Self>) {
+        loop {
+            let waiter = {
+                let mut state = self.lock();
+                if state.running >= state.limit {
+                    return;
+                }
+                let next = state
+                    .waiting
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|(_, waiter)| (waiter.priority, Reverse(waiter.sequence)))
+                    .map(|(index, _)| index);
+
+                match next {
+                    Some(index) => {
+                        state.running += 1;
+                        state.waiting.swap_remove(index)
+                    }
+                    None => return,
+                }
+            };
+
+            // the permit of a transfer that stopped waiting is dropped once the lock is released
+            if waiter
+                .start
+                .send(Permit {
+                    scheduler: Arc::clone(self),
+                })
+                .is_err()
+            {
+                info!("Transfer of {} stopped waiting", waiter.job_id);
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use super::Error;
+use crate::models::backup::Backup;
+use log::{error, info, warn};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const SNAPSHOTS_DIR: &str = "snapshots";
+/// Files SQLite keeps next to a database while it is in use
+const COMPANION_SUFFIXES: [&str; 3] = ["-wal", "-shm", "-journal"];
+/// Milliseconds to wait for a writer to release its lock before giving up
+const BUSY_TIMEOUT_MS: u32 = 10_000;
+/// Every SQLite database starts with this header
+const HEADER: &[u8; 16] = b"SQLite format 3\0";
+const SQLITE3_MISSING: &str =
+    "sqlite3 is required to back up databases, install it on the client and run the backup again";
+
+/// Whether the `sqlite3` command line tool that snapshots are taken with is installed.
+#[must_use]
+pub fn sqlite3_is_installed() -> bool {
+    Command::new("sqlite3")
+        .arg("-version")
+        .output()
+        .map_or(false, |output| output.status.success())
+}
+
+/// Whether the file at `path` starts with the header of a SQLite database.
+fn is_database(path: &Path) -> bool {
+    let mut header = [0; 16];
+
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .map_or(false, |_| &header == HEADER)
+}
+
+/// Staging area in the app cache for the snapshots of a job.
+#[must_use]
+pub fn staging_dir(app_cache_dir: &Path, job_id: &str) -> PathBuf {
+    app_cache_dir
+        .join(SNAPSHOTS_DIR)
+        .join(job_id.trim_start_matches('/').replace('/', "_"))
+}
+
+/// The database of `backup` that `path` is, or that `path` is a journal of. Relative to the
+/// client location like the databases of the backup.
+#[must_use]
+pub fn database_for(backup: &Backup, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(&backup.client_location.path).ok()?;
+    let relative = relative.to_str()?;
+
+    backup
+        .databases
+        .as_ref()?
+        .iter()
+        .find(|database| {
+            relative == database.as_str()
+                || COMPANION_SUFFIXES
+                    .iter()
+                    .any(|suffix| relative.strip_suffix(suffix) == Some(database.as_str()))
+        })
+        .cloned()
+}
+
+/// The databases of `backup` inside `directory`, relative to it like the databases of a backup
+/// of `directory` would be.
+#[must_use]
+pub fn databases_in(backup: &Backup, directory: &Path) -> Vec<String> {
+    let root = Path::new(&backup.client_location.path);
+
+    backup
+        .databases
+        .iter()
+        .flatten()
+        .filter_map(|database| {
+            let path = root.join(database);
+            let relative = path.strip_prefix(directory).ok()?;
+            relative.to_str().map(String::from)
+        })
+        .collect()
+}
+
+/// Copies the database at `source` to `target` with the online backup API of SQLite, through
+/// the `sqlite3` command line tool, which has to be installed on the client. Writers are waited
+/// for, so the copy is consistent even if the database is in use.
+pub fn take(source: &Path, target: &Path) -> Result<(), Error> {
+    // sqlite3 would create an empty database otherwise
+    if !source.is_file() {
+        return Err(Error::NotFound(format!(
+            "Database {} does not exist",
+            source.display()
+        )));
+    }
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|e| Error::Command(e.to_string()))?;
+    }
+    if target.exists() {
+        fs::remove_file(target).map_err(|e| Error::Command(e.to_string()))?;
+    }
+
+    // double quoted arguments of dot commands resolve backslash escapes
+    let quoted_target = target
+        .to_string_lossy()
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"");
+    let output = Command::new("sqlite3")
+        .arg("-batch")
+        .arg("-bail")
+        .args(["-cmd", &format!(".timeout {BUSY_TIMEOUT_MS}")])
+        .arg(source)
+        .arg(format!(".backup main \"{quoted_target}\""))
+        .output()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::NotFound(String::from(SQLITE3_MISSING)),
+            _ => Error::Command(format!("Could not run sqlite3: {e}")),
+        })?;
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+
+    // older versions of sqlite3 exit successfully on some errors of dot commands, the snapshot
+    // is only trusted once it is a database
+    if output.status.success() && is_database(target) {
+        if !stderr.is_empty() {
+            warn!(
+                "sqlite3 warned while snapshotting {}: {stderr}",
+                source.display()
+            );
+        }
+        info!("Snapshot of {} taken", source.display());
+        Ok(())
+    } else {
+        Err(Error::Command(format!(
+            "Could not snapshot {}: {stderr}",
+            source.display()
+        )))
+    }
+}
+
+/// Takes a snapshot of `database` of `backup` in `staging_dir` and returns its path.
+pub fn database(backup: &Backup, database: &str, staging_dir: &Path) -> Result<PathBuf, Error> {
+    let target = staging_dir.join(database);
+    take(
+        &Path::new(&backup.client_location.path).join(database),
+        &target,
+    )?;
+
+    Ok(target)
+}
+
+/// Removes the snapshots of a job once they are uploaded, they are copies of user data.
+pub fn clean(staging_dir: &Path) {
+    if staging_dir.exists() {
+        if let Err(e) = fs::remove_dir_all(staging_dir) {
+            error!("Could not remove snapshots in {staging_dir:?}: {e:?}");
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use super::retry::{self, FailureKind};
+use super::{Attempt, Id, Status};
+use crate::control::DaemonStatus;
+use crate::models::history::{Run, RunStatus, Transfer};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use ts_rs::TS;
+
+/// What a job is doing, as shown to the user.
+#[derive(TS, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[ts(export, export_to = "../../bindings/JobState.ts", rename = "JobState")]
+pub enum State {
+    /// The job waits for one of the running transfers to finish
+    Queued,
+    /// The job runs, or waits for its next attempt
+    Running,
+    /// The watcher is stopped until background backups are resumed
+    Paused,
+    Completed,
+    Failed,
+    /// The job was cancelled, it may still be cleaning up
+    Cancelled,
+}
+
+/// A job as reported by the pool, along with its latest run in the history.
+#[derive(TS, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[ts(
+    export,
+    export_to = "../../bindings/JobStatus.ts",
+    rename = "JobStatus"
+)]
+pub struct JobStatus {
+    pub job_id: Id,
+    pub state: State,
+    /// Unix timestamp in seconds the latest run started at
+    pub started_at: Option<u64>,
+    /// Unix timestamp in seconds the latest run ended at, `None` while it runs
+    pub ended_at: Option<u64>,
+    /// Error of the latest failed run, `None` once a later run succeeded
+    pub error: Option<String>,
+    pub error_kind: Option<FailureKind>,
+    /// The attempt the pool is at, `None` if the job is not retried
+    pub attempt: Option<Attempt>,
+    /// Files and bytes of the latest run, the history records them once it ends
+    pub progress: Option<Transfer>,
+}
+
+/// Status of the job `job_id` from the lists of `status` and the `runs` of the history, oldest
+/// first. A job the pool doesn't know about anymore takes the state of its latest run.
+#[must_use]
+pub fn describe(job_id: &str, status: &DaemonStatus, runs: &[Run]) -> JobStatus {
+    let attempt = status
+        .retrying
+        .iter()
+        .find(|attempt| attempt.job_id == job_id)
+        .cloned();
+    let runs: Vec<&Run> = runs
+        .iter()
+        .rev()
+        .filter(|run| run.job_id == job_id)
+        .collect();
+    let latest = runs.first();
+
+    let state = match status.status_of(job_id) {
+        Status::Queued => State::Queued,
+        Status::Running | Status::Retrying(_) => State::Running,
+        Status::Failed => State::Failed,
+        Status::Cancelled => State::Cancelled,
+        Status::Completed if status.paused && job_id.ends_with("_backup_on_change") => {
+            State::Paused
+        }
+        Status::Completed => match latest.map(|run| run.status) {
+            Some(RunStatus::Failure | RunStatus::Interrupted) => State::Failed,
+            Some(RunStatus::Cancelled) => State::Cancelled,
+            _ => State::Completed,
+        },
+    };
+
+    let error = runs
+        .iter()
+        .find(|run| {
+            run.status != RunStatus::Running
+                && (run.error.is_some() || run.status == RunStatus::Success)
+        })
+        .and_then(|run| run.error.clone());
+
+    JobStatus {
+        job_id: job_id.to_string(),
+        state,
+        started_at: latest.map(|run| run.started_at),
+        ended_at: latest
+            .filter(|run| run.status != RunStatus::Running)
+            .map(|run| run.ended_at),
+        error_kind: error.as_deref().map(retry::classify),
+        error,
+        attempt,
+        progress: latest.map(|run| run.transfer),
+    }
+}
+
+/// Statuses of every job in the lists of `status` or with a run in the history, by job id.
+#[must_use]
+pub fn list(status: &DaemonStatus, runs: &[Run]) -> Vec<JobStatus> {
+    let ids: BTreeSet<&str> = status
+        .jobs
+        .iter()
+        .chain(&status.failed_jobs)
+        .chain(&status.cancelled)
+        .chain(&status.queued)
+        .chain(status.retrying.iter().map(|attempt| &attempt.job_id))
+        .chain(runs.iter().map(|run| &run.job_id))
+        .map(String::as_str)
+        .collect();
+
+    ids.into_iter()
+        .map(|job_id| describe(job_id, status, runs))
+        .collect()
+}
@@ -0,0 +1,76 @@
+use super::Error;
+use crate::models::history::Transfer;
+use crate::ssh::commands::Copied;
+use log::info;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Attempts of a transfer whose files keep changing, before it is given up as partial
+pub const MAX_ATTEMPTS: usize = 3;
+
+/// Files of `sent` below `root` that were modified since `started`, leaving out anything `skip`
+/// matches. Only the files a transfer touched are looked at, the rest of the source is not
+/// walked. Files that were removed are not of interest, there is nothing left to transfer.
+#[must_use]
+fn changed_since(
+    root: &Path,
+    sent: &[PathBuf],
+    started: SystemTime,
+    skip: &dyn Fn(&Path) -> bool,
+) -> Vec<PathBuf> {
+    let mut changed: Vec<PathBuf> = sent
+        .iter()
+        .map(|path| root.join(path))
+        .filter(|path| !skip(path))
+        .filter(|path| {
+            fs::symlink_metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .map_or(false, |modified| modified >= started)
+        })
+        .collect();
+    changed.sort();
+    changed
+}
+
+/// Runs `copy` until none of the files it sent changes while it runs, at most `MAX_ATTEMPTS`
+/// times. The files sent are relative to `root`. rsync only sends files that differ from the
+/// destination, so every attempt after the first retries just the files that changed or
+/// vanished during the previous one. Files that still change during the last attempt are
+/// returned as warnings, their copies may be half-written.
+pub fn with_retries(
+    root: &Path,
+    skip: &dyn Fn(&Path) -> bool,
+    mut copy: impl FnMut() -> Result<Copied, Error>,
+) -> Result<Copied, Error> {
+    let mut attempt = 1;
+    let mut transfer = Transfer::default();
+
+    loop {
+        let started = SystemTime::now();
+        let mut copied = copy()?;
+        transfer += copied.transfer;
+        copied.transfer = transfer;
+        let changed_files = changed_since(root, &copied.sent, started, skip);
+
+        if changed_files.is_empty() && !copied.vanished {
+            return Ok(copied);
+        }
+
+        if attempt == MAX_ATTEMPTS {
+            copied.warnings.extend(
+                changed_files
+                    .iter()
+                    .map(|path| format!("{} changed during the transfer", path.display())),
+            );
+            return Ok(copied);
+        }
+
+        info!(
+            "Retrying transfer of {}, {} files changed during attempt {attempt}",
+            root.display(),
+            changed_files.len()
+        );
+        attempt += 1;
+    }
+}
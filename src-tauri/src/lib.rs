@@ -1,14 +1,21 @@
 use log::error;
 use std::io::{self, Write};
+use std::time::Duration;
 
 pub mod commands;
+pub mod control;
 pub mod jobs;
+pub mod metrics;
 pub mod models;
+pub mod notification;
 pub mod ssh;
 
 #[cfg(test)]
 mod tests;
 
+/// Time terminated jobs get to stop their transfers before the app exits
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(15);
+
 pub async fn graceful_exit(state: &models::app::MutexState) {
     if let Some(connection) = state.connection.lock().await.take() {
         if let Err(e) = connection.sftp_client.close().await {
@@ -32,7 +39,8 @@ pub async fn graceful_exit(state: &models::app::MutexState) {
 
     print!("\r⏳ Terminating jobs...      "); // add trailing blankspaces to overwrite previous loading output
     io::stdout().flush().expect("failed to flush stdout");
-    jobs::backup::terminate_all(&mut jobs, &mut pool);
+    jobs::backup::terminate_all(&mut jobs, &pool);
+    pool.shutdown(SHUTDOWN_TIMEOUT);
     println!("\r\x1B[1A\x1B[2K");
     io::stdout().flush().expect("failed to flush stdout");
 
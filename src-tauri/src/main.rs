@@ -1,6 +1,7 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
-use back_me_up::{jobs, models::app::MutexState};
+use back_me_up::jobs::{self, history::History};
+use back_me_up::models::app::MutexState;
 use log::{warn, LevelFilter};
 use std::fs::DirBuilder;
 use std::path::PathBuf;
@@ -10,6 +11,7 @@ use tauri::{CustomMenuItem, SystemTrayMenu, SystemTrayMenuItem};
 use tauri_plugin_log::fern::colors::ColoredLevelConfig;
 use tauri_plugin_log::LogTarget;
 
+mod daemon;
 mod event;
 mod handlers;
 mod tray;
@@ -37,8 +39,10 @@ fn main() {
     let pool = jobs::Pool::new(None);
     let init_cache_dir: Arc<Mutex<PathBuf>> = Arc::new(Mutex::default());
     let init_log_dir: Arc<Mutex<PathBuf>> = Arc::new(Mutex::default());
+    let init_history: Arc<Mutex<History>> = Arc::new(Mutex::default());
     let app_cache_dir_for_setup = Arc::clone(&init_cache_dir);
     let app_log_dir_for_setup = Arc::clone(&init_cache_dir);
+    let history_for_setup = Arc::clone(&init_history);
 
     tauri::Builder::default()
         .setup(move |app| {
@@ -77,6 +81,17 @@ fn main() {
             *app_log_dir_for_setup
                 .lock()
                 .expect("could not lock app cache dir on setup") = app_log_dir;
+
+            match app.path_resolver().app_data_dir() {
+                Some(app_data_dir) => {
+                    *history_for_setup
+                        .lock()
+                        .expect("could not lock history on setup") = History::new(app_data_dir);
+                }
+                None => warn!("Could not find app data directory, runs will not be recorded"),
+            }
+
+            daemon::watch(app.handle());
             Ok(())
         })
         .plugin(
@@ -94,6 +109,8 @@ fn main() {
             pool: Mutex::new(pool),
             app_cache_dir: Arc::clone(&init_cache_dir),
             app_log_dir: Arc::clone(&init_log_dir),
+            history: Arc::clone(&init_history),
+            metrics: Arc::new(Mutex::default()),
         })
         .invoke_handler(tauri::generate_handler![
             handlers::list_home_folders,
@@ -107,7 +124,17 @@ fn main() {
             handlers::drop_pool,
             handlers::reset,
             handlers::get_client_name,
-            handlers::check_job_status
+            handlers::is_valid_server_address,
+            handlers::check_host_key,
+            handlers::trust_host_key,
+            handlers::install_ssh_key,
+            handlers::check_job_status,
+            handlers::list_jobs,
+            handlers::cancel_job,
+            handlers::check_destination_status,
+            handlers::list_runs,
+            handlers::latest_successful_run,
+            handlers::daemon_status
         ])
         .system_tray(app_tray)
         .on_system_tray_event(tray::handle_system_tray_event)
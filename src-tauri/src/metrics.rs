@@ -0,0 +1,227 @@
+use crate::control::{Incoming, Request, Response};
+use crate::models::history::{Run, RunStatus};
+use log::error;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Counters of the runs finished by this process, per client path.
+#[derive(Default)]
+pub struct Metrics {
+    backups: BTreeMap<String, Counters>,
+}
+
+#[derive(Default)]
+struct Counters {
+    runs: u64,
+    failures: u64,
+    files: u64,
+    bytes: u64,
+}
+
+impl Metrics {
+    /// Counts a finished run.
+    pub fn observe(&mut self, run: &Run) {
+        let counters = self.backups.entry(run.client_path.clone()).or_default();
+        counters.runs += 1;
+        counters.files += run.transfer.files;
+        counters.bytes += run.transfer.bytes;
+
+        if run.status == RunStatus::Failure {
+            counters.failures += 1;
+        }
+    }
+}
+
+/// Values read from the daemon when the metrics are scraped.
+pub struct Gauges {
+    pub running_jobs: usize,
+    pub queued_jobs: usize,
+    pub failed_jobs: usize,
+    pub connected: bool,
+    pub paused: bool,
+    /// Client path and unix timestamp of the latest successful run of every backup
+    pub last_success: Vec<(String, Option<u64>)>,
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// The metrics in the Prometheus text format.
+#[must_use]
+pub fn render(metrics: &Metrics, gauges: &Gauges) -> String {
+    let mut text = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, samples: Vec<(String, String)>| {
+        _ = writeln!(text, "# HELP {name} {help}");
+        _ = writeln!(text, "# TYPE {name} {kind}");
+        for (labels, value) in samples {
+            _ = writeln!(text, "{name}{labels} {value}");
+        }
+    };
+    let single = |value: usize| vec![(String::new(), value.to_string())];
+    let flag = |value: bool| single(usize::from(value));
+    let per_backup = |value: fn(&Counters) -> u64| {
+        metrics
+            .backups
+            .iter()
+            .map(|(path, counters)| {
+                (
+                    format!("{{client_path=\"{}\"}}", escape(path)),
+                    value(counters).to_string(),
+                )
+            })
+            .collect()
+    };
+
+    metric(
+        "bmu_running_jobs",
+        "gauge",
+        "Jobs currently running, watchers included",
+        single(gauges.running_jobs),
+    );
+    metric(
+        "bmu_queued_jobs",
+        "gauge",
+        "Transfers waiting for a free slot of the pool",
+        single(gauges.queued_jobs),
+    );
+    metric(
+        "bmu_failed_jobs",
+        "gauge",
+        "Jobs whose latest run failed",
+        single(gauges.failed_jobs),
+    );
+    metric(
+        "bmu_connected",
+        "gauge",
+        "Whether the daemon holds a connection to the server",
+        flag(gauges.connected),
+    );
+    metric(
+        "bmu_paused",
+        "gauge",
+        "Whether background backups are paused",
+        flag(gauges.paused),
+    );
+    metric(
+        "bmu_last_success_timestamp_seconds",
+        "gauge",
+        "Unix timestamp of the latest successful run of a backup",
+        gauges
+            .last_success
+            .iter()
+            .filter_map(|(path, timestamp)| {
+                timestamp.map(|timestamp| {
+                    (
+                        format!("{{client_path=\"{}\"}}", escape(path)),
+                        timestamp.to_string(),
+                    )
+                })
+            })
+            .collect(),
+    );
+    metric(
+        "bmu_runs_total",
+        "counter",
+        "Finished runs of a backup",
+        per_backup(|counters| counters.runs),
+    );
+    metric(
+        "bmu_failures_total",
+        "counter",
+        "Failed runs of a backup",
+        per_backup(|counters| counters.failures),
+    );
+    metric(
+        "bmu_transferred_files_total",
+        "counter",
+        "Files transferred by a backup",
+        per_backup(|counters| counters.files),
+    );
+    metric(
+        "bmu_transferred_bytes_total",
+        "counter",
+        "Bytes transferred by a backup",
+        per_backup(|counters| counters.bytes),
+    );
+
+    text
+}
+
+/// Serves `/metrics` on `port` of localhost from a separate thread and returns the address it
+/// is bound to. Every scrape is forwarded to `sender` as `Request::Metrics`, so the daemon
+/// renders them from its own state.
+pub fn serve(
+    port: u16,
+    sender: tokio::sync::mpsc::Sender<Incoming>,
+) -> Result<SocketAddr, io::Error> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    let address = listener.local_addr()?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream.and_then(|stream| handle_connection(&stream, &sender));
+
+            if let Err(e) = result {
+                error!("Could not serve metrics: {e:?}");
+            }
+        }
+    });
+
+    Ok(address)
+}
+
+fn handle_connection(
+    stream: &TcpStream,
+    sender: &tokio::sync::mpsc::Sender<Incoming>,
+) -> Result<(), io::Error> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // the headers are of no interest, but have to be read before responding
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+    let (status, body) = if path == "/metrics" {
+        let (response_sender, response_receiver) = mpsc::channel();
+        let response = sender
+            .blocking_send((Request::Metrics, response_sender))
+            .ok()
+            .and_then(|_| response_receiver.recv_timeout(TIMEOUT).ok());
+
+        match response {
+            Some(Response::Metrics(text)) => ("200 OK", text),
+            _ => (
+                "503 Service Unavailable",
+                String::from("The daemon did not render the metrics\n"),
+            ),
+        }
+    } else {
+        (
+            "404 Not Found",
+            String::from("Metrics are served on /metrics\n"),
+        )
+    };
+
+    let mut writer = stream;
+    write!(
+        writer,
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
@@ -1,4 +1,7 @@
-use crate::jobs::{self, Pool};
+use super::notification::Notifications;
+use super::retry::RetryPolicy;
+use crate::jobs::{self, history::History, Pool};
+use crate::metrics::Metrics;
 use crate::ssh::connect::Connection;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -11,7 +14,7 @@ pub enum Error {
     MissingConnection(String),
     Config(String),
     JobPool(String),
-    Storage(String)
+    Storage(String),
 }
 
 impl From<PoisonError<MutexGuard<'_, Option<Config>>>> for Error {
@@ -32,13 +35,19 @@ impl From<PoisonError<MutexGuard<'_, HashMap<String, usize>>>> for Error {
     }
 }
 
+impl From<PoisonError<MutexGuard<'_, History>>> for Error {
+    fn from(e: PoisonError<MutexGuard<History>>) -> Self {
+        Self::Storage(e.to_string())
+    }
+}
+
 impl From<PoisonError<MutexGuard<'_, PathBuf>>> for Error {
     fn from(e: PoisonError<MutexGuard<PathBuf>>) -> Self {
         Self::Storage(e.to_string())
     }
 }
 
-#[derive(TS, Serialize, Deserialize, Clone, Debug)]
+#[derive(TS, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 #[ts(export)]
 pub struct Config {
     pub client_name: String,
@@ -46,6 +55,37 @@ pub struct Config {
     pub server_address: String,
     pub server_port: u16,
     pub allow_background_backup: bool,
+    /// Private key used instead of the default keys of the ssh agent
+    #[serde(default)]
+    #[ts(optional)]
+    pub identity_file: Option<String>,
+    /// Comma separated chain of jump hosts, as accepted by `ssh -J`
+    #[serde(default)]
+    #[ts(optional)]
+    pub proxy_jump: Option<String>,
+    /// Extra ssh options in `Key=Value` form, as accepted by `ssh -o`
+    #[serde(default)]
+    #[ts(optional)]
+    pub ssh_options: Option<Vec<String>>,
+    /// known_hosts file holding the host key confirmed during setup
+    #[serde(default)]
+    #[ts(optional)]
+    pub known_hosts_file: Option<String>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub notifications: Option<Notifications>,
+    /// Localhost port the daemon serves Prometheus metrics on
+    #[serde(default)]
+    #[ts(optional)]
+    pub metrics_port: Option<u16>,
+    /// How failed one-off backups are run again, the default policy if not given
+    #[serde(default)]
+    #[ts(optional)]
+    pub retry: Option<RetryPolicy>,
+    /// Transfers running at the same time, further ones wait by priority. 4 if not given
+    #[serde(default)]
+    #[ts(optional)]
+    pub max_transfers: Option<usize>,
 }
 
 pub struct MutexState {
@@ -56,4 +96,6 @@ pub struct MutexState {
     pub pool: Mutex<jobs::Pool>,
     pub app_cache_dir: Arc<Mutex<PathBuf>>,
     pub app_log_dir: Arc<Mutex<PathBuf>>,
+    pub history: Arc<Mutex<History>>,
+    pub metrics: Arc<Mutex<Metrics>>,
 }
@@ -3,26 +3,180 @@ use std::fmt::Display;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
-#[derive(TS, Serialize, Deserialize, Clone)]
+#[derive(TS, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[ts(export)]
 pub struct Location {
     pub entity_name: String,
     pub path: String,
 }
 
-#[derive(TS, Serialize, Deserialize, Clone)]
+#[derive(TS, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[ts(export)]
 pub struct Options {
     pub use_client_directory: bool,
 }
 
-#[derive(TS, Serialize, Deserialize, Clone)]
+/// A target that a backup is mirrored to.
+#[derive(TS, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[ts(export)]
+pub enum Destination {
+    /// A folder on the configured backup server, reached over ssh.
+    Server(Location),
+    /// A folder on a disk mounted on the client, e.g. an external drive.
+    Disk(Location),
+}
+
+impl Destination {
+    #[must_use]
+    pub const fn location(&self) -> &Location {
+        match self {
+            Self::Server(location) | Self::Disk(location) => location,
+        }
+    }
+
+    #[must_use]
+    pub fn with_path(&self, path: String) -> Self {
+        let location = Location {
+            entity_name: self.location().entity_name.clone(),
+            path,
+        };
+
+        match self {
+            Self::Server(_) => Self::Server(location),
+            Self::Disk(_) => Self::Disk(location),
+        }
+    }
+}
+
+impl Display for Destination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Server(location) => write!(f, "server:{}", location.path),
+            Self::Disk(location) => write!(f, "disk:{}", location.path),
+        }
+    }
+}
+
+/// A shell command run before or after a backup.
+#[derive(TS, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub struct Hook {
+    /// Run with `sh -c`
+    pub command: String,
+    /// Seconds before the command is killed, 300 if not given
+    #[serde(default)]
+    #[ts(optional)]
+    pub timeout_seconds: Option<u32>,
+}
+
+const fn abort_on_pre_failure() -> bool {
+    true
+}
+
+/// Commands run around one-off backups, e.g. to dump a database or stop a service. They are not
+/// run for the changes picked up by a watcher, a hook writing into the watched folder would
+/// trigger the watcher again.
+#[derive(TS, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub struct Hooks {
+    #[serde(default)]
+    #[ts(optional)]
+    pub pre: Option<Hook>,
+    /// Runs after the backup, even if it or the pre hook failed
+    #[serde(default)]
+    #[ts(optional)]
+    pub post: Option<Hook>,
+    /// Skips the backup if the pre hook fails, otherwise the run only ends partial
+    #[serde(default = "abort_on_pre_failure")]
+    pub abort_on_pre_failure: bool,
+}
+
+#[derive(TS, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[ts(export)]
 pub struct Backup {
     pub client_location: Location,
     pub server_location: Location,
+    /// Only written by earlier versions, the history keeps the runs of a backup
     pub latest_run: Option<u64>,
     pub options: Option<Options>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub mirrors: Option<Vec<Destination>>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub hooks: Option<Hooks>,
+    /// SQLite databases inside the client location, relative to it. They are uploaded from a
+    /// snapshot, since copying a database while it is written to may corrupt the copy.
+    #[serde(default)]
+    #[ts(optional)]
+    pub databases: Option<Vec<String>>,
+}
+
+impl Backup {
+    /// Whether both backups are configured the same. `latest_run` is left out, it is not part of
+    /// the configuration.
+    #[must_use]
+    pub fn same_config(&self, other: &Self) -> bool {
+        let without_run = |backup: &Self| Self {
+            latest_run: None,
+            ..backup.clone()
+        };
+
+        without_run(self) == without_run(other)
+    }
+
+    /// Every destination of the backup, starting with the primary server location.
+    #[must_use]
+    pub fn destinations(&self) -> Vec<Destination> {
+        let mut destinations = vec![Destination::Server(self.server_location.clone())];
+
+        if let Some(mirrors) = &self.mirrors {
+            destinations.extend(mirrors.iter().cloned());
+        }
+
+        destinations
+    }
+
+    /// The path handed to rsync as source. A trailing slash makes rsync copy the content of a
+    /// directory rather than the directory itself.
+    #[must_use]
+    pub fn client_source(&self, is_directory: bool) -> String {
+        self.options.as_ref().map_or_else(
+            || self.client_location.path.clone(),
+            |options| {
+                if options.use_client_directory || !is_directory {
+                    self.client_location.path.clone()
+                } else {
+                    format!("{}/", self.client_location.path)
+                }
+            },
+        )
+    }
+
+    /// Rsync arguments excluding the databases of the backup and the files SQLite keeps next to
+    /// them, which are uploaded from a snapshot instead.
+    #[must_use]
+    pub fn database_excludes(&self, is_directory: bool) -> Vec<String> {
+        let databases = match &self.databases {
+            Some(databases) if is_directory => databases,
+            _ => return vec![],
+        };
+        // patterns starting with a slash are matched from the root of the transfer
+        let root = if self.client_source(is_directory).ends_with('/') {
+            String::new()
+        } else {
+            format!("/{}", self.client_location.entity_name)
+        };
+
+        databases
+            .iter()
+            .flat_map(|database| {
+                ["", "-wal", "-shm", "-journal"]
+                    .iter()
+                    .map(move |suffix| format!("--exclude={root}/{database}{suffix}"))
+            })
+            .collect()
+    }
 }
 
 impl Display for Backup {
@@ -31,6 +185,14 @@ impl Display for Backup {
             f,
             "{} -> {}",
             self.client_location.path, self.server_location.path
-        )
+        )?;
+
+        if let Some(mirrors) = &self.mirrors {
+            for mirror in mirrors {
+                write!(f, ", {mirror}")?;
+            }
+        }
+
+        Ok(())
     }
 }
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use std::ops::AddAssign;
+use ts_rs::TS;
+
+/// What started a run.
+#[derive(TS, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum Trigger {
+    Manual,
+    Watcher,
+    Schedule,
+}
+
+#[derive(TS, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum RunStatus {
+    /// The run has started, but not finished yet
+    Running,
+    Success,
+    /// The run finished, but some files were skipped, which its error lists
+    Partial,
+    Failure,
+    /// The process running it exited before the run finished
+    Interrupted,
+    /// The run was stopped on request
+    Cancelled,
+}
+
+/// Statistics reported by a transfer.
+#[derive(TS, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[ts(export)]
+pub struct Transfer {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+impl AddAssign for Transfer {
+    fn add_assign(&mut self, other: Self) {
+        self.files += other.files;
+        self.bytes += other.bytes;
+    }
+}
+
+#[derive(TS, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub enum HookStage {
+    Pre,
+    Post,
+}
+
+/// A hook executed as part of a run.
+#[derive(TS, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub struct HookRun {
+    pub stage: HookStage,
+    pub command: String,
+    /// None if the command was killed or could not be started
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    /// Combined stdout and stderr, truncated to the last few kilobytes
+    pub output: String,
+}
+
+impl HookRun {
+    #[must_use]
+    pub const fn succeeded(&self) -> bool {
+        matches!(self.exit_code, Some(0))
+    }
+}
+
+/// A single run of a backup job, as stored in the history.
+#[derive(TS, Serialize, Deserialize, Clone, Debug)]
+#[ts(export)]
+pub struct Run {
+    pub job_id: String,
+    pub client_path: String,
+    pub server_path: String,
+    pub trigger: Trigger,
+    /// Unix timestamp in seconds
+    pub started_at: u64,
+    /// Unix timestamp in seconds
+    pub ended_at: u64,
+    pub transfer: Transfer,
+    pub status: RunStatus,
+    pub error: Option<String>,
+    /// Process that recorded the run, used to detect interrupted runs
+    #[serde(default)]
+    #[ts(optional)]
+    pub pid: Option<u32>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub hooks: Option<Vec<HookRun>>,
+}
@@ -1,3 +1,6 @@
 pub mod app;
 pub mod backup;
+pub mod history;
+pub mod notification;
+pub mod retry;
 pub mod storage;
@@ -0,0 +1,105 @@
+use super::history::Run;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// Where to report failing and recovering backups, and optionally finished ones.
+#[derive(TS, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[ts(export)]
+pub struct Notifications {
+    /// Shows a notification on the desktop of the client
+    #[serde(default)]
+    pub desktop: bool,
+    /// Also reports one-off backups that finished without failing
+    #[serde(default)]
+    pub completions: bool,
+    /// Receives every event as a json POST
+    #[serde(default)]
+    #[ts(optional)]
+    pub webhook_url: Option<String>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub email: Option<Email>,
+    /// Minutes a backup has to keep failing before it is reported again
+    #[serde(default)]
+    #[ts(optional)]
+    pub failing_threshold_minutes: Option<u32>,
+}
+
+impl Notifications {
+    /// The failing threshold in seconds, comparable to the timestamps of a run.
+    #[must_use]
+    pub fn failing_threshold(&self) -> Option<u64> {
+        self.failing_threshold_minutes
+            .map(|minutes| u64::from(minutes) * 60)
+    }
+}
+
+#[derive(TS, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[ts(export)]
+pub struct Email {
+    /// `smtp://host:port`, or `smtps://host:port` for implicit tls
+    pub server_url: String,
+    pub from: String,
+    pub to: Vec<String>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub username: Option<String>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub password: Option<String>,
+}
+
+/// Something about a backup worth telling the user, sent as json to webhooks.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum Event {
+    /// The backup failed after it last succeeded
+    Failed(Run),
+    /// The backup succeeded after it failed
+    Recovered(Run),
+    /// The backup has kept failing since the given unix timestamp for longer than the threshold
+    StillFailing { run: Run, since: u64 },
+    /// A one-off backup finished, maybe skipping some files
+    Completed(Run),
+}
+
+impl Event {
+    #[must_use]
+    pub const fn run(&self) -> &Run {
+        match self {
+            Self::Failed(run)
+            | Self::Recovered(run)
+            | Self::StillFailing { run, .. }
+            | Self::Completed(run) => run,
+        }
+    }
+
+    #[must_use]
+    pub fn title(&self) -> String {
+        let path = &self.run().client_path;
+
+        match self {
+            Self::Failed(_) => format!("Backup of {path} failed"),
+            Self::Recovered(_) => format!("Backup of {path} recovered"),
+            Self::StillFailing { .. } => format!("Backup of {path} is still failing"),
+            Self::Completed(_) => format!("Backup of {path} completed"),
+        }
+    }
+
+    #[must_use]
+    pub fn message(&self) -> String {
+        let run = self.run();
+
+        match self {
+            Self::Failed(_) => run.error.clone().unwrap_or_default(),
+            Self::Recovered(_) | Self::Completed(_) => format!(
+                "Backed up {} files to {}",
+                run.transfer.files, run.server_path
+            ),
+            Self::StillFailing { since, .. } => format!(
+                "Failing for {} minutes\n{}",
+                run.ended_at.saturating_sub(*since) / 60,
+                run.error.clone().unwrap_or_default()
+            ),
+        }
+    }
+}
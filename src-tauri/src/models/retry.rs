@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use ts_rs::TS;
+
+/// How failed one-off backups are run again. They are not by default, since running a backup
+/// again also runs its hooks again.
+#[derive(TS, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub struct RetryPolicy {
+    /// Attempts including the first one, 1 disables retries
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every further one
+    pub initial_delay_seconds: u32,
+    /// Upper bound of the delay
+    pub max_delay_seconds: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_delay_seconds: 30,
+            max_delay_seconds: 600,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay after the failed `attempt`, starting at 1. `jitter` between 0 and 1 picks a delay
+    /// between half and all of the exponential backoff, so jobs that failed together don't
+    /// retry together.
+    #[must_use]
+    pub fn delay(&self, attempt: u32, jitter: f64) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let backoff = u64::from(self.initial_delay_seconds)
+            .saturating_mul(1 << exponent)
+            .min(u64::from(self.max_delay_seconds));
+        let millis = backoff * 1000;
+
+        // at most half of the delay, which fits into f64 without loss
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            clippy::cast_precision_loss
+        )]
+        let jittered = (millis as f64 / 2.0 * jitter.clamp(0.0, 1.0)) as u64;
+
+        Duration::from_millis(millis / 2 + jittered)
+    }
+}
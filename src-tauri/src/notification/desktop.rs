@@ -0,0 +1,22 @@
+use super::{Error, Notifier};
+use crate::models::notification::Event;
+use tauri::api::notification::Notification;
+
+/// The bundle identifier of the app, notifications are shown under its name.
+pub const IDENTIFIER: &str = "BackMeUp";
+
+/// Notifications shown by the desktop of the client through the notification api of Tauri. The
+/// daemon is built with Tauri as well, so it shows them the same way while the app is closed.
+pub struct Desktop {
+    pub identifier: String,
+}
+
+impl Notifier for Desktop {
+    fn notify(&self, event: &Event) -> Result<(), Error> {
+        Notification::new(&self.identifier)
+            .title(event.title())
+            .body(event.message())
+            .show()
+            .map_err(|e| Error::Command(format!("Could not show desktop notification: {e}")))
+    }
+}
@@ -0,0 +1,110 @@
+use super::{run_with_input, Error, Notifier};
+use crate::models::notification::{Email, Event};
+use log::warn;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const TIMEOUT_SECONDS: &str = "30";
+
+static CREDENTIALS_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// A curl config file with the smtp credentials, removed once it is dropped. Other users can
+/// read the arguments of a process, so the password is not passed to curl as one.
+struct Credentials {
+    path: PathBuf,
+}
+
+impl Credentials {
+    fn write(username: &str, password: &str) -> io::Result<Self> {
+        let path = env::temp_dir().join(format!(
+            "bmu_smtp_{}_{}.conf",
+            process::id(),
+            CREDENTIALS_FILES.fetch_add(1, Ordering::SeqCst)
+        ));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        let mut file = options.open(&path)?;
+        let credentials = Self { path };
+        let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+        writeln!(file, "user = \"{}:{}\"", escape(username), escape(password))?;
+
+        Ok(credentials)
+    }
+}
+
+impl Drop for Credentials {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Could not remove smtp credentials: {e}");
+        }
+    }
+}
+
+/// Sends every event as a plain text email through the smtp server of `email`.
+pub struct Smtp {
+    pub email: Email,
+}
+
+impl Smtp {
+    fn message(&self, event: &Event) -> String {
+        let headers = [
+            format!("From: {}", self.email.from),
+            format!("To: {}", self.email.to.join(", ")),
+            format!("Subject: {}", event.title()),
+            String::from("Content-Type: text/plain; charset=utf-8"),
+        ];
+
+        // lines starting with a dot are escaped by curl
+        format!(
+            "{}\r\n\r\n{}\r\n",
+            headers.join("\r\n"),
+            event.message().replace('\n', "\r\n")
+        )
+    }
+}
+
+impl Notifier for Smtp {
+    fn notify(&self, event: &Event) -> Result<(), Error> {
+        let mut command = Command::new("curl");
+        command.args([
+            "--silent",
+            "--show-error",
+            "--max-time",
+            TIMEOUT_SECONDS,
+            "--url",
+            &self.email.server_url,
+            "--mail-from",
+            &self.email.from,
+        ]);
+
+        for recipient in &self.email.to {
+            command.args(["--mail-rcpt", recipient]);
+        }
+
+        // kept until curl has finished
+        let credentials = match &self.email.username {
+            Some(username) => {
+                let password = self.email.password.as_deref().unwrap_or_default();
+                let credentials = Credentials::write(username, password)?;
+                command.arg("--config").arg(&credentials.path);
+                Some(credentials)
+            }
+            None => None,
+        };
+
+        command.args(["--upload-file", "-"]);
+
+        let result = run_with_input(&mut command, self.message(event).as_bytes());
+        drop(credentials);
+        result
+    }
+}
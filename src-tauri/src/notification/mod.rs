@@ -0,0 +1,158 @@
+use crate::models::history::{Run, RunStatus, Trigger};
+use crate::models::notification::{Event, Notifications};
+use log::{error, info};
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+pub mod desktop;
+pub mod email;
+pub mod webhook;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The command sending the notification failed
+    Command(String),
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// A place failing, recovering and finished backups are reported to.
+pub trait Notifier {
+    fn notify(&self, event: &Event) -> Result<(), Error>;
+}
+
+/// Every notifier enabled in `notifications`.
+#[must_use]
+pub fn from_config(notifications: &Notifications) -> Vec<Box<dyn Notifier + Send>> {
+    let mut notifiers: Vec<Box<dyn Notifier + Send>> = vec![];
+
+    if notifications.desktop {
+        notifiers.push(Box::new(desktop::Desktop {
+            identifier: String::from(desktop::IDENTIFIER),
+        }));
+    }
+
+    if let Some(url) = &notifications.webhook_url {
+        notifiers.push(Box::new(webhook::Webhook { url: url.clone() }));
+    }
+
+    if let Some(email) = &notifications.email {
+        if !email.server_url.is_empty() && !email.to.is_empty() {
+            notifiers.push(Box::new(email::Smtp {
+                email: email.clone(),
+            }));
+        }
+    }
+
+    notifiers
+}
+
+/// The event to report once `run` has finished, given the runs stored before it. Runs belong to
+/// the same backup when both their client and server path match, so two backups of a folder to
+/// different destinations keep their own streak. Only the first
+/// failure is reported, further failures are reported once they span `failing_threshold`
+/// seconds, so a watcher failing on every change does not flood the user. With `completions`,
+/// one-off backups are reported once they finished as well, runs of watchers are not.
+#[must_use]
+pub fn event_for(
+    run: &Run,
+    previous: &[Run],
+    failing_threshold: Option<u64>,
+    completions: bool,
+) -> Option<Event> {
+    let finished: Vec<&Run> = previous
+        .iter()
+        .filter(|stored| {
+            stored.client_path == run.client_path
+                && stored.server_path == run.server_path
+                && matches!(
+                    stored.status,
+                    RunStatus::Success | RunStatus::Partial | RunStatus::Failure
+                )
+        })
+        .collect();
+    let last_failed = finished
+        .last()
+        .map_or(false, |last| last.status == RunStatus::Failure);
+
+    match run.status {
+        RunStatus::Success | RunStatus::Partial if last_failed => {
+            Some(Event::Recovered(run.clone()))
+        }
+        RunStatus::Success | RunStatus::Partial
+            if completions && run.trigger == Trigger::Manual =>
+        {
+            Some(Event::Completed(run.clone()))
+        }
+        RunStatus::Failure if !last_failed => Some(Event::Failed(run.clone())),
+        RunStatus::Failure => {
+            let threshold = failing_threshold?;
+            let streak_start = finished
+                .iter()
+                .rposition(|stored| {
+                    matches!(stored.status, RunStatus::Success | RunStatus::Partial)
+                })
+                .map_or(0, |index| index + 1);
+            let since = finished[streak_start].started_at;
+            let last_ended_at = finished.last().map_or(since, |last| last.ended_at);
+
+            if run.ended_at.saturating_sub(since) >= threshold
+                && last_ended_at.saturating_sub(since) < threshold
+            {
+                Some(Event::StillFailing {
+                    run: run.clone(),
+                    since,
+                })
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Sends `event` to every notifier of `notifications` from a separate thread, so a slow server
+/// does not hold up the job. Errors are only logged.
+pub fn dispatch(notifications: &Notifications, event: Event) {
+    let notifiers = from_config(notifications);
+    if notifiers.is_empty() {
+        return;
+    }
+
+    thread::spawn(move || {
+        info!("Sending notification: {}", event.title());
+        for notifier in notifiers {
+            if let Err(e) = notifier.notify(&event) {
+                error!("Could not send notification: {e:?}");
+            }
+        }
+    });
+}
+
+/// Runs `command` with `input` written to its stdin.
+fn run_with_input(command: &mut Command, input: &[u8]) -> Result<(), Error> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input)?;
+    }
+
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        Err(Error::Command(format!("{stdout}\n{stderr}")))
+    }
+}
@@ -0,0 +1,34 @@
+use super::{run_with_input, Error, Notifier};
+use crate::models::notification::Event;
+use std::process::Command;
+
+const TIMEOUT_SECONDS: &str = "10";
+
+/// POSTs every event as json to `url`.
+pub struct Webhook {
+    pub url: String,
+}
+
+impl Notifier for Webhook {
+    fn notify(&self, event: &Event) -> Result<(), Error> {
+        let body = serde_json::to_vec(event).map_err(|e| Error::Command(e.to_string()))?;
+
+        run_with_input(
+            Command::new("curl").args([
+                "--silent",
+                "--show-error",
+                "--fail",
+                "--max-time",
+                TIMEOUT_SECONDS,
+                "--request",
+                "POST",
+                "--header",
+                "Content-Type: application/json",
+                "--data-binary",
+                "@-",
+                &self.url,
+            ]),
+            &body,
+        )
+    }
+}
@@ -0,0 +1,348 @@
+use crate::models::app::Config;
+use glob::Pattern;
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::net::{IpAddr, Ipv6Addr};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const SCHEMES: [&str; 3] = ["http://", "https://", "ssh://"];
+
+/// A server address resolved from the config. This is the single place where the address is
+/// normalized, so that openssh, rsync and ssh all connect to the same host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    pub user: String,
+    /// The host given to ssh, a `Host` alias is kept so that ssh applies all of its options
+    pub host: String,
+    /// The host the server is reached at, the `HostName` of an alias. Host keys are stored for it
+    pub host_name: String,
+    pub port: u16,
+    pub identity_file: Option<PathBuf>,
+    pub proxy_jump: Option<String>,
+    pub options: Vec<String>,
+    pub known_hosts_file: Option<PathBuf>,
+}
+
+/// The parts of a `Host` block in `~/.ssh/config` that are relevant for a connection.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HostAlias {
+    pub host_name: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub identity_file: Option<PathBuf>,
+    pub proxy_jump: Option<String>,
+}
+
+impl Address {
+    /// Resolves the address in `config` against the `Host` aliases of `~/.ssh/config`.
+    #[must_use]
+    pub fn resolve(config: &Config) -> Self {
+        let host = normalize_host(&config.server_address);
+        let alias = ssh_config().and_then(|contents| parse_host_alias(&contents, &host));
+
+        Self::with_alias(config, alias)
+    }
+
+    /// The address in `config`, which names the `alias` if there is one. The alias takes
+    /// precedence over the values in `config` for every option it defines, except for the
+    /// identity file and jump hosts which are only read from the alias when none are configured.
+    /// ssh is still given the alias itself, so that options not read here apply as well.
+    #[must_use]
+    pub fn with_alias(config: &Config, alias: Option<HostAlias>) -> Self {
+        let host = normalize_host(&config.server_address);
+        let identity_file = config
+            .identity_file
+            .as_ref()
+            .filter(|path| !path.trim().is_empty())
+            .map(|path| expand_home(path.trim()));
+        let proxy_jump = config
+            .proxy_jump
+            .as_ref()
+            .map(|jump| jump.trim().to_string())
+            .filter(|jump| !jump.is_empty());
+        let known_hosts_file = config
+            .known_hosts_file
+            .as_ref()
+            .filter(|path| !path.trim().is_empty())
+            .map(PathBuf::from);
+        let mut options: Vec<String> = config
+            .ssh_options
+            .iter()
+            .flatten()
+            .map(|option| option.trim().to_string())
+            .filter(|option| !option.is_empty())
+            .collect();
+
+        // the app managed file is checked first and the user's own file is kept as a fallback
+        if let Some(known_hosts_file) = &known_hosts_file {
+            options.push(format!(
+                "UserKnownHostsFile=\"{}\" ~/.ssh/known_hosts",
+                known_hosts_file.display()
+            ));
+        }
+
+        alias.map_or_else(
+            || Self {
+                user: config.username.clone(),
+                host: host.clone(),
+                host_name: host.clone(),
+                port: config.server_port,
+                identity_file: identity_file.clone(),
+                proxy_jump: proxy_jump.clone(),
+                options: options.clone(),
+                known_hosts_file: known_hosts_file.clone(),
+            },
+            |alias| Self {
+                user: alias.user.unwrap_or_else(|| config.username.clone()),
+                host: host.clone(),
+                host_name: alias
+                    .host_name
+                    .map_or_else(|| host.clone(), |host_name| normalize_host(&host_name)),
+                port: alias.port.unwrap_or(config.server_port),
+                identity_file: identity_file.clone().or(alias.identity_file),
+                proxy_jump: proxy_jump.clone().or(alias.proxy_jump),
+                options: options.clone(),
+                known_hosts_file: known_hosts_file.clone(),
+            },
+        )
+    }
+
+    /// The host as written in a `user@host:path` argument, IPv6 literals must be bracketed.
+    #[must_use]
+    pub fn bracketed_host(&self) -> String {
+        if Ipv6Addr::from_str(&self.host).is_ok() {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        }
+    }
+
+    /// Target for rsync, e.g. `user@[::1]:/path`
+    #[must_use]
+    pub fn rsync_target(&self, path: &str) -> String {
+        format!("{}@{}:{path}", self.user, self.bracketed_host())
+    }
+
+    /// Login for ssh, which takes the port as a separate argument, e.g. `user@::1`
+    #[must_use]
+    pub fn ssh_login(&self) -> String {
+        format!("{}@{}", self.user, self.host)
+    }
+
+    /// Arguments passed to every spawned ssh process.
+    #[must_use]
+    pub fn ssh_args(&self) -> Vec<String> {
+        let mut args = vec![String::from("-p"), self.port.to_string()];
+
+        if let Some(identity_file) = &self.identity_file {
+            args.push(String::from("-i"));
+            args.push(identity_file.display().to_string());
+        }
+
+        if let Some(proxy_jump) = &self.proxy_jump {
+            args.push(String::from("-J"));
+            args.push(proxy_jump.clone());
+        }
+
+        for option in &self.options {
+            args.push(String::from("-o"));
+            args.push(option.clone());
+        }
+
+        args
+    }
+
+    /// Contents of an ssh config file carrying the options that `openssh::SessionBuilder` has
+    /// no setter for. The user's own config is included afterwards, so that jump host aliases
+    /// still resolve.
+    #[must_use]
+    pub fn ssh_config_contents(&self) -> String {
+        let mut contents = format!("# Generated by Back me up\nHost {}\n", self.host);
+
+        if let Some(proxy_jump) = &self.proxy_jump {
+            contents.push_str(&format!("    ProxyJump {proxy_jump}\n"));
+        }
+
+        for option in &self.options {
+            let (key, value) = option
+                .split_once(|c: char| c == '=' || c.is_whitespace())
+                .unwrap_or((option.as_str(), ""));
+            contents.push_str(&format!("    {key} {}\n", value.trim()));
+        }
+
+        if let Some(home) = env::var_os("HOME") {
+            let user_config = PathBuf::from(home).join(".ssh/config");
+            if user_config.exists() {
+                contents.push_str(&format!(
+                    "Match all\n    Include {}\n",
+                    user_config.display()
+                ));
+            }
+        }
+
+        contents
+    }
+
+    /// The remote shell given to rsync with `-e`.
+    #[must_use]
+    pub fn rsync_shell(&self) -> String {
+        let args: Vec<String> = self
+            .ssh_args()
+            .iter()
+            .map(|arg| {
+                if arg.contains(' ') {
+                    format!("'{arg}'")
+                } else {
+                    arg.clone()
+                }
+            })
+            .collect();
+
+        format!("ssh {}", args.join(" "))
+    }
+}
+
+impl Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}:{}", self.user, self.bracketed_host(), self.port)
+    }
+}
+
+/// Strips schemes, trailing slashes and IPv6 brackets from a user provided server address.
+#[must_use]
+pub fn normalize_host(input: &str) -> String {
+    let mut host = input.trim();
+
+    for scheme in SCHEMES {
+        if let Some(stripped) = host.strip_prefix(scheme) {
+            host = stripped;
+        }
+    }
+
+    let host = host.trim_end_matches('/');
+
+    host.strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host)
+        .to_string()
+}
+
+/// Returns true if the input is an IP address, a DNS name or a `Host` alias in `~/.ssh/config`.
+#[must_use]
+pub fn is_valid(input: &str) -> bool {
+    let host = normalize_host(input);
+
+    if host.is_empty() {
+        return false;
+    }
+
+    IpAddr::from_str(&host).is_ok()
+        || is_hostname(&host)
+        || ssh_config().map_or(false, |contents| {
+            parse_host_alias(&contents, &host).is_some()
+        })
+}
+
+fn is_hostname(host: &str) -> bool {
+    host.len() <= 253
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+fn ssh_config() -> Option<String> {
+    let home = env::var_os("HOME")?;
+    fs::read_to_string(PathBuf::from(home).join(".ssh/config")).ok()
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(relative), Some(home)) => PathBuf::from(home).join(relative),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn host_matches(patterns: &[String], alias: &str) -> bool {
+    let mut matches = false;
+
+    for pattern in patterns {
+        let (negated, pattern) = pattern
+            .strip_prefix('!')
+            .map_or((false, pattern.as_str()), |p| (true, p));
+        let is_match = Pattern::new(pattern).map_or(false, |p| p.matches(alias));
+
+        if is_match && negated {
+            return false;
+        }
+        matches |= is_match;
+    }
+
+    matches
+}
+
+/// Parses the contents of an ssh config file and collects the options for `alias`. Like ssh,
+/// the first value found for an option wins. Returns `None` if no `Host` line names the alias
+/// explicitly, so wildcard blocks alone don't turn any hostname into an alias.
+#[must_use]
+pub fn parse_host_alias(contents: &str, alias: &str) -> Option<HostAlias> {
+    let mut result = HostAlias::default();
+    let mut is_named = false;
+    // options before the first Host line apply to every host
+    let mut in_matching_block = true;
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = match line.split_once(|c: char| c.is_whitespace() || c == '=') {
+            Some((key, value)) => (
+                key.to_lowercase(),
+                value
+                    .trim_start_matches(|c: char| c.is_whitespace() || c == '=')
+                    .trim(),
+            ),
+            None => continue,
+        };
+        let value = value.trim_matches('"');
+
+        match key.as_str() {
+            "host" => {
+                let patterns: Vec<String> = value.split_whitespace().map(String::from).collect();
+                in_matching_block = host_matches(&patterns, alias);
+                is_named |= patterns.iter().any(|pattern| pattern == alias);
+            }
+            // Match blocks need runtime context, ignore them
+            "match" => in_matching_block = false,
+            "hostname" if in_matching_block && result.host_name.is_none() => {
+                result.host_name = Some(value.to_string());
+            }
+            "user" if in_matching_block && result.user.is_none() => {
+                result.user = Some(value.to_string());
+            }
+            "port" if in_matching_block && result.port.is_none() => {
+                result.port = value.parse().ok();
+            }
+            "identityfile" if in_matching_block && result.identity_file.is_none() => {
+                result.identity_file = Some(expand_home(value));
+            }
+            "proxyjump" if in_matching_block && result.proxy_jump.is_none() => {
+                result.proxy_jump = Some(value.to_string());
+            }
+            _ => (),
+        }
+    }
+
+    if is_named {
+        Some(result)
+    } else {
+        None
+    }
+}
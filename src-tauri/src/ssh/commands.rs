@@ -1,15 +1,48 @@
+use super::address::Address;
 use super::Error;
+use crate::jobs::cancel::{self, Cancel};
 use crate::models::app::Config;
 use crate::models::backup::Backup;
+use crate::models::history::Transfer;
 use crate::models::storage::{Folder, Size};
 use futures::TryStreamExt;
 use log::info;
-use openssh_sftp_client::Sftp;
 use openssh_sftp_client::fs::DirEntry;
-use std::path::Path;
-use std::process::Command;
+use openssh_sftp_client::Sftp;
+use std::ops::AddAssign;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
 use std::sync::Arc;
 
+/// Exit code of rsync if some files could not be transferred
+const RSYNC_PARTIAL_TRANSFER: i32 = 23;
+/// Exit code of rsync if some files vanished before they were transferred
+const RSYNC_VANISHED_FILES: i32 = 24;
+/// Reported by rsync if a source given to it does not exist, it still exits with 23
+const RSYNC_MISSING_SOURCE: &str = "link_stat";
+
+/// A transfer rsync finished, possibly without some of the files.
+#[derive(Debug, Default)]
+pub struct Copied {
+    pub transfer: Transfer,
+    /// What rsync reported about the files it skipped
+    pub warnings: Vec<String>,
+    /// Whether files vanished while rsync was running
+    pub vanished: bool,
+    /// The files rsync sent, relative to the source directory if the source ends with a slash,
+    /// or to its parent otherwise
+    pub sent: Vec<PathBuf>,
+}
+
+impl AddAssign for Copied {
+    fn add_assign(&mut self, other: Self) {
+        self.transfer += other.transfer;
+        self.warnings.extend(other.warnings);
+        self.vanished |= other.vanished;
+        self.sent.extend(other.sent);
+    }
+}
+
 pub async fn assert_client_directory_on_server(client: &Sftp, path: &Path) -> Result<(), Error> {
     match client.open(&path).await {
         Ok(_) => Ok(()),
@@ -21,70 +54,129 @@ pub async fn assert_client_directory_on_server(client: &Sftp, path: &Path) -> Re
     }
 }
 
-pub fn backup_to_server(backup: &Backup, config: &Config, is_directory: bool) -> Result<(), Error> {
+/// Reads the transferred files and bytes from the output of `rsync --stats`.
+#[must_use]
+pub fn parse_rsync_stats(stdout: &str) -> Transfer {
+    let number = |value: &str| {
+        value
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .replace(',', "")
+            .parse::<u64>()
+            .unwrap_or_default()
+    };
+    let mut transfer = Transfer::default();
+
+    for line in stdout.lines() {
+        if let Some((key, value)) = line.split_once(':') {
+            match key.trim() {
+                // older rsync versions don't count regular files separately
+                "Number of regular files transferred" | "Number of files transferred" => {
+                    transfer.files = number(value);
+                }
+                "Total transferred file size" => transfer.bytes = number(value),
+                _ => (),
+            }
+        }
+    }
+
+    transfer
+}
+
+/// Reads the files that were sent from the output of `rsync --itemize-changes`. Every change is
+/// listed as eleven flags followed by the path, the first two flags tell that a file was sent.
+#[must_use]
+pub fn parse_itemized_files(stdout: &str) -> Vec<PathBuf> {
+    stdout
+        .lines()
+        .filter(|line| line.starts_with("<f") || line.starts_with(">f"))
+        .filter_map(|line| line.get(12..))
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Interprets the exit code of rsync. Exit codes 23 and 24 mean that rsync finished, but skipped
+/// some files, so its messages become warnings instead of failing the whole transfer. A missing
+/// source is not skipped files though, nothing was transferred. Anything else that is not a
+/// success is returned as the output of rsync.
+pub fn rsync_result(output: &Output) -> Result<Copied, String> {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let is_missing_source = stderr.lines().any(|line| {
+        line.contains(RSYNC_MISSING_SOURCE) && line.contains("No such file or directory")
+    });
+
+    match output.status.code() {
+        Some(0) => Ok(Copied {
+            transfer: parse_rsync_stats(&stdout),
+            sent: parse_itemized_files(&stdout),
+            ..Copied::default()
+        }),
+        Some(code @ (RSYNC_PARTIAL_TRANSFER | RSYNC_VANISHED_FILES)) if !is_missing_source => {
+            Ok(Copied {
+                transfer: parse_rsync_stats(&stdout),
+                // the summary line only repeats the exit code
+                warnings: stderr
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| {
+                        !line.is_empty()
+                            && !line.starts_with("rsync error:")
+                            && !line.starts_with("rsync warning:")
+                    })
+                    .map(String::from)
+                    .collect(),
+                vanished: code == RSYNC_VANISHED_FILES,
+                sent: parse_itemized_files(&stdout),
+            })
+        }
+        _ => Err(format!("{}\n{}", stdout.trim(), stderr.trim())),
+    }
+}
+
+/// Runs rsync to the server, stopping it once `cancel` is set.
+pub fn backup_to_server(
+    backup: &Backup,
+    config: &Config,
+    is_directory: bool,
+    cancel: &Cancel,
+) -> Result<Copied, Error> {
+    let address = Address::resolve(config);
+
     #[allow(unused_variables)]
-    let connection_string = format!(
-        "{}@{}:{}",
-        config.username,
-        config.server_address.replace("http://", ""),
-        backup.server_location.path
-    );
+    let connection_string = address.rsync_target(&backup.server_location.path);
 
     #[cfg(target_os = "macos")]
     #[allow(unused_variables)]
-    let connection_string = format!(
-        "{}@{}:'{}'",
-        config.username,
-        config.server_address.replace("http://", ""),
-        backup.server_location.path
-    );
-
-    let entity_location_on_client = backup.options.as_ref().map_or_else(
-        || backup.client_location.path.clone(),
-        |options| {
-            if options.use_client_directory || !is_directory {
-                backup.client_location.path.clone()
-            } else {
-                format!("{}/", backup.client_location.path.clone())
-            }
-        },
-    );
-
-    let rsync = Command::new("rsync")
-        .arg("-a")
-        .arg("-e")
-        .arg(format!("ssh -p {}", config.server_port))
-        .arg("--exclude=.*")
-        .arg(&entity_location_on_client)
-        .arg(&connection_string)
-        .output()?;
-
-    if rsync.status.success() {
-        Ok(())
-    } else {
-        let stdout = String::from_utf8_lossy(&rsync.stdout).trim().to_string();
-        let stderr = String::from_utf8_lossy(&rsync.stderr).trim().to_string();
-        let why = format!("{stdout}\n{stderr}");
-        Err(Error::Command(format!("Rsync failed: {why}")))
-    }
+    let connection_string = address.rsync_target(&format!("'{}'", backup.server_location.path));
+
+    let entity_location_on_client = backup.client_source(is_directory);
+
+    let rsync = cancel::output(
+        Command::new("rsync")
+            .arg("-a")
+            .arg("-e")
+            .arg(address.rsync_shell())
+            .arg("--exclude=.*")
+            .arg("--stats")
+            .arg("--itemize-changes")
+            .args(backup.database_excludes(is_directory))
+            .arg(&entity_location_on_client)
+            .arg(&connection_string),
+        cancel,
+    )?;
+
+    rsync_result(&rsync).map_err(|why| Error::Command(format!("Rsync failed: {why}")))
 }
 
 pub fn delete_from_server(backup: &Backup, config: &Config) -> Result<(), Error> {
-    let connection_string = format!(
-        "{}@{}",
-        config.username,
-        config.server_address.replace("http://", ""),
-    );
-
+    let address = Address::resolve(config);
     let delete_command_string = format!("rm -rf {}", backup.server_location.path);
 
     let ssh_delete = Command::new("ssh")
-        .args([
-            "-p",
-            &config.server_port.to_string(),
-            &connection_string,
-            &delete_command_string,
-        ])
+        .args(address.ssh_args())
+        .args([&address.ssh_login(), &delete_command_string])
         .output()?;
 
     if ssh_delete.status.success() {
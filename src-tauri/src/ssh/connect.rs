@@ -2,8 +2,11 @@ use super::Error;
 use log::info;
 use openssh::{KnownHosts::Strict, Session, SessionBuilder};
 use openssh_sftp_client::{Sftp, SftpOptions};
+use std::fs;
 use std::path::PathBuf;
 
+use super::address::Address;
+use super::host_key;
 use crate::models::app::Config;
 
 pub struct Connection {
@@ -32,14 +35,37 @@ impl Connection {
 /// # Panics
 /// If stdout from `whoami` command, which is made after a successful connection, does not match `username` provided in config.
 pub async fn to_server(config: Config, control_directory: PathBuf) -> Result<Session, Error> {
-    let user = config.username;
-    let host = config.server_address;
-    let port = config.server_port;
-    let session = SessionBuilder::default()
+    let address = Address::resolve(&config);
+    let mut builder = SessionBuilder::default();
+    builder
         .known_hosts_check(Strict)
-        .control_directory(control_directory)
-        .connect(&format!("ssh://{user}@{host}:{port}"))
-        .await?;
+        .control_directory(&control_directory)
+        .user(address.user.clone())
+        .port(address.port);
+
+    if let Some(identity_file) = &address.identity_file {
+        builder.keyfile(identity_file);
+    }
+
+    if address.proxy_jump.is_some() || !address.options.is_empty() {
+        let config_file = control_directory.join("ssh_config");
+        fs::write(&config_file, address.ssh_config_contents())?;
+        builder.config_file(config_file);
+    }
+
+    // the host is passed without scheme, so that IPv6 literals are not mistaken for a port
+    let session = match builder.connect(&address.host).await {
+        Ok(session) => session,
+        Err(e) => {
+            // a bare connection error is opaque, point out host key problems explicitly
+            let host_key_error = address
+                .known_hosts_file
+                .as_ref()
+                .and_then(|known_hosts| host_key::diagnose(&address, known_hosts));
+            return Err(host_key_error.unwrap_or(Error::Connection(e)));
+        }
+    };
+    let user = address.user;
     info!("Connected as user {user}");
 
     let whoami = session.command("whoami").output().await?;
@@ -0,0 +1,201 @@
+use super::address::Address;
+use super::Error;
+use serde::Serialize;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use ts_rs::TS;
+
+/// A public host key offered by the server, along with the fingerprint shown to the user.
+#[derive(TS, Serialize, Clone, Debug, PartialEq, Eq)]
+#[ts(export)]
+pub struct HostKey {
+    pub key_type: String,
+    pub fingerprint: String,
+    #[serde(skip)]
+    entry: String,
+}
+
+/// Result of checking the server against the known hosts, `keys` is empty if the server is
+/// already trusted.
+#[derive(TS, Serialize, Clone, Debug)]
+#[ts(export)]
+pub struct HostKeyScan {
+    pub known_hosts_file: String,
+    pub keys: Vec<HostKey>,
+}
+
+/// The known_hosts file managed by the app, kept next to the ssh control sockets.
+#[must_use]
+pub fn app_known_hosts(app_cache_dir: &Path) -> PathBuf {
+    app_cache_dir.join("known_hosts")
+}
+
+fn user_known_hosts() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".ssh/known_hosts"))
+}
+
+/// Returns true if the server is in the app managed known_hosts file or in the one of the user.
+#[must_use]
+pub fn is_trusted(address: &Address, known_hosts: &Path) -> bool {
+    is_known(address, known_hosts)
+        || user_known_hosts().map_or(false, |user_known_hosts| {
+            is_known(address, &user_known_hosts)
+        })
+}
+
+/// Checks if the server is trusted and otherwise fetches its keys so the user can confirm them.
+pub fn check(address: &Address, known_hosts: &Path) -> Result<HostKeyScan, Error> {
+    let keys = if is_trusted(address, known_hosts) {
+        vec![]
+    } else {
+        scan(address)?
+    };
+
+    Ok(HostKeyScan {
+        known_hosts_file: known_hosts.display().to_string(),
+        keys,
+    })
+}
+
+/// Fetches the host keys of the server with `ssh-keyscan`.
+pub fn scan(address: &Address) -> Result<Vec<HostKey>, Error> {
+    if address.proxy_jump.is_some() {
+        return Err(Error::Command(String::from(
+            "Host keys can't be fetched through jump hosts, add the server to ~/.ssh/known_hosts manually",
+        )));
+    }
+
+    let keyscan = Command::new("ssh-keyscan")
+        .args(["-p", &address.port.to_string(), &address.host_name])
+        .output()?;
+
+    let entries: Vec<String> = String::from_utf8_lossy(&keyscan.stdout)
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect();
+
+    if entries.is_empty() {
+        let stderr = String::from_utf8_lossy(&keyscan.stderr).trim().to_string();
+        return Err(Error::Command(format!(
+            "Could not fetch host keys from {address}: {stderr}"
+        )));
+    }
+
+    entries.into_iter().map(fingerprint).collect()
+}
+
+fn fingerprint(entry: String) -> Result<HostKey, Error> {
+    let mut keygen = Command::new("ssh-keygen")
+        .args(["-l", "-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = keygen.stdin.take() {
+        stdin.write_all(entry.as_bytes())?;
+    }
+
+    let output = keygen.wait_with_output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    // e.g. "256 SHA256:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU [host]:22 (ED25519)"
+    let fingerprint = stdout.split_whitespace().nth(1);
+    let key_type = stdout
+        .rsplit_once('(')
+        .map(|(_, key_type)| key_type.trim_end_matches(')').to_string());
+
+    match (output.status.success(), fingerprint, key_type) {
+        (true, Some(fingerprint), Some(key_type)) => Ok(HostKey {
+            key_type,
+            fingerprint: fingerprint.to_string(),
+            entry,
+        }),
+        _ => Err(Error::Command(format!(
+            "Could not compute host key fingerprint: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))),
+    }
+}
+
+/// Returns true if `known_hosts` already has an entry for the server.
+#[must_use]
+pub fn is_known(address: &Address, known_hosts: &Path) -> bool {
+    if !known_hosts.exists() {
+        return false;
+    }
+
+    lookup(address, known_hosts).map_or(false, |entries| !entries.is_empty())
+}
+
+/// Fingerprints of the keys stored for the server in `known_hosts`.
+fn lookup(address: &Address, known_hosts: &Path) -> Option<String> {
+    // known_hosts names servers on non standard ports as [host]:port
+    let host = if address.port == 22 {
+        address.host_name.clone()
+    } else {
+        format!("[{}]:{}", address.host_name, address.port)
+    };
+
+    Command::new("ssh-keygen")
+        .args(["-l", "-F", &host, "-f"])
+        .arg(known_hosts)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Stores the host keys of the server in `known_hosts`, but only if the server still offers a
+/// key with the fingerprint the user confirmed.
+pub fn trust(address: &Address, known_hosts: &Path, fingerprint: &str) -> Result<(), Error> {
+    let keys = scan(address)?;
+
+    if !keys.iter().any(|key| key.fingerprint == fingerprint) {
+        return Err(Error::HostKeyChanged(format!(
+            "{address} no longer offers a key with fingerprint {fingerprint}"
+        )));
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(known_hosts)?;
+
+    for key in keys {
+        writeln!(file, "{}", key.entry)?;
+    }
+
+    Ok(())
+}
+
+/// Explains why a connection failed when the host key is the cause.
+#[must_use]
+pub fn diagnose(address: &Address, known_hosts: &Path) -> Option<Error> {
+    if !is_trusted(address, known_hosts) {
+        return Some(Error::UnknownHostKey(format!(
+            "{address} is not trusted yet, run the setup again to confirm its host key"
+        )));
+    }
+
+    let stored = lookup(address, known_hosts)?;
+    let keys = scan(address).ok()?;
+    let fingerprints: Vec<String> = keys.iter().map(|key| key.fingerprint.clone()).collect();
+
+    if fingerprints
+        .iter()
+        .any(|fingerprint| stored.contains(fingerprint))
+    {
+        None
+    } else {
+        Some(Error::HostKeyChanged(format!(
+            "The host key of {address} has changed and now has fingerprint {}. This could mean someone is intercepting the connection. If the change is expected, remove the server from {} and run the setup again.",
+            fingerprints.join(", "),
+            known_hosts.display()
+        )))
+    }
+}
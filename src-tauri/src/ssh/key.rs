@@ -0,0 +1,119 @@
+use super::address::Address;
+use super::Error;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const KEY_FILE_NAME: &str = "id_ed25519_bmu";
+const ASKPASS_PATHS: [&str; 4] = [
+    "/usr/bin/ssh-askpass",
+    "/usr/lib/ssh/ssh-askpass",
+    "/usr/libexec/openssh/ssh-askpass",
+    "/usr/lib/openssh/gnome-ssh-askpass",
+];
+
+/// How the password for the one-time key installation is asked for.
+pub enum PasswordPrompt {
+    /// ssh asks in the terminal the command runs in
+    Terminal,
+    /// ssh asks through the graphical askpass program of the system
+    Askpass,
+}
+
+/// Generates an ed25519 keypair in `directory`, unless it already exists, and returns the path
+/// to the private key.
+pub fn generate(directory: &Path, comment: &str) -> Result<PathBuf, Error> {
+    let private_key = directory.join(KEY_FILE_NAME);
+
+    if private_key.exists() {
+        return Ok(private_key);
+    }
+
+    fs::create_dir_all(directory)?;
+
+    let keygen = Command::new("ssh-keygen")
+        .args(["-q", "-t", "ed25519", "-N", "", "-C", comment, "-f"])
+        .arg(&private_key)
+        .output()?;
+
+    if keygen.status.success() {
+        Ok(private_key)
+    } else {
+        let stderr = String::from_utf8_lossy(&keygen.stderr).trim().to_string();
+        Err(Error::Command(format!(
+            "Could not generate ssh key: {stderr}"
+        )))
+    }
+}
+
+fn askpass() -> Option<String> {
+    env::var("SSH_ASKPASS").ok().or_else(|| {
+        ASKPASS_PATHS
+            .iter()
+            .find(|path| Path::new(path).exists())
+            .map(|path| (*path).to_string())
+    })
+}
+
+/// Appends the public key of `private_key` to `authorized_keys` on the server. Password
+/// authentication is forced, since no key is accepted by the server yet.
+pub fn install(
+    address: &Address,
+    private_key: &Path,
+    prompt: &PasswordPrompt,
+) -> Result<(), Error> {
+    let public_key_path = PathBuf::from(format!("{}.pub", private_key.display()));
+    let public_key = fs::read_to_string(&public_key_path)?.trim().to_string();
+
+    if public_key.contains('\'') {
+        return Err(Error::Command(format!(
+            "Unexpected content in public key {}",
+            public_key_path.display()
+        )));
+    }
+
+    let remote_command = format!(
+        "umask 077; mkdir -p ~/.ssh && (grep -qxF '{public_key}' ~/.ssh/authorized_keys 2>/dev/null || echo '{public_key}' >> ~/.ssh/authorized_keys)"
+    );
+
+    let mut ssh = Command::new("ssh");
+    ssh.args(address.ssh_args())
+        .args([
+            "-o",
+            "PubkeyAuthentication=no",
+            "-o",
+            "PreferredAuthentications=password,keyboard-interactive",
+            "-o",
+            "NumberOfPasswordPrompts=1",
+        ])
+        .args([&address.ssh_login(), &remote_command]);
+
+    if let PasswordPrompt::Askpass = prompt {
+        let askpass = askpass().ok_or_else(|| {
+            Error::Command(String::from(
+                "No ssh askpass program found, install one or set SSH_ASKPASS",
+            ))
+        })?;
+
+        ssh.env("SSH_ASKPASS", askpass)
+            .env("SSH_ASKPASS_REQUIRE", "force")
+            .stdin(Stdio::null());
+
+        // older OpenSSH versions only use askpass when a display is set
+        if env::var_os("DISPLAY").is_none() {
+            ssh.env("DISPLAY", ":0");
+        }
+    }
+
+    let output = ssh.output()?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        Err(Error::Command(format!(
+            "Could not install the public key on the server: {stderr}"
+        )))
+    }
+}
@@ -1,8 +1,11 @@
 use crate::models::app;
 use serde::Serialize;
 
+pub mod address;
 pub mod commands;
 pub mod connect;
+pub mod host_key;
+pub mod key;
 
 #[derive(Debug)]
 pub enum Error {
@@ -10,6 +13,8 @@ pub enum Error {
     Connection(openssh::Error),
     Sftp(openssh_sftp_client::Error),
     Command(String),
+    UnknownHostKey(String),
+    HostKeyChanged(String),
 }
 
 impl From<app::Error> for Error {
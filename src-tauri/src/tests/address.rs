@@ -0,0 +1,115 @@
+use crate::models::app::Config;
+use crate::ssh::address::{normalize_host, parse_host_alias, Address};
+use std::path::PathBuf;
+
+const SSH_CONFIG: &str = "
+Host nas
+    HostName 192.168.1.20
+    User backup
+    Port 2222
+
+Host bastion-*
+    IdentityFile /keys/bastion
+
+Host office
+    HostName 10.0.0.5
+    ProxyJump bastion-1
+
+Host *
+    User fallback
+    IdentityFile /keys/default
+";
+
+#[test]
+fn test_normalize_host() {
+    assert_eq!(normalize_host("http://example.com/"), "example.com");
+    assert_eq!(normalize_host(" ssh://192.168.1.2 "), "192.168.1.2");
+    assert_eq!(normalize_host("[fe80::1]"), "fe80::1");
+    assert_eq!(normalize_host("nas"), "nas");
+}
+
+#[test]
+fn test_parse_named_host_alias() {
+    let alias = parse_host_alias(SSH_CONFIG, "nas").expect("nas should be an alias");
+
+    assert_eq!(alias.host_name.as_deref(), Some("192.168.1.20"));
+    assert_eq!(alias.user.as_deref(), Some("backup"));
+    assert_eq!(alias.port, Some(2222));
+    assert_eq!(alias.identity_file, Some(PathBuf::from("/keys/default")));
+}
+
+#[test]
+fn test_wildcard_hosts_are_not_aliases() {
+    assert!(parse_host_alias(SSH_CONFIG, "bastion-1").is_none());
+    assert!(parse_host_alias(SSH_CONFIG, "example.com").is_none());
+}
+
+#[test]
+fn test_ipv6_targets_are_bracketed() {
+    let address = Address {
+        user: String::from("backup"),
+        host: String::from("fe80::1"),
+        host_name: String::from("fe80::1"),
+        port: 22,
+        identity_file: None,
+        proxy_jump: None,
+        options: vec![],
+        known_hosts_file: None,
+    };
+
+    assert_eq!(
+        address.rsync_target("/backups"),
+        "backup@[fe80::1]:/backups"
+    );
+    assert_eq!(address.ssh_login(), "backup@fe80::1");
+    assert_eq!(address.rsync_shell(), "ssh -p 22");
+}
+
+#[test]
+fn test_connection_options_are_passed_to_ssh() {
+    let address = Address {
+        user: String::from("backup"),
+        host: String::from("nas.example.com"),
+        host_name: String::from("nas.example.com"),
+        port: 2222,
+        identity_file: Some(PathBuf::from("/keys/backup key")),
+        proxy_jump: Some(String::from("bastion")),
+        options: vec![String::from("ServerAliveInterval=30")],
+        known_hosts_file: None,
+    };
+
+    assert_eq!(
+        address.rsync_shell(),
+        "ssh -p 2222 -i '/keys/backup key' -J bastion -o ServerAliveInterval=30"
+    );
+    assert!(address
+        .ssh_config_contents()
+        .contains("Host nas.example.com\n    ProxyJump bastion\n    ServerAliveInterval 30\n"));
+}
+
+#[test]
+fn test_alias_is_given_to_ssh() {
+    let config = Config {
+        client_name: String::from("test"),
+        username: String::from("test"),
+        server_address: String::from("office"),
+        server_port: 22,
+        allow_background_backup: true,
+        identity_file: None,
+        proxy_jump: None,
+        ssh_options: None,
+        known_hosts_file: None,
+        notifications: None,
+        metrics_port: None,
+        retry: None,
+        max_transfers: None,
+    };
+    let address = Address::with_alias(&config, parse_host_alias(SSH_CONFIG, "office"));
+
+    // ssh resolves the alias itself, host keys are looked up for the host behind it
+    assert_eq!(address.host, "office");
+    assert_eq!(address.host_name, "10.0.0.5");
+    assert_eq!(address.user, "fallback");
+    assert_eq!(address.proxy_jump.as_deref(), Some("bastion-1"));
+    assert_eq!(address.rsync_target("/backups"), "fallback@office:/backups");
+}
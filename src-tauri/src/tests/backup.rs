@@ -0,0 +1,278 @@
+use crate::commands;
+use crate::jobs::{self, history::History, scheduler::Priority, Pool};
+use crate::models::app::{Config, MutexState};
+use crate::models::backup::{Backup, Destination, Hook, Hooks, Location, Options};
+use std::env;
+use std::fs;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+fn backup_with_mirror() -> Backup {
+    Backup {
+        client_location: Location {
+            entity_name: String::from("documents"),
+            path: String::from("/home/test/documents"),
+        },
+        server_location: Location {
+            entity_name: String::from("backups"),
+            path: String::from("/home/server/backups"),
+        },
+        latest_run: None,
+        options: Some(Options {
+            use_client_directory: false,
+        }),
+        mirrors: Some(vec![Destination::Disk(Location {
+            entity_name: String::from("external"),
+            path: String::from("/media/external"),
+        })]),
+        hooks: None,
+        databases: None,
+    }
+}
+
+fn config() -> Config {
+    Config {
+        client_name: String::from("test"),
+        username: String::from("test"),
+        server_address: String::from("localhost"),
+        server_port: 22,
+        allow_background_backup: true,
+        identity_file: None,
+        proxy_jump: None,
+        ssh_options: None,
+        known_hosts_file: None,
+        notifications: None,
+        metrics_port: None,
+        retry: None,
+        max_transfers: None,
+    }
+}
+
+#[test]
+fn test_destinations_start_with_server_location() {
+    let backup = backup_with_mirror();
+    let destinations = backup.destinations();
+
+    assert_eq!(destinations.len(), 2);
+    assert!(
+        matches!(&destinations[0], Destination::Server(location) if location.path == "/home/server/backups")
+    );
+    assert!(
+        matches!(&destinations[1], Destination::Disk(location) if location.path == "/media/external")
+    );
+}
+
+#[test]
+fn test_destination_ids_are_unique_per_destination() {
+    let backup = backup_with_mirror();
+    let job_id = jobs::id_from_backup(&backup, &jobs::Kind::Backup);
+    let ids: Vec<String> = backup
+        .destinations()
+        .iter()
+        .map(|destination| jobs::id_for_destination(&job_id, destination))
+        .collect();
+
+    assert_ne!(ids[0], ids[1]);
+    assert!(ids.iter().all(|id| id.starts_with(&job_id)));
+}
+
+#[test]
+fn test_client_source_copies_directory_content() {
+    let backup = backup_with_mirror();
+
+    assert_eq!(backup.client_source(true), "/home/test/documents/");
+    assert_eq!(backup.client_source(false), "/home/test/documents");
+}
+
+#[test]
+fn test_diff_backups_only_touches_changed_backups() {
+    let unchanged = backup_with_mirror();
+    let mut changed = backup_with_mirror();
+    changed.client_location.path = String::from("/home/test/pictures");
+    let mut changed_options = changed.clone();
+    changed_options.options = Some(Options {
+        use_client_directory: true,
+    });
+    let mut added = backup_with_mirror();
+    added.client_location.path = String::from("/home/test/music");
+    let mut backed_up = unchanged.clone();
+    backed_up.latest_run = Some(1_700_000_000);
+
+    let (to_stop, to_start) = commands::app::diff_backups(
+        &[unchanged.clone(), changed.clone()],
+        &[backed_up, changed_options.clone(), added.clone()],
+    );
+
+    assert!(to_stop == vec![changed]);
+    assert!(to_start == vec![changed_options, added]);
+}
+
+#[test]
+fn test_watcher_terminates_without_touching_the_directory() {
+    let dir = env::temp_dir().join(format!("bmu_watcher_{}", std::process::id()));
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("could not create temp dir");
+    fs::write(dir.join("notes.txt"), "notes").expect("could not write file");
+
+    let mut backup = backup_with_mirror();
+    backup.client_location.path = dir.display().to_string();
+    let config = config();
+    let cache_dir = dir.join(".cache");
+    let (done_sender, done_receiver) = mpsc::channel();
+    let mut pool = Pool::new(None);
+
+    let worker = pool
+        .spawn(
+            String::from("watcher"),
+            Priority::Watcher,
+            move |arguments| async move {
+                jobs::backup::directory_on_change(
+                    arguments,
+                    backup,
+                    config,
+                    Arc::default(),
+                    Arc::new(Mutex::new(History::new(cache_dir.clone()))),
+                    Arc::default(),
+                    cache_dir,
+                )
+                .await;
+                done_sender.send(()).expect("could not send done");
+            },
+        )
+        .expect("could not spawn watcher");
+    pool.terminate_job(worker)
+        .expect("could not terminate watcher");
+
+    assert!(done_receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+    let entries: Vec<_> = fs::read_dir(&dir)
+        .expect("could not read temp dir")
+        .flatten()
+        .map(|entry| entry.file_name())
+        .collect();
+    assert_eq!(entries, ["notes.txt"]);
+    _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_watcher_does_not_run_hooks() {
+    let dir = env::temp_dir().join(format!("bmu_watcher_hooks_{}", std::process::id()));
+    // the history and the marker are kept out of the watched folder, writing them is a change
+    let cache_dir = env::temp_dir().join(format!("bmu_watcher_hooks_cache_{}", std::process::id()));
+    let marker = cache_dir.join("hook_ran");
+    _ = fs::remove_dir_all(&dir);
+    _ = fs::remove_dir_all(&cache_dir);
+    fs::create_dir_all(&dir).expect("could not create temp dir");
+    fs::create_dir_all(&cache_dir).expect("could not create cache dir");
+
+    let hook = Hook {
+        command: format!("touch {}", marker.display()),
+        timeout_seconds: None,
+    };
+    let mut backup = backup_with_mirror();
+    backup.client_location.path = dir.display().to_string();
+    backup.hooks = Some(Hooks {
+        pre: Some(hook.clone()),
+        post: Some(hook),
+        abort_on_pre_failure: true,
+    });
+    let config = config();
+    let history = Arc::new(Mutex::new(History::new(cache_dir.clone())));
+    let (done_sender, done_receiver) = mpsc::channel();
+    let mut pool = Pool::new(None);
+
+    let worker = {
+        let history = Arc::clone(&history);
+        let cache_dir = cache_dir.clone();
+        pool.spawn(
+            String::from("watcher"),
+            Priority::Watcher,
+            move |arguments| async move {
+                jobs::backup::directory_on_change(
+                    arguments,
+                    backup,
+                    config,
+                    Arc::default(),
+                    history,
+                    Arc::default(),
+                    cache_dir,
+                )
+                .await;
+                done_sender.send(()).expect("could not send done");
+            },
+        )
+        .expect("could not spawn watcher")
+    };
+    // gives the watcher time to start before the change
+    std::thread::sleep(Duration::from_secs(1));
+    fs::write(dir.join("notes.txt"), "notes").expect("could not write file");
+
+    let picked_up = (0..50).any(|_| {
+        std::thread::sleep(Duration::from_millis(200));
+        let runs = history
+            .lock()
+            .expect("could not lock history")
+            .runs()
+            .expect("could not read history");
+        !runs.is_empty()
+    });
+    pool.terminate_job(worker)
+        .expect("could not terminate watcher");
+
+    assert!(done_receiver.recv_timeout(Duration::from_secs(10)).is_ok());
+    assert!(picked_up);
+    assert!(!marker.exists());
+    _ = fs::remove_dir_all(dir);
+    _ = fs::remove_dir_all(cache_dir);
+}
+
+#[test]
+fn test_changed_watcher_is_restarted_in_the_same_reload() {
+    let dir = env::temp_dir().join(format!("bmu_restart_{}", std::process::id()));
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("could not create temp dir");
+
+    let mut backup = backup_with_mirror();
+    backup.client_location.path = dir.display().to_string();
+    let mut changed = backup.clone();
+    changed.options = None;
+    let cache_dir = dir.join(".cache");
+    let state = MutexState {
+        config: Mutex::new(Some(config())),
+        connection: tokio::sync::Mutex::new(None),
+        jobs: Arc::default(),
+        failed_jobs: Arc::default(),
+        pool: Mutex::new(Pool::new(None)),
+        app_cache_dir: Arc::new(Mutex::new(cache_dir.clone())),
+        app_log_dir: Arc::new(Mutex::new(cache_dir.clone())),
+        history: Arc::new(Mutex::new(History::new(cache_dir))),
+        metrics: Arc::default(),
+    };
+    let job_id = jobs::id_from_backup(&backup, &jobs::Kind::BackupOnChange);
+    let watchers = |state: &MutexState| -> Vec<usize> {
+        let pool = state.pool.lock().expect("could not lock pool");
+        pool.active_jobs()
+            .into_iter()
+            .filter(|(id, _)| *id == job_id)
+            .map(|(_, worker)| worker)
+            .collect()
+    };
+
+    commands::app::start_background_backups(&state, &[backup.clone()])
+        .expect("could not start watcher");
+    let first = watchers(&state);
+    // the stopped watcher is still finishing when the new one is started
+    commands::app::apply_backup_changes(&state, &[backup], &[changed])
+        .expect("could not apply changes");
+    let second = watchers(&state);
+
+    assert_eq!(first.len(), 1);
+    assert_eq!(second.len(), 1);
+    assert_ne!(first, second);
+    assert_eq!(
+        state.jobs.lock().expect("could not lock jobs").get(&job_id),
+        second.first()
+    );
+
+    commands::app::terminate_watchers(&state).expect("could not stop watchers");
+    _ = fs::remove_dir_all(dir);
+}
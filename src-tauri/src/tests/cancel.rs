@@ -0,0 +1,114 @@
+use crate::jobs::cancel::{self, Cancel};
+use crate::jobs::scheduler::Priority;
+use crate::jobs::{status_from, Error, Pool, Status};
+use crate::models::retry::RetryPolicy;
+use std::io;
+use std::process::Command;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[test]
+fn test_output_is_captured() {
+    let output = cancel::output(
+        Command::new("sh").args(["-c", "echo transferred; echo skipped >&2; exit 24"]),
+        &Cancel::default(),
+    )
+    .expect("could not run sh");
+
+    assert_eq!(output.status.code(), Some(24));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "transferred\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "skipped\n");
+}
+
+#[test]
+fn test_cancelled_command_is_terminated() {
+    let cancel = Cancel::default();
+    let started = Instant::now();
+
+    {
+        let cancel = cancel.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            cancel.cancel();
+        });
+    }
+
+    let error = cancel::output(Command::new("sleep").arg("30"), &cancel)
+        .expect_err("the command was cancelled");
+
+    assert_eq!(error.kind(), io::ErrorKind::Interrupted);
+    assert!(started.elapsed() < TIMEOUT);
+}
+
+#[test]
+fn test_cancelled_command_is_not_started() {
+    let cancel = Cancel::default();
+    cancel.cancel();
+
+    assert!(cancel::output(&mut Command::new("true"), &cancel).is_err());
+}
+
+#[test]
+fn test_cancelled_job_is_not_retried() {
+    let mut pool = Pool::new(None);
+    let (sender, receiver) = mpsc::channel();
+    let policy = RetryPolicy {
+        max_attempts: 3,
+        initial_delay_seconds: 60,
+        max_delay_seconds: 60,
+    };
+
+    pool.execute_with_retries(
+        String::from("job"),
+        Priority::Manual,
+        policy,
+        move |arguments| {
+            sender.send(()).expect("could not send call");
+            thread::sleep(Duration::from_millis(200));
+
+            if arguments.cancel.is_cancelled() {
+                Err(String::from("cancelled"))
+            } else {
+                Err(String::from("Connection refused"))
+            }
+        },
+    )
+    .expect("could not execute job");
+
+    receiver.recv_timeout(TIMEOUT).expect("job did not start");
+    pool.cancel("job").expect("could not cancel job");
+
+    // the job would wait a minute before its retry otherwise, instead it ends right away
+    assert_eq!(
+        receiver.recv_timeout(TIMEOUT),
+        Err(RecvTimeoutError::Disconnected)
+    );
+    assert!(pool
+        .cancellations()
+        .lock()
+        .expect("could not lock cancellations")
+        .get("job")
+        .map_or(false, Cancel::is_cancelled));
+}
+
+#[test]
+fn test_unknown_job_is_not_cancelled() {
+    let pool = Pool::new(None);
+
+    assert!(matches!(pool.cancel("job"), Err(Error::NotFound(_))));
+}
+
+#[test]
+fn test_status_of_cancelled_job() {
+    assert!(matches!(
+        status_from(None, true, false, true, false),
+        Status::Cancelled
+    ));
+    assert!(matches!(
+        status_from(None, false, true, true, false),
+        Status::Cancelled
+    ));
+}
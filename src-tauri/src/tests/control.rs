@@ -0,0 +1,62 @@
+use crate::control::{self, Request, Response};
+use std::fs;
+use std::os::unix::net::UnixStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_request_is_answered_over_socket() {
+    let dir = std::env::temp_dir().join("bmu_test_control");
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("daemon")).expect("could not create daemon dir");
+    let socket = control::socket_path(&dir);
+    let (sender, mut receiver) = tokio::sync::mpsc::channel::<control::Incoming>(1);
+
+    control::listen(&socket, sender).expect("could not listen on socket");
+    thread::spawn(move || {
+        while let Some((request, respond)) = receiver.blocking_recv() {
+            _ = respond.send(Response::Ack(format!("{request:?}")));
+        }
+    });
+
+    let response = control::send(&socket, &Request::RunBackup(String::from("/home/test")));
+    _ = fs::remove_dir_all(&dir);
+
+    match response {
+        Ok(Response::Ack(message)) => assert_eq!(message, "RunBackup(\"/home/test\")"),
+        other => panic!("unexpected response: {other:?}"),
+    }
+}
+
+#[test]
+fn test_no_daemon_is_not_running() {
+    let socket = std::env::temp_dir().join("bmu_test_no_daemon.sock");
+
+    assert!(!control::is_running(&socket));
+}
+
+#[test]
+fn test_silent_client_does_not_block_others() {
+    let dir = std::env::temp_dir().join("bmu_test_control_silent");
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("daemon")).expect("could not create daemon dir");
+    let socket = control::socket_path(&dir);
+    let (sender, mut receiver) = tokio::sync::mpsc::channel::<control::Incoming>(1);
+
+    control::listen(&socket, sender).expect("could not listen on socket");
+    thread::spawn(move || {
+        while let Some((request, respond)) = receiver.blocking_recv() {
+            _ = respond.send(Response::Ack(format!("{request:?}")));
+        }
+    });
+
+    // never sends a request, its connection waits for the read timeout
+    let _silent = UnixStream::connect(&socket).expect("could not connect");
+    let started = Instant::now();
+    let response = control::send(&socket, &Request::Reload);
+    let elapsed = started.elapsed();
+    _ = fs::remove_dir_all(&dir);
+
+    assert!(matches!(response, Ok(Response::Ack(_))));
+    assert!(elapsed < Duration::from_secs(2));
+}
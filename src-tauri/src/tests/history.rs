@@ -0,0 +1,189 @@
+use crate::commands::os::process_is_running_since;
+use crate::jobs::history::{self, History};
+use crate::models::history::{Run, RunStatus, Transfer, Trigger};
+use crate::ssh::commands::parse_rsync_stats;
+use std::fs;
+use std::thread;
+
+const RSYNC_STATS: &str = "
+Number of files: 12 (reg: 10, dir: 2)
+Number of created files: 3 (reg: 3)
+Number of deleted files: 0
+Number of regular files transferred: 3
+Total file size: 1,234,567 bytes
+Total transferred file size: 4,096 bytes
+Literal data: 4,096 bytes
+";
+
+fn run(client_path: &str, status: RunStatus, ended_at: u64) -> Run {
+    Run {
+        job_id: format!("{client_path}_backup"),
+        client_path: client_path.to_string(),
+        server_path: String::from("/home/server/backups"),
+        trigger: Trigger::Manual,
+        started_at: ended_at - 1,
+        ended_at,
+        transfer: Transfer::default(),
+        status,
+        error: None,
+        pid: Some(1),
+        hooks: None,
+    }
+}
+
+#[test]
+fn test_parse_rsync_stats() {
+    assert_eq!(
+        parse_rsync_stats(RSYNC_STATS),
+        Transfer {
+            files: 3,
+            bytes: 4096
+        }
+    );
+}
+
+#[test]
+fn test_parse_rsync_stats_without_stats() {
+    assert_eq!(
+        parse_rsync_stats("sending incremental file list\n"),
+        Transfer::default()
+    );
+}
+
+#[test]
+fn test_history_query_is_newest_first() {
+    let data_dir = std::env::temp_dir().join("bmu_test_history_query");
+    _ = fs::remove_dir_all(&data_dir);
+    fs::create_dir_all(&data_dir).expect("could not create data dir");
+    let history = History::new(data_dir.clone());
+
+    history
+        .record(run("/home/test/documents", RunStatus::Success, 10))
+        .expect("could not record run");
+    history
+        .record(run("/home/test/pictures", RunStatus::Success, 20))
+        .expect("could not record run");
+    history
+        .record(run("/home/test/documents", RunStatus::Failure, 30))
+        .expect("could not record run");
+
+    let runs = history
+        .query(Some("/home/test/documents"), None)
+        .expect("could not query history");
+    let latest_success = history
+        .latest_success("/home/test/documents")
+        .expect("could not query history");
+    _ = fs::remove_dir_all(&data_dir);
+
+    assert_eq!(runs.len(), 2);
+    assert_eq!(runs[0].ended_at, 30);
+    assert_eq!(latest_success.map(|run| run.ended_at), Some(10));
+}
+
+#[test]
+fn test_history_detects_interrupted_runs() {
+    let data_dir = std::env::temp_dir().join("bmu_test_history_interrupted");
+    _ = fs::remove_dir_all(&data_dir);
+    fs::create_dir_all(&data_dir).expect("could not create data dir");
+    let history = History::new(data_dir.clone());
+    let finished = run("/home/test/documents", RunStatus::Success, 10);
+    let unfinished = run("/home/test/pictures", RunStatus::Success, 20);
+
+    history.begin(&finished).expect("could not begin run");
+    history.record(finished).expect("could not record run");
+    history.begin(&unfinished).expect("could not begin run");
+
+    let still_running = history
+        .interrupted(|_, _| true)
+        .expect("could not query history");
+    let interrupted = history
+        .interrupted(|_, _| false)
+        .expect("could not query history");
+    let runs = history.runs().expect("could not read history");
+    _ = fs::remove_dir_all(&data_dir);
+
+    assert!(still_running.is_empty());
+    assert_eq!(interrupted.len(), 1);
+    assert_eq!(interrupted[0].client_path, "/home/test/pictures");
+    assert_eq!(runs.len(), 2);
+    assert_eq!(runs[1].status, RunStatus::Interrupted);
+}
+
+#[test]
+fn test_concurrent_writers_keep_every_run() {
+    let data_dir = std::env::temp_dir().join("bmu_test_history_concurrent");
+    _ = fs::remove_dir_all(&data_dir);
+    fs::create_dir_all(&data_dir).expect("could not create data dir");
+
+    let writers: Vec<_> = (0..4)
+        .map(|writer| {
+            let data_dir = data_dir.clone();
+            thread::spawn(move || {
+                // every writer reads the file on its own, like separate processes do
+                let history = History::new(data_dir);
+                for index in 0..10 {
+                    history
+                        .record(run(
+                            &format!("/home/test/{writer}"),
+                            RunStatus::Success,
+                            index + 1,
+                        ))
+                        .expect("could not record run");
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().expect("writer panicked");
+    }
+
+    let runs = History::new(data_dir.clone())
+        .runs()
+        .expect("could not read history");
+    let leftovers: Vec<_> = fs::read_dir(&data_dir)
+        .expect("could not list data dir")
+        .flatten()
+        .map(|entry| entry.file_name())
+        .filter(|name| name != "history.json")
+        .collect();
+    _ = fs::remove_dir_all(&data_dir);
+
+    assert_eq!(runs.len(), 40);
+    assert!(leftovers.is_empty(), "{leftovers:?}");
+}
+
+#[test]
+fn test_runs_leave_backups_untouched() {
+    let data_dir = std::env::temp_dir().join("bmu_test_history_backups");
+    _ = fs::remove_dir_all(&data_dir);
+    fs::create_dir_all(&data_dir).expect("could not create data dir");
+    let backups = data_dir.join("backups.json");
+    fs::write(&backups, "[]").expect("could not write backups");
+    let modified = fs::metadata(&backups).and_then(|metadata| metadata.modified());
+    let history = History::new(data_dir.clone());
+
+    history
+        .record(run("/home/test/documents", RunStatus::Success, 10))
+        .expect("could not record run");
+    let latest_success = history
+        .latest_success("/home/test/documents")
+        .expect("could not query history");
+
+    assert_eq!(latest_success.map(|run| run.ended_at), Some(10));
+    assert_eq!(
+        fs::metadata(&backups)
+            .and_then(|metadata| metadata.modified())
+            .ok(),
+        modified.ok()
+    );
+    _ = fs::remove_dir_all(&data_dir);
+}
+
+#[test]
+fn test_reused_pid_is_not_running() {
+    let pid = std::process::id();
+
+    assert!(process_is_running_since(pid, history::now()));
+    // the run started long before this process did
+    assert!(!process_is_running_since(pid, history::now() - 86_400));
+}
@@ -0,0 +1,107 @@
+use crate::jobs::backup::run_pre_hook;
+use crate::jobs::hooks::{environment, failure, run};
+use crate::models::backup::{Backup, Hook, Hooks, Location};
+use crate::models::history::{HookStage, Run, RunStatus, Transfer, Trigger};
+
+fn backup_run() -> Run {
+    Run {
+        job_id: String::from("/home/test/documents_backup"),
+        client_path: String::from("/home/test/documents"),
+        server_path: String::from("/home/server/backups"),
+        trigger: Trigger::Manual,
+        started_at: 100,
+        ended_at: 110,
+        transfer: Transfer {
+            files: 3,
+            bytes: 42,
+        },
+        status: RunStatus::Success,
+        error: None,
+        pid: Some(1),
+        hooks: None,
+    }
+}
+
+fn hook(command: &str, timeout_seconds: Option<u32>) -> Hook {
+    Hook {
+        command: command.to_string(),
+        timeout_seconds,
+    }
+}
+
+#[test]
+fn test_hook_sees_backup_environment() {
+    let hook_run = run(
+        &hook(
+            "echo $BMU_HOOK $BMU_CLIENT_PATH $BMU_STATUS $BMU_TRANSFERRED_FILES",
+            None,
+        ),
+        HookStage::Post,
+        &environment(&backup_run(), "client"),
+    );
+
+    assert!(hook_run.succeeded());
+    assert_eq!(hook_run.output, "post /home/test/documents Success 3");
+}
+
+#[test]
+fn test_failing_hook_keeps_output() {
+    let hook_run = run(
+        &hook("echo dump failed >&2; exit 3", None),
+        HookStage::Pre,
+        &[],
+    );
+
+    assert!(!hook_run.succeeded());
+    assert_eq!(hook_run.exit_code, Some(3));
+    assert_eq!(hook_run.output, "dump failed");
+    assert_eq!(failure(&hook_run), "Pre hook exited with 3: dump failed");
+}
+
+#[test]
+fn test_hook_is_killed_after_timeout() {
+    let hook_run = run(&hook("sleep 10", Some(1)), HookStage::Pre, &[]);
+
+    assert!(!hook_run.succeeded());
+    assert!(hook_run.timed_out);
+    assert_eq!(hook_run.exit_code, None);
+}
+
+#[test]
+fn test_pre_hook_failure_is_a_warning_when_backup_continues() {
+    let location = Location {
+        path: String::from("/home/test/documents"),
+        entity_name: String::from("documents"),
+    };
+    let mut backup = Backup {
+        client_location: location.clone(),
+        server_location: location,
+        latest_run: None,
+        options: None,
+        mirrors: None,
+        hooks: Some(Hooks {
+            pre: Some(hook("exit 1", None)),
+            post: None,
+            abort_on_pre_failure: false,
+        }),
+        databases: None,
+    };
+    let (mut errors, mut warnings) = (vec![], vec![]);
+
+    let hook_run = run_pre_hook(&backup, &backup_run(), "client", &mut errors, &mut warnings);
+
+    assert!(hook_run.map_or(false, |hook_run| !hook_run.succeeded()));
+    assert!(errors.is_empty());
+    assert_eq!(warnings, vec![String::from("Pre hook exited with 1: ")]);
+
+    backup
+        .hooks
+        .as_mut()
+        .expect("hooks are set")
+        .abort_on_pre_failure = true;
+    let (mut errors, mut warnings) = (vec![], vec![]);
+    run_pre_hook(&backup, &backup_run(), "client", &mut errors, &mut warnings);
+
+    assert_eq!(errors.len(), 1);
+    assert!(warnings.is_empty());
+}
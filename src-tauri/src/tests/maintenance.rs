@@ -0,0 +1,32 @@
+use crate::jobs::maintenance::{self, Directories, Options};
+use crate::jobs::Pool;
+use std::env;
+use std::fs;
+use std::sync::mpsc;
+use std::time::Duration;
+
+#[test]
+fn test_clean_runs_as_job_of_the_pool() {
+    let dir = env::temp_dir().join(format!("bmu_clean_{}", std::process::id()));
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("logs")).expect("could not create log dir");
+    fs::write(dir.join("logs/app.log"), "log").expect("could not write log");
+    fs::write(dir.join("logs/notes.txt"), "notes").expect("could not write file");
+    let directories = Directories {
+        cache: dir.join("cache"),
+        log: dir.join("logs"),
+    };
+    let (sender, receiver) = mpsc::channel();
+    let mut pool = Pool::new(None);
+
+    maintenance::spawn(&mut pool, directories, Options::default(), sender)
+        .expect("could not spawn cleaning job");
+    let result = receiver
+        .recv_timeout(Duration::from_secs(5))
+        .expect("cleaning job did not finish");
+
+    assert!(result.is_ok());
+    assert!(!dir.join("logs/app.log").exists());
+    assert!(dir.join("logs/notes.txt").exists());
+    _ = fs::remove_dir_all(dir);
+}
@@ -0,0 +1,89 @@
+use crate::control::Response;
+use crate::metrics::{self, Gauges, Metrics};
+use crate::models::history::{Run, RunStatus, Transfer, Trigger};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::thread;
+
+fn run(client_path: &str, status: RunStatus, bytes: u64) -> Run {
+    Run {
+        job_id: format!("{client_path}_backup"),
+        client_path: client_path.to_string(),
+        server_path: String::from("/home/server/backups"),
+        trigger: Trigger::Watcher,
+        started_at: 1,
+        ended_at: 2,
+        transfer: Transfer { files: 1, bytes },
+        status,
+        error: None,
+        pid: Some(1),
+        hooks: None,
+    }
+}
+
+fn gauges() -> Gauges {
+    Gauges {
+        running_jobs: 2,
+        queued_jobs: 0,
+        failed_jobs: 1,
+        connected: true,
+        paused: false,
+        last_success: vec![
+            (String::from("/home/test/documents"), Some(1_700_000_000)),
+            (String::from("/home/test/pictures"), None),
+        ],
+    }
+}
+
+#[test]
+fn test_render_counts_runs_per_backup() {
+    let mut metrics = Metrics::default();
+    metrics.observe(&run("/home/test/documents", RunStatus::Success, 100));
+    metrics.observe(&run("/home/test/documents", RunStatus::Failure, 20));
+    metrics.observe(&run("/home/test/\"quoted\"", RunStatus::Success, 1));
+
+    let text = metrics::render(&metrics, &gauges());
+
+    assert!(text.contains("# TYPE bmu_running_jobs gauge\nbmu_running_jobs 2\n"));
+    assert!(text.contains("bmu_connected 1\n"));
+    assert!(text.contains("bmu_paused 0\n"));
+    assert!(text.contains(
+        "bmu_last_success_timestamp_seconds{client_path=\"/home/test/documents\"} 1700000000\n"
+    ));
+    assert!(
+        !text.contains("bmu_last_success_timestamp_seconds{client_path=\"/home/test/pictures\"}")
+    );
+    assert!(text.contains("bmu_runs_total{client_path=\"/home/test/documents\"} 2\n"));
+    assert!(text.contains("bmu_failures_total{client_path=\"/home/test/documents\"} 1\n"));
+    assert!(
+        text.contains("bmu_transferred_bytes_total{client_path=\"/home/test/documents\"} 120\n")
+    );
+    assert!(text.contains("bmu_runs_total{client_path=\"/home/test/\\\"quoted\\\"\"} 1\n"));
+}
+
+#[test]
+fn test_serve_forwards_scrapes_to_the_daemon() {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+    let address = metrics::serve(0, sender).expect("could not serve metrics");
+
+    thread::spawn(move || {
+        if let Some((_, respond)) = receiver.blocking_recv() {
+            _ = respond.send(Response::Metrics(String::from("bmu_running_jobs 0\n")));
+        }
+    });
+
+    let scrape = |path: &str| {
+        let mut stream = TcpStream::connect(address).expect("could not connect");
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").expect("could not send");
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .expect("could not read response");
+        response
+    };
+
+    let response = scrape("/metrics");
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with("\r\n\r\nbmu_running_jobs 0\n"));
+    assert!(scrape("/").starts_with("HTTP/1.1 404 Not Found"));
+}
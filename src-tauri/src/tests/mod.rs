@@ -1 +1,16 @@
+pub mod address;
+pub mod backup;
+pub mod cancel;
+#[cfg(unix)]
+pub mod control;
+pub mod history;
+pub mod hooks;
+pub mod maintenance;
+pub mod metrics;
+pub mod notification;
+pub mod retry;
+pub mod scheduler;
+pub mod snapshot;
 pub mod ssh;
+pub mod status;
+pub mod transfer;
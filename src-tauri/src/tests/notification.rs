@@ -0,0 +1,242 @@
+use crate::models::history::{Run, RunStatus, Transfer, Trigger};
+use crate::models::notification::{Email, Event};
+use crate::notification::{email::Smtp, event_for, webhook::Webhook, Notifier};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
+
+const CLIENT_PATH: &str = "/home/test/documents";
+
+fn run(status: RunStatus, started_at: u64) -> Run {
+    Run {
+        job_id: format!("{CLIENT_PATH}_backup"),
+        client_path: CLIENT_PATH.to_string(),
+        server_path: String::from("/home/server/backups"),
+        trigger: Trigger::Watcher,
+        started_at,
+        ended_at: started_at + 10,
+        transfer: Transfer::default(),
+        status,
+        error: match status {
+            RunStatus::Failure => Some(String::from("connection refused")),
+            _ => None,
+        },
+        pid: Some(1),
+        hooks: None,
+    }
+}
+
+/// Accepts a single http request and sends its body on the returned channel.
+fn http_server() -> (String, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("could not bind http server");
+    let url = format!("http://{}/hook", listener.local_addr().expect("no address"));
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().expect("could not accept connection");
+        let mut reader = BufReader::new(stream);
+        let mut content_length = 0;
+
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).expect("could not read header");
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                content_length = value.trim().parse().expect("invalid content length");
+            }
+        }
+
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).expect("could not read body");
+        reader
+            .get_mut()
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+            .expect("could not respond");
+        sender
+            .send(String::from_utf8_lossy(&body).to_string())
+            .expect("could not send body");
+    });
+
+    (url, receiver)
+}
+
+/// Accepts a single smtp session and sends the recipients and data on the returned channel.
+fn smtp_server() -> (String, mpsc::Receiver<(Vec<String>, String)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("could not bind smtp server");
+    let url = format!("smtp://{}", listener.local_addr().expect("no address"));
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().expect("could not accept connection");
+        let mut writer = stream.try_clone().expect("could not clone stream");
+        let mut reader = BufReader::new(stream);
+        let mut recipients = vec![];
+        let mut data = String::new();
+        let mut reply = |message: &str| {
+            writer
+                .write_all(format!("{message}\r\n").as_bytes())
+                .expect("could not reply");
+        };
+
+        reply("220 localhost");
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).expect("could not read command") == 0 {
+                break;
+            }
+            let command = line.trim_end().to_string();
+
+            if command.starts_with("EHLO") || command.starts_with("HELO") {
+                reply("250 localhost");
+            } else if let Some(recipient) = command.strip_prefix("RCPT TO:") {
+                recipients.push(recipient.trim_matches(|c| c == '<' || c == '>').to_string());
+                reply("250 OK");
+            } else if command == "DATA" {
+                reply("354 End data with <CR><LF>.<CR><LF>");
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).expect("could not read data");
+                    if line.trim_end() == "." {
+                        break;
+                    }
+                    data.push_str(&line);
+                }
+                reply("250 OK");
+            } else if command == "QUIT" {
+                reply("221 Bye");
+                break;
+            } else {
+                reply("250 OK");
+            }
+        }
+
+        sender
+            .send((recipients, data))
+            .expect("could not send mail");
+    });
+
+    (url, receiver)
+}
+
+#[test]
+fn test_first_failure_is_reported() {
+    let previous = vec![run(RunStatus::Success, 0)];
+    let event = event_for(&run(RunStatus::Failure, 100), &previous, None, false);
+
+    assert!(matches!(event, Some(Event::Failed(_))));
+}
+
+#[test]
+fn test_repeated_failure_is_not_reported() {
+    let previous = vec![run(RunStatus::Success, 0), run(RunStatus::Failure, 100)];
+    let event = event_for(&run(RunStatus::Failure, 200), &previous, None, false);
+
+    assert!(event.is_none());
+}
+
+#[test]
+fn test_success_after_failure_is_reported_as_recovery() {
+    let previous = vec![run(RunStatus::Failure, 100), run(RunStatus::Running, 150)];
+    let event = event_for(&run(RunStatus::Success, 200), &previous, None, false);
+
+    assert!(matches!(event, Some(Event::Recovered(_))));
+    assert!(event_for(
+        &run(RunStatus::Success, 300),
+        &[run(RunStatus::Success, 200)],
+        None,
+        false
+    )
+    .is_none());
+}
+
+#[test]
+fn test_failing_threshold_is_reported_once() {
+    let threshold = Some(3600);
+    let mut previous = vec![run(RunStatus::Success, 0), run(RunStatus::Failure, 100)];
+
+    let before_threshold = run(RunStatus::Failure, 1800);
+    assert!(event_for(&before_threshold, &previous, threshold, false).is_none());
+    previous.push(before_threshold);
+
+    let crossing_threshold = run(RunStatus::Failure, 3700);
+    match event_for(&crossing_threshold, &previous, threshold, false) {
+        Some(Event::StillFailing { since, .. }) => assert_eq!(since, 100),
+        event => panic!("expected StillFailing, got {event:?}"),
+    }
+    previous.push(crossing_threshold);
+
+    assert!(event_for(&run(RunStatus::Failure, 7200), &previous, threshold, false).is_none());
+}
+
+#[test]
+fn test_backups_to_other_destinations_keep_their_own_streak() {
+    let previous = vec![run(RunStatus::Success, 0), run(RunStatus::Failure, 100)];
+    let other_destination = Run {
+        server_path: String::from("/media/disk/backups"),
+        ..run(RunStatus::Failure, 200)
+    };
+
+    assert!(matches!(
+        event_for(&other_destination, &previous, None, false),
+        Some(Event::Failed(_))
+    ));
+    assert!(event_for(&run(RunStatus::Failure, 200), &previous, None, false).is_none());
+}
+
+#[test]
+fn test_completions_of_one_off_backups_are_reported() {
+    let previous = vec![run(RunStatus::Success, 0)];
+    let one_off = Run {
+        trigger: Trigger::Manual,
+        ..run(RunStatus::Partial, 100)
+    };
+
+    assert!(matches!(
+        event_for(&one_off, &previous, None, true),
+        Some(Event::Completed(_))
+    ));
+    assert!(event_for(&one_off, &previous, None, false).is_none());
+    assert!(event_for(&run(RunStatus::Success, 100), &previous, None, true).is_none());
+}
+
+#[test]
+fn test_webhook_posts_event_as_json() {
+    let (url, receiver) = http_server();
+    let event = Event::Failed(run(RunStatus::Failure, 100));
+
+    Webhook { url }
+        .notify(&event)
+        .expect("could not notify webhook");
+
+    let body = receiver.recv().expect("no request received");
+    let received: Event = serde_json::from_str(&body).expect("body is not an event");
+    assert!(matches!(received, Event::Failed(run) if run.client_path == CLIENT_PATH));
+}
+
+#[test]
+fn test_smtp_sends_email_to_every_recipient() {
+    let (server_url, receiver) = smtp_server();
+    let event = Event::Recovered(run(RunStatus::Success, 100));
+
+    Smtp {
+        email: Email {
+            server_url,
+            from: String::from("bmu@client.local"),
+            to: vec![
+                String::from("admin@example.com"),
+                String::from("backup@example.com"),
+            ],
+            username: None,
+            password: None,
+        },
+    }
+    .notify(&event)
+    .expect("could not send email");
+
+    let (recipients, data) = receiver.recv().expect("no email received");
+    assert_eq!(recipients, ["admin@example.com", "backup@example.com"]);
+    assert!(data.contains(&format!("Subject: Backup of {CLIENT_PATH} recovered")));
+}
@@ -0,0 +1,198 @@
+use crate::jobs::retry::{classify, FailureKind};
+use crate::jobs::scheduler::Priority;
+use crate::jobs::{panic_message, status_from, Attempt, Pool, Status};
+use crate::models::retry::RetryPolicy;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn immediate_policy(max_attempts: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_attempts,
+        initial_delay_seconds: 0,
+        max_delay_seconds: 0,
+    }
+}
+
+/// Runs a job failing with `error` on every call but `succeed_on` and returns the calls made.
+fn calls_until_done(max_attempts: u32, error: &'static str, succeed_on: usize) -> Vec<usize> {
+    let mut pool = Pool::new(None);
+    let (sender, receiver) = mpsc::channel();
+    let calls = AtomicUsize::new(0);
+
+    pool.execute_with_retries(
+        String::from("job"),
+        Priority::Manual,
+        immediate_policy(max_attempts),
+        move |_| {
+            let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            sender.send(call).expect("could not send call");
+
+            if call == succeed_on {
+                Ok(())
+            } else {
+                Err(String::from(error))
+            }
+        },
+    )
+    .expect("could not execute job");
+
+    let mut calls = vec![];
+    while let Ok(call) = receiver.recv_timeout(if calls.is_empty() {
+        TIMEOUT
+    } else {
+        Duration::from_millis(500)
+    }) {
+        calls.push(call);
+    }
+    calls
+}
+
+#[test]
+fn test_errors_are_classified() {
+    assert_eq!(
+        classify("ssh: connect to host backup.local port 22: Connection refused"),
+        FailureKind::Network
+    );
+    assert_eq!(
+        classify("Server(/backups): Rsync failed: rsync: connection unexpectedly closed"),
+        FailureKind::Network
+    );
+    assert_eq!(
+        classify("Permission denied (publickey)."),
+        FailureKind::Permission
+    );
+    assert_eq!(
+        classify("rsync: link_stat \"/home/test/documents\" failed: No such file or directory (2)"),
+        FailureKind::MissingSource
+    );
+    assert_eq!(classify("Pre hook exited with 1: "), FailureKind::Other);
+    assert_eq!(
+        classify("Backup panicked: Connection refused"),
+        FailureKind::Panic
+    );
+}
+
+#[test]
+fn test_backoff_is_exponential_and_capped() {
+    let policy = RetryPolicy {
+        max_attempts: 5,
+        initial_delay_seconds: 10,
+        max_delay_seconds: 60,
+    };
+
+    assert_eq!(policy.delay(1, 1.0), Duration::from_secs(10));
+    assert_eq!(policy.delay(2, 1.0), Duration::from_secs(20));
+    assert_eq!(policy.delay(3, 0.0), Duration::from_secs(20));
+    assert_eq!(policy.delay(4, 1.0), Duration::from_secs(60));
+    assert_eq!(policy.delay(40, 1.0), Duration::from_secs(60));
+}
+
+#[test]
+fn test_network_failures_are_retried_until_success() {
+    assert_eq!(calls_until_done(3, "Connection refused", 2), [1, 2]);
+}
+
+#[test]
+fn test_retries_are_bounded_by_max_attempts() {
+    assert_eq!(calls_until_done(3, "Connection refused", 0), [1, 2, 3]);
+}
+
+#[test]
+fn test_retries_are_opt_in() {
+    let max_attempts = RetryPolicy::default().max_attempts;
+
+    assert_eq!(calls_until_done(max_attempts, "Connection refused", 0), [1]);
+}
+
+#[test]
+fn test_permission_failures_are_not_retried() {
+    assert_eq!(calls_until_done(3, "Permission denied", 0), [1]);
+}
+
+#[test]
+fn test_panicking_job_is_not_retried() {
+    let mut pool = Pool::new(None);
+    let (sender, receiver) = mpsc::channel();
+
+    pool.execute_with_retries(
+        String::from("job"),
+        Priority::Manual,
+        immediate_policy(3),
+        move |_| -> Result<(), String> {
+            sender.send(()).expect("could not send call");
+            panic!("Connection refused");
+        },
+    )
+    .expect("could not execute job");
+
+    receiver.recv_timeout(TIMEOUT).expect("job was not called");
+    assert!(receiver.recv_timeout(Duration::from_millis(500)).is_err());
+    assert_eq!(pool.running(), 0);
+}
+
+#[test]
+fn test_panic_message_is_read_from_payload() {
+    assert_eq!(panic_message(&"static message"), "static message");
+    assert_eq!(panic_message(&String::from("formatted")), "formatted");
+    assert_eq!(panic_message(&42), "unknown panic");
+}
+
+#[test]
+fn test_status_of_retried_job() {
+    let attempt = |attempt, next_attempt_at| Attempt {
+        job_id: String::from("job"),
+        attempt,
+        max_attempts: 3,
+        next_attempt_at,
+    };
+
+    // the first attempt runs like any other job, even after it failed
+    assert!(matches!(
+        status_from(Some(&attempt(1, None)), false, true, false, false),
+        Status::Running
+    ));
+    assert!(matches!(
+        status_from(Some(&attempt(2, Some(100))), false, true, false, false),
+        Status::Retrying(Attempt { attempt: 2, .. })
+    ));
+    assert!(matches!(
+        status_from(None, false, true, false, false),
+        Status::Failed
+    ));
+    assert!(matches!(
+        status_from(None, false, false, false, false),
+        Status::Completed
+    ));
+}
+
+#[test]
+fn test_job_has_task_from_the_start() {
+    let mut pool = Pool::new(None);
+    let (release, released) = mpsc::channel();
+
+    pool.execute_with_retries(
+        String::from("job"),
+        Priority::Manual,
+        immediate_policy(1),
+        move |_| {
+            released
+                .recv_timeout(TIMEOUT)
+                .expect("job was not released");
+            Ok(())
+        },
+    )
+    .expect("could not execute job");
+
+    // before the job could register itself anywhere else
+    assert!(pool.has_task("job"));
+    release.send(()).expect("could not release job");
+
+    let started = std::time::Instant::now();
+    while pool.has_task("job") {
+        assert!(started.elapsed() < TIMEOUT, "job did not finish");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
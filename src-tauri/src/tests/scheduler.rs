@@ -0,0 +1,144 @@
+use crate::jobs::scheduler::{Priority, Scheduler};
+use crate::jobs::{status_from, Status};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Queues a transfer of `job_id`, which reports its id once it starts and holds its permit
+/// until `release` gets a message.
+fn queue(
+    scheduler: &Arc<Scheduler>,
+    job_id: &str,
+    priority: Priority,
+    started: &mpsc::UnboundedSender<String>,
+) -> mpsc::UnboundedSender<()> {
+    let (release, mut released) = mpsc::unbounded_channel();
+    let scheduler = Arc::clone(scheduler);
+    let job_id = job_id.to_string();
+    let started = started.clone();
+
+    tokio::spawn(async move {
+        let permit = scheduler
+            .acquire(job_id.clone(), priority)
+            .await
+            .expect("could not acquire permit");
+        started.send(job_id).expect("could not send start");
+        released.recv().await;
+        drop(permit);
+    });
+
+    release
+}
+
+async fn next(started: &mut mpsc::UnboundedReceiver<String>) -> String {
+    time::timeout(TIMEOUT, started.recv())
+        .await
+        .expect("no transfer started")
+        .expect("channel closed")
+}
+
+/// Waits until `count` transfers are queued.
+async fn queued(scheduler: &Scheduler, count: usize) -> Vec<String> {
+    time::timeout(TIMEOUT, async {
+        loop {
+            let queued = scheduler.queued();
+            if queued.len() == count {
+                return queued;
+            }
+            time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("transfers were not queued")
+}
+
+#[tokio::test]
+async fn test_waiting_transfers_start_by_priority() {
+    let scheduler = Arc::new(Scheduler::new(1));
+    let (sender, mut started) = mpsc::unbounded_channel();
+
+    let running = queue(&scheduler, "running", Priority::Watcher, &sender);
+    assert_eq!(next(&mut started).await, "running");
+
+    let mut releases = vec![];
+    for (job_id, priority) in [
+        ("maintenance", Priority::Maintenance),
+        ("watcher", Priority::Watcher),
+        ("manual", Priority::Manual),
+        ("second watcher", Priority::Watcher),
+    ] {
+        releases.push(queue(&scheduler, job_id, priority, &sender));
+        queued(&scheduler, releases.len()).await;
+    }
+
+    let order = ["manual", "watcher", "second watcher", "maintenance"];
+    assert_eq!(scheduler.queued(), order);
+
+    running.send(()).expect("could not release transfer");
+    for job_id in order {
+        let started_id = next(&mut started).await;
+        assert_eq!(started_id, job_id);
+        assert!(!scheduler.is_queued(job_id));
+
+        for release in &releases {
+            _ = release.send(());
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_limit_bounds_running_transfers() {
+    let scheduler = Arc::new(Scheduler::new(2));
+    let (sender, mut started) = mpsc::unbounded_channel();
+
+    let releases: Vec<_> = ["first", "second", "third"]
+        .iter()
+        .map(|job_id| queue(&scheduler, job_id, Priority::Manual, &sender))
+        .collect();
+
+    next(&mut started).await;
+    next(&mut started).await;
+    assert_eq!(queued(&scheduler, 1).await.len(), 1);
+
+    scheduler.set_limit(3);
+    next(&mut started).await;
+    assert!(scheduler.queued().is_empty());
+    drop(releases);
+}
+
+#[tokio::test]
+async fn test_transfer_that_stops_waiting_leaves_the_queue() {
+    let scheduler = Arc::new(Scheduler::new(1));
+    let (sender, mut started) = mpsc::unbounded_channel();
+
+    let running = queue(&scheduler, "running", Priority::Manual, &sender);
+    next(&mut started).await;
+
+    let waiting = time::timeout(
+        Duration::from_millis(100),
+        scheduler.acquire(String::from("cancelled"), Priority::Manual),
+    )
+    .await;
+    assert!(waiting.is_err());
+    assert!(!scheduler.is_queued("cancelled"));
+
+    let _next = queue(&scheduler, "next", Priority::Maintenance, &sender);
+    queued(&scheduler, 1).await;
+    running.send(()).expect("could not release transfer");
+    assert_eq!(next(&mut started).await, "next");
+}
+
+#[test]
+fn test_status_of_queued_job() {
+    assert!(matches!(
+        status_from(None, true, false, false, true),
+        Status::Queued
+    ));
+    assert!(matches!(
+        status_from(None, false, false, true, true),
+        Status::Cancelled
+    ));
+}
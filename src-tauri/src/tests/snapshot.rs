@@ -0,0 +1,141 @@
+use crate::jobs::snapshot;
+use crate::models::backup::{Backup, Location, Options};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn backup_with_database(client_path: &str, use_client_directory: bool) -> Backup {
+    Backup {
+        client_location: Location {
+            entity_name: String::from("app"),
+            path: client_path.to_string(),
+        },
+        server_location: Location {
+            entity_name: String::from("backups"),
+            path: String::from("/home/server/backups"),
+        },
+        latest_run: None,
+        options: Some(Options {
+            use_client_directory,
+        }),
+        mirrors: None,
+        hooks: None,
+        databases: Some(vec![String::from("data/app.db")]),
+    }
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("bmu_snapshot_{name}_{}", std::process::id()));
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("could not create temp dir");
+    dir
+}
+
+fn sqlite(database: &Path, sql: &str) -> String {
+    let output = Command::new("sqlite3")
+        .arg(database)
+        .arg(sql)
+        .output()
+        .expect("could not run sqlite3");
+    assert!(output.status.success(), "sqlite3 failed: {output:?}");
+
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+#[test]
+fn test_journals_belong_to_their_database() {
+    let backup = backup_with_database("/home/test/app", false);
+
+    for path in [
+        "/home/test/app/data/app.db",
+        "/home/test/app/data/app.db-wal",
+        "/home/test/app/data/app.db-journal",
+    ] {
+        assert_eq!(
+            snapshot::database_for(&backup, Path::new(path)).as_deref(),
+            Some("data/app.db")
+        );
+    }
+    assert!(snapshot::database_for(&backup, Path::new("/home/test/app/data/other.db")).is_none());
+    assert!(snapshot::database_for(&backup, Path::new("/home/test/data/app.db")).is_none());
+}
+
+#[test]
+fn test_databases_are_relative_to_a_changed_directory() {
+    let backup = backup_with_database("/home/test/app", false);
+
+    assert_eq!(
+        snapshot::databases_in(&backup, Path::new("/home/test/app")),
+        vec![String::from("data/app.db")]
+    );
+    assert_eq!(
+        snapshot::databases_in(&backup, Path::new("/home/test/app/data")),
+        vec![String::from("app.db")]
+    );
+    assert!(snapshot::databases_in(&backup, Path::new("/home/test/app/other")).is_empty());
+}
+
+#[test]
+fn test_databases_are_excluded_from_the_transfer_root() {
+    let content = backup_with_database("/home/test/app", false);
+    let directory = backup_with_database("/home/test/app", true);
+
+    assert_eq!(
+        content.database_excludes(true).first().map(String::as_str),
+        Some("--exclude=/data/app.db")
+    );
+    assert!(directory
+        .database_excludes(true)
+        .contains(&String::from("--exclude=/app/data/app.db-wal")));
+    assert!(content.database_excludes(false).is_empty());
+}
+
+#[test]
+fn test_snapshot_of_database_in_use() {
+    let client_dir = temp_dir("client");
+    let staging_dir = temp_dir("staging");
+    let backup = backup_with_database(client_dir.to_str().expect("invalid temp dir"), false);
+    let database = client_dir.join("data/app.db");
+    fs::create_dir_all(client_dir.join("data")).expect("could not create data dir");
+
+    sqlite(
+        &database,
+        "PRAGMA journal_mode=WAL; CREATE TABLE runs (id INTEGER); INSERT INTO runs VALUES (1), (2);",
+    );
+
+    let snapshot =
+        snapshot::database(&backup, "data/app.db", &staging_dir).expect("could not take snapshot");
+
+    assert_eq!(snapshot, staging_dir.join("data/app.db"));
+    assert_eq!(sqlite(&snapshot, "SELECT count(*) FROM runs;"), "2");
+    assert_eq!(sqlite(&snapshot, "PRAGMA integrity_check;"), "ok");
+
+    snapshot::clean(&staging_dir);
+    assert!(!staging_dir.exists());
+    _ = fs::remove_dir_all(client_dir);
+}
+
+#[test]
+fn test_snapshot_of_missing_database_fails() {
+    let staging_dir = temp_dir("missing");
+    let backup = backup_with_database("/nonexistent/app", false);
+
+    assert!(snapshot::database(&backup, "data/app.db", &staging_dir).is_err());
+    assert!(!Path::new("/nonexistent/app/data/app.db").exists());
+    snapshot::clean(&staging_dir);
+}
+
+#[test]
+fn test_snapshot_of_file_that_is_not_a_database_fails() {
+    let client_dir = temp_dir("not_a_database");
+    let staging_dir = temp_dir("not_a_database_staging");
+    let backup = backup_with_database(client_dir.to_str().expect("invalid temp dir"), false);
+    fs::create_dir_all(client_dir.join("data")).expect("could not create data dir");
+    fs::write(client_dir.join("data/app.db"), "not a database").expect("could not write file");
+
+    assert!(snapshot::database(&backup, "data/app.db", &staging_dir).is_err());
+
+    snapshot::clean(&staging_dir);
+    _ = fs::remove_dir_all(client_dir);
+}
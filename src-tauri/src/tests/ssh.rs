@@ -19,6 +19,14 @@ async fn test_connection() {
             .parse()
             .expect("SSH_PORT must be a number"),
         allow_background_backup: true,
+        identity_file: std::env::var("SSH_IDENTITY_FILE").ok(),
+        proxy_jump: std::env::var("SSH_PROXY_JUMP").ok(),
+        ssh_options: None,
+        known_hosts_file: None,
+        notifications: None,
+        metrics_port: None,
+        retry: None,
+        max_transfers: None,
     };
     let connection = connect::to_server(config, PathBuf::from(control_directory)).await;
     if let Err(e) = &connection {
@@ -47,6 +55,14 @@ async fn test_sftp_client() {
             .parse()
             .expect("SSH_PORT must be a number"),
         allow_background_backup: true,
+        identity_file: std::env::var("SSH_IDENTITY_FILE").ok(),
+        proxy_jump: std::env::var("SSH_PROXY_JUMP").ok(),
+        ssh_options: None,
+        known_hosts_file: None,
+        notifications: None,
+        metrics_port: None,
+        retry: None,
+        max_transfers: None,
     };
     let client = connect::Connection::new(config, PathBuf::from(control_directory))
         .await
@@ -0,0 +1,141 @@
+use crate::control::DaemonStatus;
+use crate::jobs::retry::FailureKind;
+use crate::jobs::status::{describe, list, State};
+use crate::jobs::Attempt;
+use crate::models::history::{Run, RunStatus, Transfer, Trigger};
+
+const JOB: &str = "/home/test/documents_/backups_backup";
+const WATCHER: &str = "/home/test/documents_/backups_backup_on_change";
+
+fn lists() -> DaemonStatus {
+    DaemonStatus {
+        pid: 1,
+        paused: false,
+        jobs: vec![],
+        failed_jobs: vec![],
+        retrying: vec![],
+        cancelled: vec![],
+        queued: vec![],
+    }
+}
+
+fn run(job_id: &str, status: RunStatus, error: Option<&str>, started_at: u64) -> Run {
+    Run {
+        job_id: job_id.to_string(),
+        client_path: String::from("/home/test/documents"),
+        server_path: String::from("/backups"),
+        trigger: Trigger::Manual,
+        started_at,
+        ended_at: started_at + 10,
+        transfer: Transfer {
+            files: 2,
+            bytes: 1024,
+        },
+        status,
+        error: error.map(String::from),
+        pid: Some(1),
+        hooks: None,
+    }
+}
+
+#[test]
+fn test_finished_job_reports_its_latest_run() {
+    let runs = [
+        run(JOB, RunStatus::Success, None, 100),
+        run(
+            JOB,
+            RunStatus::Failure,
+            Some("ssh: Connection refused"),
+            200,
+        ),
+    ];
+
+    let status = describe(JOB, &lists(), &runs);
+
+    assert_eq!(status.state, State::Failed);
+    assert_eq!(status.started_at, Some(200));
+    assert_eq!(status.ended_at, Some(210));
+    assert_eq!(status.error.as_deref(), Some("ssh: Connection refused"));
+    assert_eq!(status.error_kind, Some(FailureKind::Network));
+    assert_eq!(status.progress.map(|progress| progress.files), Some(2));
+}
+
+#[test]
+fn test_success_clears_error() {
+    let runs = [
+        run(JOB, RunStatus::Failure, Some("Permission denied"), 100),
+        run(JOB, RunStatus::Success, None, 200),
+    ];
+
+    let status = describe(JOB, &lists(), &runs);
+
+    assert_eq!(status.state, State::Completed);
+    assert_eq!(status.error, None);
+    assert_eq!(status.error_kind, None);
+}
+
+#[test]
+fn test_retried_job_runs_with_its_attempt_and_previous_error() {
+    let attempt = Attempt {
+        job_id: JOB.to_string(),
+        attempt: 2,
+        max_attempts: 3,
+        next_attempt_at: None,
+    };
+    let lists = DaemonStatus {
+        jobs: vec![JOB.to_string()],
+        retrying: vec![attempt.clone()],
+        ..lists()
+    };
+    let runs = [
+        run(JOB, RunStatus::Failure, Some("Connection reset"), 100),
+        run(JOB, RunStatus::Running, None, 200),
+    ];
+
+    let status = describe(JOB, &lists, &runs);
+
+    assert_eq!(status.state, State::Running);
+    assert_eq!(status.attempt, Some(attempt));
+    assert_eq!(status.started_at, Some(200));
+    assert_eq!(status.ended_at, None);
+    assert_eq!(status.error.as_deref(), Some("Connection reset"));
+}
+
+#[test]
+fn test_stopped_watcher_is_paused() {
+    let lists = DaemonStatus {
+        paused: true,
+        ..lists()
+    };
+
+    assert_eq!(describe(WATCHER, &lists, &[]).state, State::Paused);
+    assert_eq!(describe(JOB, &lists, &[]).state, State::Completed);
+}
+
+#[test]
+fn test_jobs_are_listed_once() {
+    let lists = DaemonStatus {
+        jobs: vec![WATCHER.to_string()],
+        queued: vec![JOB.to_string(), WATCHER.to_string()],
+        ..lists()
+    };
+    let runs = [
+        run(JOB, RunStatus::Success, None, 100),
+        run("other_backup", RunStatus::Cancelled, None, 200),
+    ];
+
+    let statuses = list(&lists, &runs);
+    let states: Vec<(&str, State)> = statuses
+        .iter()
+        .map(|status| (status.job_id.as_str(), status.state))
+        .collect();
+
+    assert_eq!(
+        states,
+        [
+            (JOB, State::Queued),
+            (WATCHER, State::Queued),
+            ("other_backup", State::Cancelled),
+        ]
+    );
+}
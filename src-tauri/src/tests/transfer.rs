@@ -0,0 +1,176 @@
+use crate::jobs::retry::{classify, FailureKind};
+use crate::jobs::transfer::{with_retries, MAX_ATTEMPTS};
+use crate::models::history::Transfer;
+use crate::ssh::commands::{parse_itemized_files, rsync_result, Copied};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn rsync_exit(code: i32, stderr: &str) -> Output {
+    Command::new("sh")
+        .args([
+            "-c",
+            &format!(
+                "echo 'Number of regular files transferred: 2'; printf '{stderr}' >&2; exit {code}"
+            ),
+        ])
+        .output()
+        .expect("could not run sh")
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("bmu_transfer_{name}_{}", std::process::id()));
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("could not create temp dir");
+    fs::write(dir.join("notes.txt"), "first").expect("could not write file");
+    dir
+}
+
+fn copied(sent: &[&str]) -> Copied {
+    Copied {
+        transfer: Transfer { files: 1, bytes: 5 },
+        sent: sent.iter().map(PathBuf::from).collect(),
+        ..Copied::default()
+    }
+}
+
+#[test]
+fn test_vanished_files_are_warnings() {
+    let copied = rsync_result(&rsync_exit(
+        24,
+        "file has vanished: \"/home/test/documents/tmp.txt\"\\nrsync warning: some files vanished before they could be transferred (code 24)\\n",
+    ))
+    .expect("exit code 24 is a partial transfer");
+
+    assert!(copied.vanished);
+    assert_eq!(copied.transfer.files, 2);
+    assert_eq!(
+        copied.warnings,
+        ["file has vanished: \"/home/test/documents/tmp.txt\""]
+    );
+}
+
+#[test]
+fn test_partial_transfer_is_not_a_failure() {
+    let copied = rsync_result(&rsync_exit(
+        23,
+        "rsync: send_files failed to open \"/home/test/documents/secret\": Permission denied (13)\\n",
+    ))
+    .expect("exit code 23 is a partial transfer");
+
+    assert!(!copied.vanished);
+    assert_eq!(copied.warnings.len(), 1);
+}
+
+#[test]
+fn test_missing_source_is_a_failure() {
+    let error = rsync_result(&rsync_exit(
+        23,
+        "rsync: [sender] link_stat \"/home/test/documents\" failed: No such file or directory (2)\\nrsync error: some files/attrs were not transferred (see previous errors) (code 23)\\n",
+    ))
+    .expect_err("a missing source is not a partial transfer");
+
+    assert_eq!(classify(&error), FailureKind::MissingSource);
+}
+
+#[test]
+fn test_other_exit_codes_fail() {
+    assert!(rsync_result(&rsync_exit(12, "rsync: connection unexpectedly closed\\n")).is_err());
+    assert!(rsync_result(&rsync_exit(0, ""))
+        .expect("exit code 0 is a success")
+        .warnings
+        .is_empty());
+}
+
+#[test]
+fn test_files_changed_during_transfer_are_retried() {
+    let dir = temp_dir("retried");
+    let mut attempts = 0;
+
+    let copied = with_retries(&dir, &|_: &Path| false, || {
+        attempts += 1;
+        if attempts == 1 {
+            fs::write(dir.join("notes.txt"), "second version").expect("could not write file");
+        }
+        Ok(copied(&["notes.txt"]))
+    })
+    .expect("transfer failed");
+
+    assert_eq!(attempts, 2);
+    assert!(copied.warnings.is_empty());
+    assert_eq!(copied.transfer.files, 2);
+    _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_retries_are_bounded() {
+    let dir = temp_dir("bounded");
+    let mut attempts = 0;
+
+    let copied = with_retries(&dir, &|_: &Path| false, || {
+        attempts += 1;
+        fs::write(dir.join("notes.txt"), "x".repeat(attempts)).expect("could not write file");
+        Ok(copied(&["notes.txt"]))
+    })
+    .expect("transfer failed");
+
+    assert_eq!(attempts, MAX_ATTEMPTS);
+    assert_eq!(copied.warnings.len(), 1);
+    assert!(copied.warnings[0].ends_with("notes.txt changed during the transfer"));
+    _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_skipped_files_are_not_retried() {
+    let dir = temp_dir("skipped");
+    let mut attempts = 0;
+
+    let copied = with_retries(&dir, &|path: &Path| path.ends_with("app.db"), || {
+        attempts += 1;
+        fs::write(dir.join("app.db"), "x".repeat(attempts)).expect("could not write file");
+        Ok(copied(&["app.db"]))
+    })
+    .expect("transfer failed");
+
+    assert_eq!(attempts, 1);
+    assert!(copied.warnings.is_empty());
+    _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_files_the_transfer_did_not_send_are_not_retried() {
+    let dir = temp_dir("untouched");
+    let mut attempts = 0;
+
+    let copied = with_retries(&dir, &|_: &Path| false, || {
+        attempts += 1;
+        fs::write(dir.join("other.txt"), "x".repeat(attempts)).expect("could not write file");
+        Ok(copied(&["notes.txt"]))
+    })
+    .expect("transfer failed");
+
+    assert_eq!(attempts, 1);
+    assert!(copied.warnings.is_empty());
+    _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_parse_itemized_files() {
+    let stdout = "sending incremental file list
+cd+++++++++ documents/
+>f+++++++++ documents/notes.txt
+<f.st...... documents/with space.txt
+cL+++++++++ documents/link -> notes.txt
+
+Number of files: 3 (reg: 2, dir: 1)
+";
+
+    assert_eq!(
+        parse_itemized_files(stdout),
+        [
+            PathBuf::from("documents/notes.txt"),
+            PathBuf::from("documents/with space.txt")
+        ]
+    );
+}
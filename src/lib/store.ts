@@ -1,8 +1,10 @@
 import { writable } from 'svelte/store';
 import type { Config } from '../../src-tauri/bindings/Config';
 import type { Backup } from '../../src-tauri/bindings/Backup';
+import type { DaemonStatus } from '../../src-tauri/bindings/DaemonStatus';
 
 export const clientDefaults: App.Config = {theme: 'light'};
 export const serverConfig = writable<Config | undefined>(undefined);
 export const clientConfig = writable<App.Config>(clientDefaults);
 export const backups = writable<Backup[]>([]);
+export const daemonStatus = writable<DaemonStatus | null>(null);